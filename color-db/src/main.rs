@@ -1,6 +1,7 @@
 use std::{collections::HashMap, path::Path};
 
 use sqlx::{sqlite::SqliteConnectOptions, Acquire, SqliteConnection, SqlitePool};
+use studio_paths::StudioFile;
 
 pub mod ldraw;
 
@@ -32,34 +33,39 @@ async fn main() -> Result<()> {
 }
 
 async fn load(conn: &mut SqliteConnection) -> Result<()> {
-    let ldraw_paths = [
-        "/mnt/c/program files/studio 2.0/ldraw/ldconfig.ldr",
-        "/mnt/c/users/andrew/documents/lego/rendering/ldraw/ldconfig.ldr",
-        "/mnt/c/users/andrew/documents/lego/rendering/ldraw/ldcfgalt.ldr",
-    ];
-    let ldraw_names = ["studio", "ldraw", "ldraw_alt"];
-    for (name, path) in ldraw_names.iter().zip(ldraw_paths) {
+    let install = studio_paths::find_install().ok_or("couldn't find a Studio installation")?;
+
+    // The LDraw library's own color tables, if there is one to compare against.
+    let mut ldraw_paths = vec![("studio", install.require(StudioFile::LdConfig)?)];
+    match std::env::var_os("LDRAW_DIR") {
+        Some(dir) => {
+            let dir = std::path::PathBuf::from(dir);
+            for (name, file) in [("ldraw", "ldconfig.ldr"), ("ldraw_alt", "ldcfgalt.ldr")] {
+                let path = studio_paths::resolve(&dir, file)
+                    .ok_or_else(|| format!("{file} isn't in LDRAW_DIR ({})", dir.display()))?;
+                ldraw_paths.push((name, path));
+            }
+        }
+        None => eprintln!(
+            "warning: LDRAW_DIR isn't set, so the ldraw and ldraw_alt tables are left out"
+        ),
+    }
+    for (name, path) in ldraw_paths {
         let ldconfig = std::fs::read_to_string(path)?;
         ldraw::insert_file(&ldconfig, name, &mut *conn).await?;
     }
 
-    let studio_paths = [
-        "/mnt/c/program files/studio 2.0/data/studiocolordefinition.txt",
-        "/mnt/c/program files/studio 2.0/data/customcolordefinition.txt",
-        "/mnt/c/program files/studio 2.0/data/customcolors/customcolordefinition.txt",
-    ];
     let studio_names = ["studio", "custom1", "custom2"];
-    for (name, path) in studio_names.iter().zip(studio_paths) {
+    for (name, file) in studio_names.iter().zip(StudioFile::COLOR_DEFINITIONS) {
+        let path = install.require(file)?;
         let definitions = std::fs::read_to_string(path)?;
         studio::insert_file(&definitions, name, &mut *conn).await?;
     }
 
-    let eyesight_paths = [
-        "/mnt/c/program files/studio 2.0/photorealisticrenderer/win/64/settings.xml",
-        "/mnt/c/program files/studio 2.0/data/customcolors/customcolorsettings.xml",
-    ];
+    let eyesight_files = [StudioFile::Settings, StudioFile::CustomColorSettings];
     let eyesight_names = ["eyesight", "custom", "unpixelled"];
-    for (name, path) in eyesight_names.iter().zip(eyesight_paths) {
+    for (name, file) in eyesight_names.iter().zip(eyesight_files) {
+        let path = install.require(file)?;
         let definitions = std::fs::read_to_string(path)?;
         eyesight::insert_file(&definitions, name, &mut *conn).await?;
     }
@@ -157,11 +157,14 @@ fn parse_cmyk(s: &str) -> Option<u32> {
 #[cfg(test)]
 #[test]
 fn test_parse_line() {
-    let base =
-        include_str!("/mnt/c/program files/studio 2.0 earlyaccess/data/StudioColorDefinition.txt");
-
-    let custom =
-        include_str!("/mnt/c/program files/studio 2.0 earlyaccess/data/CustomColorDefinition.txt");
+    use studio_paths::{locate_preferring, Channel, StudioFile};
+
+    let read = |file| {
+        let path = locate_preferring(Channel::EarlyAccess, file).expect("no Studio install");
+        std::fs::read_to_string(path).unwrap()
+    };
+    let base = read(StudioFile::StudioColorDefinition);
+    let custom = read(StudioFile::CustomColorDefinition);
 
     let lines = base.lines().skip(1).chain(custom.lines().skip(1));
 
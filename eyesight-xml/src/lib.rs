@@ -14,15 +14,36 @@ macro_rules! enums {
             pub enum $enum {
                 $($variant),*
             }
+
+            impl crate::EyesightEnum for $enum {
+                const NAME: &str = stringify!($enum);
+            }
         )*
+
+        /// Every enum declared here, with its variants.
+        pub const ENUMS: &[(&str, &[&str])] = &[
+            $((stringify!($enum), &[$(stringify!($variant)),*])),*
+        ];
     }
 }
 
+/// An enum read from an XML attribute.
+pub trait EyesightEnum: std::fmt::Debug + Copy {
+    const NAME: &str;
+}
+
 #[enum_dispatch]
 pub trait Named {
     fn name(&self) -> &str;
     fn name_mut(&mut self) -> &mut String;
 }
 
+/// Something that knows where it was defined.
+pub trait Spanned {
+    fn span(&self) -> &span::Span;
+    fn span_mut(&mut self) -> &mut span::Span;
+}
+
 pub mod nodes;
 pub mod schema;
+pub mod span;
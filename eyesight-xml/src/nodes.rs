@@ -1,16 +1,18 @@
 use std::borrow::Cow;
-use std::fmt::Debug;
+use std::collections::HashMap;
+use std::sync::OnceLock;
 
 use enum_dispatch::enum_dispatch;
 use glam::Vec4;
-use heck::ToShoutySnakeCase;
+use heck::{ToShoutySnakeCase, ToSnakeCase};
 use serde::de::{Deserialize, Deserializer, Error, IgnoredAny, Unexpected};
 use serde_derive::Deserialize;
 use serde_with::{serde_as, DisplayFromStr};
 
 use xml2py_macros::node;
 
-use crate::Named;
+use crate::span::Span;
+use crate::{EyesightEnum, Named, Spanned};
 
 pub trait INode: Named {
     const PYTHON_TYPE: &str;
@@ -26,14 +28,38 @@ pub trait INode: Named {
     fn attributes(&self) -> Vec<(&str, String)> {
         vec![]
     }
-    fn after(&self) -> Vec<String> {
+    /// Extra statements to run once the node exists.
+    /// The argument is the Python expression that evaluates to the `bpy.types.Node`.
+    fn after(&self, _node: &str) -> Vec<String> {
         vec![]
     }
 }
 
-pub fn python_enum(x: impl Debug) -> String {
+/// Mutable access to a node's `inputs`, for the nodes that keep them as `NodeInput`s.
+/// `#[node]` implements this.
+pub trait InputsMut {
+    fn inputs_mut(&mut self) -> Option<&mut Vec<NodeInput>>;
+}
+
+static ENUM_TRANSLATIONS: OnceLock<HashMap<(String, String), String>> = OnceLock::new();
+
+/// Overrides the Python spelling of enum values, keyed by enum name and XML value.
+/// Values without a translation are spelled in shouty snake case.
+/// Can only be called once, before any enum is converted.
+pub fn set_enum_translations(translations: HashMap<(String, String), String>) {
+    ENUM_TRANSLATIONS
+        .set(translations)
+        .expect("enum translations were already set");
+}
+
+pub fn python_enum<E: EyesightEnum>(x: E) -> String {
+    let variant = format!("{x:?}");
+    let key = (E::NAME.to_owned(), variant.to_snake_case());
+    if let Some(translation) = ENUM_TRANSLATIONS.get_or_init(HashMap::new).get(&key) {
+        return format!("'{translation}'");
+    }
     // Hideous.
-    format!("'{}'", format!("{x:?}").to_shouty_snake_case())
+    format!("'{}'", variant.to_shouty_snake_case())
 }
 
 #[node]
@@ -48,6 +74,7 @@ impl NodeInput {
         Self {
             name: name.into(),
             value: value.into(),
+            span: Span::default(),
         }
     }
 }
@@ -248,11 +275,12 @@ enums! {
         Minimum,
         Maximum,
         LessThan,
+        GreaterThan,
         Power,
     }
 
     Axis { X, Y, Z }
-    VectorOperation { Average, Multiply, Add, Scale }
+    VectorOperation { Average, Multiply, Add, Scale, DotProduct }
     BsdfDistribution { Ggx }
     Projection { Flat }
     VectorType { Point }
@@ -291,8 +319,8 @@ pub struct TexMapping {
 }
 
 impl TexMapping {
-    fn to_python(&self, var: &str) -> Vec<String> {
-        let tm = format!("{var}.node.texture_mapping");
+    fn to_python(self, node: &str) -> Vec<String> {
+        let tm = format!("{node}.texture_mapping");
         let mut v = vec![
             format!("{tm}.rotation = {}", self.rotation),
             format!("{tm}.scale = {}", self.scale),
@@ -332,19 +360,20 @@ struct GroupReference {
 
 impl INode for GroupReference {
     const PYTHON_TYPE: &str = "ShaderNodeGroup";
-    fn after(&self) -> Vec<String> {
+    fn after(&self, node: &str) -> Vec<String> {
         self.inputs_
             .iter()
             .filter_map(|input| {
                 input.value.map(|value| NodeInput {
                     name: input.name.clone(),
                     value,
+                    span: Span::default(),
                 })
             })
             .map(|input| {
                 format!(
-                    "{}.node.inputs['{}'].default_value = {}",
-                    self.name, input.name, input.value
+                    "{node}.inputs['{}'].default_value = {}",
+                    input.name, input.value
                 )
             })
             .collect()
@@ -404,8 +433,8 @@ impl INode for NoiseTexture {
     fn inputs(&self) -> &[NodeInput] {
         &self.inputs
     }
-    fn after(&self) -> Vec<String> {
-        self.tex_mapping.to_python(&self.name)
+    fn after(&self, node: &str) -> Vec<String> {
+        self.tex_mapping.to_python(node)
     }
 }
 
@@ -440,9 +469,6 @@ struct SwitchClosure {
 
 impl INode for SwitchClosure {
     const PYTHON_TYPE: &str = "ShaderNodeMixShader";
-    fn attributes(&self) -> Vec<(&str, String)> {
-        vec![("mute", python_bool(!self.enable))]
-    }
 }
 
 #[node]
@@ -488,18 +514,15 @@ impl INode for Mapping {
     fn inputs_override(&self) -> Vec<NodeInput> {
         let mut v = self.inputs.clone();
         v.extend([
-            NodeInput {
-                name: "Location".into(),
-                value: NodeInputValue::Vector(self.tex_mapping.translation),
-            },
-            NodeInput {
-                name: "Rotation".into(),
-                value: NodeInputValue::Vector(self.tex_mapping.rotation),
-            },
-            NodeInput {
-                name: "Scale".into(),
-                value: NodeInputValue::Vector(self.tex_mapping.scale),
-            },
+            NodeInput::new(
+                "Location",
+                NodeInputValue::Vector(self.tex_mapping.translation),
+            ),
+            NodeInput::new(
+                "Rotation",
+                NodeInputValue::Vector(self.tex_mapping.rotation),
+            ),
+            NodeInput::new("Scale", NodeInputValue::Vector(self.tex_mapping.scale)),
         ]);
         v
     }
@@ -520,14 +543,12 @@ struct RgbRamp {
 
 impl INode for RgbRamp {
     const PYTHON_TYPE: &str = "ShaderNodeValToRGB";
-    fn after(&self) -> Vec<String> {
+    fn after(&self, node: &str) -> Vec<String> {
         let interpolation = if self.interpolate {
             "LINEAR"
         } else {
             "CONSTANT"
         };
-        let var = &self.name;
-
         let colors = self
             .ramp
             .chunks_exact(3)
@@ -555,13 +576,13 @@ impl INode for RgbRamp {
             .collect::<Vec<_>>();
 
         vec![
-            format!("{var}.node.color_ramp.interpolation = '{interpolation}'"),
-            format!("original_elements = {var}.node.color_ramp.elements[:]"),
+            format!("{node}.color_ramp.interpolation = '{interpolation}'"),
+            format!("original_elements = {node}.color_ramp.elements[:]"),
             format!("elements = {elements:?}"),
             format!("for pos, rgba in elements:"),
-            format!("    {var}.node.color_ramp.elements.new(pos).color = rgba"),
+            format!("    {node}.color_ramp.elements.new(pos).color = rgba"),
             format!("for e in original_elements:"),
-            format!("    {var}.node.color_ramp.elements.remove(e)"),
+            format!("    {node}.color_ramp.elements.remove(e)"),
         ]
     }
 }
@@ -592,11 +613,8 @@ struct Value {
 
 impl INode for Value {
     const PYTHON_TYPE: &str = "ShaderNodeValue";
-    fn after(&self) -> Vec<String> {
-        vec![format!(
-            "{}.node.outputs[0].default_value = {}",
-            self.name, self.value,
-        )]
+    fn after(&self, node: &str) -> Vec<String> {
+        vec![format!("{node}.outputs[0].default_value = {}", self.value)]
     }
 }
 
@@ -682,12 +700,6 @@ impl INode for SwitchFloat {
     fn inputs(&self) -> &[NodeInput] {
         &self.inputs
     }
-    fn attributes(&self) -> Vec<(&str, String)> {
-        vec![
-            ("data_type", "'FLOAT'".into()),
-            ("mute", python_bool(!self.enable)),
-        ]
-    }
 }
 
 #[node]
@@ -697,6 +709,9 @@ struct UvDegradation {
 
 impl INode for UvDegradation {
     const PYTHON_TYPE: &str = "ShaderNodeGroup";
+    fn inputs(&self) -> &[NodeInput] {
+        &self.inputs
+    }
 }
 
 #[node]
@@ -786,25 +801,6 @@ impl INode for PrincipledBsdf {
         }
         v
     }
-
-    fn inputs_override(&self) -> Vec<NodeInput> {
-        self.inputs
-            .iter()
-            .cloned()
-            .map(|mut i| {
-                if i.name.ends_with("Tint") {
-                    if let NodeInputValue::Float(n) = i.value {
-                        i.value = NodeInputValue::Color(Vec3([n, n, n]));
-                    }
-                } else if i.name == "SubsurfaceColor" {
-                    if let NodeInputValue::Color(x) = i.value {
-                        i.value = NodeInputValue::Vector(x)
-                    }
-                }
-                i
-            })
-            .collect()
-    }
 }
 
 #[node]
@@ -878,13 +874,12 @@ struct Vector {
 
 impl INode for Vector {
     const PYTHON_TYPE: &str = "ShaderNodeCombineXYZ";
-    fn after(&self) -> Vec<String> {
-        let var = &self.name;
+    fn after(&self, node: &str) -> Vec<String> {
         let [x, y, z] = self.value.0;
         vec![
-            format!("{var}.node.inputs[0].default_value = {x}"),
-            format!("{var}.node.inputs[1].default_value = {y}"),
-            format!("{var}.node.inputs[2].default_value = {z}"),
+            format!("{node}.inputs[0].default_value = {x}"),
+            format!("{node}.inputs[1].default_value = {y}"),
+            format!("{node}.inputs[2].default_value = {z}"),
         ]
     }
 }
@@ -986,8 +981,9 @@ struct Color {
 
 impl INode for Color {
     const PYTHON_TYPE: &str = "ShaderNodeRGB";
-    fn attributes(&self) -> Vec<(&str, String)> {
-        vec![("value", self.value.to_string())]
+    fn after(&self, node: &str) -> Vec<String> {
+        let value = NodeInputValue::Color(self.value);
+        vec![format!("{node}.outputs[0].default_value = {value}")]
     }
 }
 
@@ -1013,6 +1009,43 @@ macro_rules! nodes {
             $($ty($ty),)*
         }
 
+        impl Node {
+            /// The variant name of every kind of node.
+            pub const KINDS: &[&str] = &["Group", $(stringify!($ty)),*];
+
+            pub fn kind(&self) -> &'static str {
+                match self {
+                    Self::Group(_) => "Group",
+                    $(Self::$ty(_) => stringify!($ty),)*
+                }
+            }
+        }
+
+        impl Spanned for Node {
+            fn span(&self) -> &Span {
+                match self {
+                    Self::Group(x) => x.span(),
+                    $(Self::$ty(x) => x.span(),)*
+                }
+            }
+
+            fn span_mut(&mut self) -> &mut Span {
+                match self {
+                    Self::Group(x) => x.span_mut(),
+                    $(Self::$ty(x) => x.span_mut(),)*
+                }
+            }
+        }
+
+        impl InputsMut for Node {
+            fn inputs_mut(&mut self) -> Option<&mut Vec<NodeInput>> {
+                match self {
+                    Self::Group(x) => x.inputs_mut(),
+                    $(Self::$ty(x) => x.inputs_mut(),)*
+                }
+            }
+        }
+
         impl INode for Node {
             const PYTHON_TYPE: &str = "";
             fn python_type(&self) -> &'static str {
@@ -1039,10 +1072,10 @@ macro_rules! nodes {
                     $(Self::$ty(x) => x.attributes(),)*
                 }
             }
-            fn after(&self) -> Vec<String> {
+            fn after(&self, node: &str) -> Vec<String> {
                 match self {
-                    Self::Group(x) => x.after(),
-                    $(Self::$ty(x) => x.after(),)*
+                    Self::Group(x) => x.after(node),
+                    $(Self::$ty(x) => x.after(node),)*
                 }
             }
         }
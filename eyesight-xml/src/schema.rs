@@ -1,9 +1,12 @@
-use crate::Named;
+use crate::span::Span;
+use crate::{Named, Spanned};
 use serde_derive::Deserialize;
-use xml2py_macros::node;
 
 #[derive(Deserialize, Debug)]
 pub struct Eyesight {
+    /// The Studio release that wrote the file, if the root element says.
+    #[serde(default, rename = "@version")]
+    pub version: Option<String>,
     #[serde(default, rename = "material")]
     pub materials: Vec<Material>,
     #[serde(default, rename = "group")]
@@ -24,6 +27,17 @@ pub struct Group {
     #[serde(rename = "@name")]
     pub name: String,
     pub shader: Shader,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+impl Spanned for Group {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+    fn span_mut(&mut self) -> &mut Span {
+        &mut self.span
+    }
 }
 
 impl Named for Group {
@@ -41,24 +55,57 @@ enums! {
     VolumeSamplingMethod { MultipleImportance }
 }
 
-#[node]
+#[derive(Deserialize, Debug, PartialEq, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct Material {
-    name: String,
-    displacement_method: DisplacementMethod,
-    heterogeneous_volume: bool,
-    use_local_tuning: bool,
-    use_mis: bool,
-    use_transparent_shadow: bool,
-    volume_interpolation_method: VolumeInterpolationMethod,
-    volume_sampling_method: VolumeSamplingMethod,
-    diffuse_ao_factor: Option<f32>,
-    glossy_ao_factor: Option<f32>,
-    subsurface_ao_factor: Option<f32>,
-    subsurface_factor: Option<f32>,
-    transmission_ao_factor: Option<f32>,
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@displacement_method")]
+    pub displacement_method: DisplacementMethod,
+    #[serde(rename = "@heterogeneous_volume")]
+    pub heterogeneous_volume: bool,
+    #[serde(rename = "@use_local_tuning")]
+    pub use_local_tuning: bool,
+    #[serde(rename = "@use_mis")]
+    pub use_mis: bool,
+    #[serde(rename = "@use_transparent_shadow")]
+    pub use_transparent_shadow: bool,
+    #[serde(rename = "@volume_interpolation_method")]
+    pub volume_interpolation_method: VolumeInterpolationMethod,
+    #[serde(rename = "@volume_sampling_method")]
+    pub volume_sampling_method: VolumeSamplingMethod,
+    #[serde(rename = "@diffuse_ao_factor")]
+    pub diffuse_ao_factor: Option<f32>,
+    #[serde(rename = "@glossy_ao_factor")]
+    pub glossy_ao_factor: Option<f32>,
+    #[serde(rename = "@subsurface_ao_factor")]
+    pub subsurface_ao_factor: Option<f32>,
+    #[serde(rename = "@subsurface_factor")]
+    pub subsurface_factor: Option<f32>,
+    #[serde(rename = "@transmission_ao_factor")]
+    pub transmission_ao_factor: Option<f32>,
+
+    pub shader: Shader,
+    #[serde(skip)]
+    pub span: Span,
+}
+
+impl Spanned for Material {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+    fn span_mut(&mut self) -> &mut Span {
+        &mut self.span
+    }
+}
 
-    #[rename = "shader"]
-    shader: Shader,
+impl Named for Material {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    fn name_mut(&mut self) -> &mut String {
+        &mut self.name
+    }
 }
 
 #[derive(Deserialize, Debug, Default, PartialEq, Clone)]
@@ -81,6 +128,8 @@ pub struct Link {
     pub from_socket: String,
     #[serde(rename = "@to_socket")]
     pub to_socket: String,
+    #[serde(skip)]
+    pub span: Span,
 }
 
 impl Link {
@@ -90,10 +139,20 @@ impl Link {
             to_node: to_node.into(),
             from_socket: from_socket.into(),
             to_socket: to_socket.into(),
+            span: Span::default(),
         }
     }
 }
 
+impl Spanned for Link {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+    fn span_mut(&mut self) -> &mut Span {
+        &mut self.span
+    }
+}
+
 impl std::fmt::Debug for Link {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
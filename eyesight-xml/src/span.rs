@@ -0,0 +1,264 @@
+//! Where groups, materials, nodes and links were defined, for pointing diagnostics
+//! at the right place in the XML.
+
+use std::cmp::Ordering;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use heck::ToSnakeCase;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+
+use crate::nodes::InputsMut;
+use crate::schema::{Eyesight, Shader};
+use crate::Spanned;
+
+/// The file something was read from, if it was read from one.
+///
+/// Definitions are compared by what they define, so any two sources are equal.
+#[derive(Debug, Default, Clone)]
+pub struct Source(Option<PathBuf>);
+
+impl Source {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self(Some(path.into()))
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.0.as_deref()
+    }
+}
+
+impl PartialEq for Source {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl std::fmt::Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.path().and_then(|p| p.file_name()) {
+            Some(name) => write!(f, "{}", name.to_string_lossy()),
+            None => f.write_str("(unknown)"),
+        }
+    }
+}
+
+/// Where something was defined. Things that weren't read from a file, like nodes
+/// added by passes, have no location.
+///
+/// Like sources, any two spans are equal, so that they don't affect comparisons.
+#[derive(Debug, Default, Clone)]
+pub struct Span {
+    pub file: Source,
+    pub location: Option<Location>,
+}
+
+/// An element's place in its file.
+#[derive(Debug, Clone)]
+pub struct Location {
+    /// From the start of the element's opening tag to the end of its closing tag.
+    pub bytes: Range<usize>,
+    /// The line the element starts on, counting from 1.
+    pub line: usize,
+}
+
+impl PartialEq for Span {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl PartialOrd for Span {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Span {
+    fn cmp(&self, _other: &Self) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(location) = &self.location {
+            write!(f, ":{}", location.line)?;
+        }
+        Ok(())
+    }
+}
+
+struct Element {
+    name: String,
+    depth: usize,
+    bytes: Range<usize>,
+}
+
+/// Fills in the spans of everything in `eyesight`, which was deserialized from `xml`,
+/// the contents of `path`.
+///
+/// Elements are matched up with what they became by their name and order, so this has
+/// to be done before anything is added, removed or reordered.
+pub fn locate(eyesight: &mut Eyesight, xml: &str, path: &Path) {
+    let mut reader = Reader::from_str(xml);
+    let mut elements: Vec<Element> = vec![];
+    let mut open = vec![];
+    loop {
+        let start = reader.buffer_position() as usize;
+        let event = reader
+            .read_event()
+            .expect("the XML has already been parsed");
+        let end = reader.buffer_position() as usize;
+        let is_start = matches!(event, Event::Start(_));
+        match event {
+            Event::Start(e) | Event::Empty(e) => {
+                let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+                elements.push(Element {
+                    name,
+                    depth: open.len(),
+                    bytes: start..end,
+                });
+                if is_start {
+                    open.push(elements.len() - 1);
+                }
+            }
+            Event::End(_) => {
+                let i = open.pop().unwrap();
+                elements[i].bytes.end = end;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    if elements.is_empty() {
+        return;
+    }
+
+    let elements = &elements[..];
+    let line_starts = std::iter::once(0)
+        .chain(xml.match_indices('\n').map(|(i, _)| i + 1))
+        .collect::<Vec<_>>();
+    let span = |i: usize| {
+        let bytes = elements[i].bytes.clone();
+        Span {
+            file: Source::new(path),
+            location: Some(Location {
+                line: line_starts.partition_point(|&s| s <= bytes.start),
+                bytes,
+            }),
+        }
+    };
+    let children = |i: usize| {
+        let depth = elements[i].depth;
+        (i + 1..elements.len())
+            .take_while(move |&j| elements[j].depth > depth)
+            .filter(move |&j| elements[j].depth == depth + 1)
+    };
+    let named =
+        move |i: usize, name: &'static str| children(i).filter(move |&j| elements[j].name == name);
+
+    let locate_shader = |shader: &mut Shader, i: usize| {
+        let (mut nodes, mut links) = (shader.nodes.iter_mut(), shader.links.iter_mut());
+        for j in children(i) {
+            if elements[j].name == "connect" {
+                if let Some(link) = links.next() {
+                    link.span = span(j);
+                }
+                continue;
+            }
+            let name = &elements[j].name;
+            let Some(node) = nodes.find(|node| node.kind().to_snake_case() == *name) else {
+                continue;
+            };
+            *node.span_mut() = span(j);
+            if let Some(inputs) = node.inputs_mut() {
+                for (input, k) in inputs.iter_mut().zip(named(j, "input")) {
+                    input.span = span(k);
+                }
+            }
+        }
+    };
+
+    let (mut materials, mut groups) = (eyesight.materials.iter_mut(), eyesight.groups.iter_mut());
+    for i in children(0) {
+        let definition: (&mut Span, &mut Shader) = match &*elements[i].name {
+            "material" => match materials.next() {
+                Some(m) => (&mut m.span, &mut m.shader),
+                None => continue,
+            },
+            "group" => match groups.next() {
+                Some(g) => (&mut g.span, &mut g.shader),
+                None => continue,
+            },
+            _ => continue,
+        };
+        *definition.0 = span(i);
+        if let Some(j) = named(i, "shader").next() {
+            locate_shader(definition.1, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nodes::INode;
+
+    const XML: &str = r#"<eyesight>
+  <group name="G">
+    <shader>
+      <group_input name="in"/>
+      <connect from_node="in" from_socket="X" to_node="diffuse" to_socket="Color"/>
+      <diffuse_bsdf name="diffuse">
+        <input name="Roughness" type="float" value="0.5"/>
+      </diffuse_bsdf>
+      <connect from_node="diffuse" from_socket="BSDF" to_node="out" to_socket="BSDF"/>
+      <group_output name="out"/>
+    </shader>
+  </group>
+  <material name="M" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance">
+    <shader>
+      <value name="v" value="1"/>
+      <connect from_node="v" from_socket="Value" to_node="output" to_socket="Displacement"/>
+    </shader>
+  </material>
+</eyesight>"#;
+
+    fn line(span: &Span) -> usize {
+        span.location.as_ref().unwrap().line
+    }
+
+    #[test]
+    fn locates_interleaved_nodes_and_links() {
+        let mut eyesight = quick_xml::de::from_str::<Eyesight>(XML).unwrap();
+        locate(&mut eyesight, XML, Path::new("test.xml"));
+
+        let group = &eyesight.groups[0];
+        assert_eq!(line(&group.span), 2);
+        let nodes = group
+            .shader
+            .nodes
+            .iter()
+            .map(|n| (n.kind(), line(n.span())))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            nodes,
+            [("GroupInput", 4), ("DiffuseBsdf", 6), ("GroupOutput", 10)]
+        );
+        let links = group.shader.links.iter().map(|l| line(&l.span));
+        assert_eq!(links.collect::<Vec<_>>(), [5, 9]);
+        let input = &group.shader.nodes[1].inputs()[0];
+        assert_eq!(line(&input.span), 7);
+
+        let material = &eyesight.materials[0];
+        assert_eq!(line(&material.span), 13);
+        assert_eq!(line(material.shader.nodes[0].span()), 15);
+        assert_eq!(line(&material.shader.links[0].span), 16);
+        assert_eq!(material.span.to_string(), "test.xml:13");
+    }
+}
@@ -3,11 +3,14 @@ use schema::*;
 pub mod schema;
 
 fn main() {
-    let settings = include_str!(
-        "/mnt/c/program files/studio 2.0 earlyaccess/photorealisticrenderer/win/64/settings_camera_light.xml"
-    );
+    let path = studio_paths::locate_preferring(
+        studio_paths::Channel::EarlyAccess,
+        studio_paths::StudioFile::CameraLightSettings,
+    )
+    .expect("couldn't find settings_camera_light.xml; set STUDIO_HOME to the Studio install");
+    let settings = std::fs::read_to_string(path).unwrap();
 
-    let eyesight: schema::Eyesight = quick_xml::de::from_str(settings).unwrap();
+    let eyesight: schema::Eyesight = quick_xml::de::from_str(&settings).unwrap();
 
     let mut preset = eyesight
         .presets
@@ -181,23 +181,26 @@ pub struct CameraInner {
 macro_rules! enums {
     (
         $(
+            $(#[$attr:meta])*
             $enum:ident {
-                $($variant:ident),*$(,)?
+                $($(#[$variant_attr:meta])* $variant:ident),*$(,)?
             }
         )*
     ) => {
         $(
             #[derive(Deserialize, Debug)]
             #[serde(rename_all = "lowercase")]
+            $(#[$attr])*
             pub enum $enum {
-                $($variant),*
+                $($(#[$variant_attr])* $variant),*
             }
         )*
     }
 }
 
 enums! {
-    LightType { Point, Distant, Background, Area, Spot, Triangle, Ambient }
+    #[derive(Default)]
+    LightType { Point, #[default] Distant, Background, Area, Spot, Triangle, Ambient }
     ApertureType { Radius, FStop }
     MotionPosition { Start, Center, End }
     RollingShutterType { None, Top }
@@ -205,12 +208,6 @@ enums! {
     CameraType { Perspective, Orthographic, Panorama }
 }
 
-impl Default for LightType {
-    fn default() -> Self {
-        Self::Distant
-    }
-}
-
 fn float_arr<'de, D: Deserializer<'de>, T: Default + FromStr, const N: usize>(
     de: D,
 ) -> Result<[T; N], D::Error> {
@@ -234,5 +231,5 @@ where
     T::Err: Display,
 {
     let s: &'de str = Deserialize::deserialize(de)?;
-    s.parse().map_err(|e| D::Error::custom(e))
+    s.parse().map_err(D::Error::custom)
 }
@@ -0,0 +1,330 @@
+//! Finds BrickLink Studio installations and the files we read from them.
+//!
+//! Installs are looked for, in order of preference, in:
+//!
+//! - `STUDIO_HOME`, which names an install directory
+//! - `ProgramFiles` and `ProgramFiles(x86)`, when running on Windows
+//! - the Windows drives WSL mounts under `/mnt`
+//! - Wine prefixes: `WINEPREFIX`, `~/.wine`, Bottles' bottles and Lutris' games
+//!
+//! Within each place, stable installs come before early access ones.
+//! Windows doesn't care about case in file names and neither does Studio,
+//! so every path below an install is matched case-insensitively.
+
+use std::path::{Path, PathBuf};
+
+/// The directory names Studio installs itself under, inside `Program Files`.
+const INSTALL_DIRS: [(&str, Channel); 2] = [
+    ("Studio 2.0", Channel::Stable),
+    ("Studio 2.0 EarlyAccess", Channel::EarlyAccess),
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Channel {
+    Stable,
+    EarlyAccess,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Install {
+    pub root: PathBuf,
+    pub channel: Channel,
+}
+
+/// A file in a Studio install that something here reads.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StudioFile {
+    /// The Eyesight renderer's materials and groups.
+    Settings,
+    /// Eyesight materials for custom colors.
+    CustomColorSettings,
+    /// The Eyesight renderer's camera and lighting presets.
+    CameraLightSettings,
+    /// The LDraw color table Studio ships.
+    LdConfig,
+    StudioColorDefinition,
+    CustomColorDefinition,
+    /// Definitions for the colors in `CustomColors`, as opposed to `CustomColorDefinition`.
+    CustomColorsColorDefinition,
+}
+
+impl StudioFile {
+    /// The color definition files, in the order Studio reads them.
+    pub const COLOR_DEFINITIONS: [Self; 3] = [
+        Self::StudioColorDefinition,
+        Self::CustomColorDefinition,
+        Self::CustomColorsColorDefinition,
+    ];
+
+    /// The path relative to the install directory, as Studio spells it.
+    pub fn relative_path(self) -> &'static str {
+        match self {
+            Self::Settings => "PhotoRealisticRenderer/win/64/settings.xml",
+            Self::CustomColorSettings => "data/CustomColors/CustomColorSettings.xml",
+            Self::CameraLightSettings => "PhotoRealisticRenderer/win/64/settings_camera_light.xml",
+            Self::LdConfig => "ldraw/ldconfig.ldr",
+            Self::StudioColorDefinition => "data/StudioColorDefinition.txt",
+            Self::CustomColorDefinition => "data/CustomColorDefinition.txt",
+            Self::CustomColorsColorDefinition => "data/CustomColors/CustomColorDefinition.txt",
+        }
+    }
+}
+
+impl Install {
+    /// Where `file` is in this install, if it exists.
+    pub fn file(&self, file: StudioFile) -> Option<PathBuf> {
+        resolve(&self.root, file.relative_path())
+    }
+
+    /// Where `file` is in this install, or an error saying that it's missing.
+    pub fn require(&self, file: StudioFile) -> Result<PathBuf, String> {
+        self.file(file).ok_or_else(|| {
+            format!(
+                "the Studio install at {} has no {}",
+                self.root.display(),
+                file.relative_path()
+            )
+        })
+    }
+
+    pub fn settings_xml(&self) -> Option<PathBuf> {
+        self.file(StudioFile::Settings)
+    }
+
+    pub fn custom_color_settings_xml(&self) -> Option<PathBuf> {
+        self.file(StudioFile::CustomColorSettings)
+    }
+
+    pub fn settings_camera_light_xml(&self) -> Option<PathBuf> {
+        self.file(StudioFile::CameraLightSettings)
+    }
+
+    pub fn ldconfig(&self) -> Option<PathBuf> {
+        self.file(StudioFile::LdConfig)
+    }
+
+    /// The color definition files this install has, in the order Studio reads them.
+    pub fn color_definitions(&self) -> Vec<PathBuf> {
+        StudioFile::COLOR_DEFINITIONS
+            .into_iter()
+            .filter_map(|file| self.file(file))
+            .collect()
+    }
+}
+
+/// Every install that could be found, most preferred first.
+pub fn find_installs() -> Vec<Install> {
+    let mut installs = vec![];
+
+    if let Some(home) = std::env::var_os("STUDIO_HOME") {
+        let root = PathBuf::from(home);
+        let name = root.file_name().unwrap_or_default().to_string_lossy();
+        let channel = if name.to_lowercase().contains("earlyaccess") {
+            Channel::EarlyAccess
+        } else {
+            Channel::Stable
+        };
+        installs.push(Install { root, channel });
+    }
+
+    for program_files in program_files_dirs() {
+        for (dir, channel) in INSTALL_DIRS {
+            if let Some(root) = resolve(&program_files, dir) {
+                let install = Install { root, channel };
+                if !installs.contains(&install) {
+                    installs.push(install);
+                }
+            }
+        }
+    }
+
+    installs
+}
+
+/// The most preferred install.
+pub fn find_install() -> Option<Install> {
+    find_installs().into_iter().next()
+}
+
+/// `file` from the most preferred install that has it.
+pub fn locate(file: StudioFile) -> Option<PathBuf> {
+    find_installs()
+        .iter()
+        .find_map(|install| install.file(file))
+}
+
+/// `file` from the most preferred install on `channel` that has it, or failing that,
+/// from any install.
+pub fn locate_preferring(channel: Channel, file: StudioFile) -> Option<PathBuf> {
+    let installs = find_installs();
+    let (preferred, others): (Vec<_>, Vec<_>) = installs
+        .iter()
+        .partition(|install| install.channel == channel);
+    preferred
+        .into_iter()
+        .chain(others)
+        .find_map(|install| install.file(file))
+}
+
+/// Every `Program Files` directory we know how to look in.
+fn program_files_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![];
+
+    for var in ["ProgramFiles", "ProgramFiles(x86)"] {
+        if let Some(dir) = std::env::var_os(var) {
+            dirs.push(PathBuf::from(dir));
+        }
+    }
+
+    let mut drives = vec![];
+
+    // WSL mounts each Windows drive as a single letter.
+    drives.extend(
+        subdirs(Path::new("/mnt"))
+            .into_iter()
+            .filter(|d| d.file_name().is_some_and(|n| n.len() == 1)),
+    );
+
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let mut prefixes = vec![];
+    if let Some(prefix) = std::env::var_os("WINEPREFIX") {
+        prefixes.push(PathBuf::from(prefix));
+    }
+    if let Some(home) = &home {
+        prefixes.push(home.join(".wine"));
+        for bottles in [
+            ".local/share/bottles/bottles",
+            ".var/app/com.usebottles.bottles/data/bottles/bottles",
+        ] {
+            prefixes.extend(subdirs(&home.join(bottles)));
+        }
+        // Lutris puts each game's prefix in its own directory under ~/Games.
+        prefixes.extend(subdirs(&home.join("Games")));
+    }
+    drives.extend(prefixes.iter().filter_map(|p| resolve(p, "drive_c")));
+
+    for drive in drives {
+        for name in ["Program Files", "Program Files (x86)"] {
+            if let Some(dir) = resolve(&drive, name) {
+                dirs.push(dir);
+            }
+        }
+    }
+
+    dirs
+}
+
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return vec![];
+    };
+    let mut dirs = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs
+}
+
+/// Finds `relative` (with `/` separators) under `base`, ignoring case in each component.
+/// An exact match wins over a case-insensitive one.
+pub fn resolve(base: &Path, relative: &str) -> Option<PathBuf> {
+    let mut path = base.to_owned();
+
+    for component in relative.split('/').filter(|c| !c.is_empty()) {
+        let exact = path.join(component);
+        if exact.exists() {
+            path = exact;
+            continue;
+        }
+
+        let entry = std::fs::read_dir(&path)
+            .ok()?
+            .filter_map(|e| e.ok())
+            .find(|e| e.file_name().to_string_lossy().to_lowercase() == component.to_lowercase())?;
+        path = entry.path();
+    }
+
+    path.exists().then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory for a test to build a fake install in.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("studio-paths-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn touch(path: &Path) {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+
+    #[test]
+    fn resolve_ignores_case() {
+        let dir = temp_dir("resolve");
+        let settings = dir.join("PhotoRealisticRenderer/WIN/64/Settings.XML");
+        touch(&settings);
+
+        let relative = StudioFile::Settings.relative_path();
+        assert_eq!(resolve(&dir, relative), Some(settings));
+        assert_eq!(
+            resolve(&dir, "photorealisticrenderer/win/64/missing.xml"),
+            None
+        );
+        assert_eq!(resolve(&dir, "nowhere/settings.xml"), None);
+
+        // An exact match wins, where the file system tells the two apart.
+        touch(&dir.join("data/Exact.txt"));
+        touch(&dir.join("data/EXACT.txt"));
+        if std::fs::read_dir(dir.join("data")).unwrap().count() == 2 {
+            let exact = dir.join("data/EXACT.txt");
+            assert_eq!(resolve(&dir, "data/EXACT.txt"), Some(exact));
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // The only test that sets STUDIO_HOME, since tests share the environment.
+    #[test]
+    fn finds_the_install_in_studio_home() {
+        let dir = temp_dir("home");
+        let root = dir.join("Studio 2.0 EarlyAccess");
+        let custom = root.join("DATA/customcolors/CustomColorSettings.xml");
+        touch(&custom);
+        touch(&root.join("DATA/studiocolordefinition.txt"));
+        touch(&root.join("DATA/customcolors/CustomColorDefinition.TXT"));
+        std::env::set_var("STUDIO_HOME", &root);
+
+        let install = find_install().unwrap();
+        assert_eq!(install.root, root);
+        assert_eq!(install.channel, Channel::EarlyAccess);
+        assert_eq!(install.custom_color_settings_xml(), Some(custom.clone()));
+        assert_eq!(install.settings_xml(), None);
+        assert!(install.require(StudioFile::Settings).is_err());
+
+        // In Studio's order, skipping the one that's missing.
+        let definitions = install.color_definitions();
+        assert_eq!(definitions.len(), 2);
+        assert!(definitions[0].ends_with("studiocolordefinition.txt"));
+        assert!(definitions[1].ends_with("CustomColorDefinition.TXT"));
+
+        assert_eq!(
+            locate(StudioFile::CustomColorSettings),
+            Some(custom.clone())
+        );
+        assert_eq!(
+            locate_preferring(Channel::Stable, StudioFile::CustomColorSettings),
+            Some(custom)
+        );
+
+        std::env::remove_var("STUDIO_HOME");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
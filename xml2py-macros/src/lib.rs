@@ -15,12 +15,27 @@ pub fn node(
 
     item.vis = parse_quote!(pub);
 
+    // Checked before the fields get their serde attributes.
+    let has_inputs = match &item.fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .any(|f| f.ident.as_ref().is_some_and(|i| i == "inputs")),
+        _ => false,
+    };
+
     if let Fields::Named(fields) = &mut item.fields {
         process_fields(fields);
     }
 
     let name = &item.ident;
 
+    let inputs_mut = if has_inputs {
+        quote!(Some(&mut self.inputs))
+    } else {
+        quote!(None)
+    };
+
     quote! {
         #item
 
@@ -33,6 +48,22 @@ pub fn node(
                 &mut self.name
             }
         }
+
+        impl crate::Spanned for #name {
+            fn span(&self) -> &crate::span::Span {
+                &self.span
+            }
+
+            fn span_mut(&mut self) -> &mut crate::span::Span {
+                &mut self.span
+            }
+        }
+
+        impl crate::nodes::InputsMut for #name {
+            fn inputs_mut(&mut self) -> Option<&mut Vec<crate::nodes::NodeInput>> {
+                #inputs_mut
+            }
+        }
     }
     .into()
 }
@@ -53,6 +84,11 @@ fn process_fields(fields: &mut FieldsNamed) {
         let rename = find_rename(field);
         field.attrs.push(parse_quote!(#[serde(rename = #rename)]));
     }
+
+    fields.named.push(parse_quote! {
+        #[serde(skip)]
+        pub span: crate::span::Span
+    });
 }
 
 fn find_rename(field: &mut Field) -> String {
@@ -63,7 +99,7 @@ fn find_rename(field: &mut Field) -> String {
             continue;
         };
 
-        if !mnv.path.get_ident().is_some_and(|ident| ident == "rename") {
+        if mnv.path.get_ident().is_none_or(|ident| ident != "rename") {
             continue;
         }
 
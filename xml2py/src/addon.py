@@ -0,0 +1,45 @@
+
+
+class MATERIAL_OT_add_studio_material(bpy.types.Operator):
+    """Give the selected objects one of Studio's materials, picked by color name"""
+
+    bl_idname = "material.add_studio_material"
+    bl_label = "Add Studio Material by Color"
+    bl_options = {'REGISTER', 'UNDO'}
+    bl_property = "color"
+
+    color: bpy.props.EnumProperty(  # type: ignore
+        name="Color",
+        items=[(name, name, "") for name in MATERIALS],
+    )
+
+    def invoke(self, context, event):
+        context.window_manager.invoke_search_popup(self)
+        return {'RUNNING_MODAL'}
+
+    def execute(self, context):
+        material = MATERIALS[self.color]()
+        for obj in context.selected_objects:
+            if not hasattr(obj.data, "materials"):
+                continue
+            if obj.material_slots:
+                obj.active_material = material
+            else:
+                obj.data.materials.append(material)
+        return {'FINISHED'}
+
+
+def menu_func(self, context):
+    self.layout.operator(MATERIAL_OT_add_studio_material.bl_idname, icon='MATERIAL')
+
+
+def register():
+    bpy.utils.register_class(MATERIAL_OT_add_studio_material)
+    bpy.types.VIEW3D_MT_object.append(menu_func)
+    bpy.types.MATERIAL_MT_context_menu.append(menu_func)
+
+
+def unregister():
+    bpy.types.MATERIAL_MT_context_menu.remove(menu_func)
+    bpy.types.VIEW3D_MT_object.remove(menu_func)
+    bpy.utils.unregister_class(MATERIAL_OT_add_studio_material)
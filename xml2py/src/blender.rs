@@ -0,0 +1,332 @@
+use std::str::FromStr;
+
+use eyesight_xml::nodes::{INode, Node};
+use phf::phf_map;
+
+/// The Blender releases we can generate code for.
+/// Each one stands for itself and every later release up to the next entry.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BlenderVersion {
+    /// Principled BSDF v1, group sockets through `tree.inputs`/`tree.outputs`.
+    V3_6,
+    /// Principled BSDF v2, group sockets through `tree.interface`.
+    V4_0,
+    /// Adds thin film to the Principled BSDF and more noise and Voronoi inputs.
+    #[default]
+    V4_2,
+}
+
+impl FromStr for BlenderVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = || {
+            let mut parts = s.split('.');
+            let major = parts.next()?.parse::<u32>().ok()?;
+            let minor = parts.next().unwrap_or("0").parse::<u32>().ok()?;
+            Some((major, minor))
+        };
+        match parse() {
+            Some((3, 6..)) => Ok(Self::V3_6),
+            Some((4, 0..=1)) => Ok(Self::V4_0),
+            Some((4.., _)) => Ok(Self::V4_2),
+            _ => Err(format!(
+                "unsupported Blender version {s:?} (expected 3.6 or later)"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for BlenderVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::V3_6 => "3.6",
+            Self::V4_0 => "4.0",
+            Self::V4_2 => "4.2",
+        })
+    }
+}
+
+impl BlenderVersion {
+    /// Whether node groups declare their sockets through `tree.interface`.
+    pub fn has_group_interface(self) -> bool {
+        self >= Self::V4_0
+    }
+
+    /// The sockets of a node type in this version, or `None` if the node type doesn't exist.
+    /// Node types whose sockets depend on the node (groups, group inputs and outputs)
+    /// are described with `NodeSockets::DYNAMIC`.
+    pub fn sockets(self, node_type: &str) -> Option<&'static NodeSockets> {
+        let overrides = match self {
+            Self::V3_6 => &SOCKETS_3_6,
+            Self::V4_0 => &SOCKETS_4_0,
+            Self::V4_2 => &SOCKETS_4_2,
+        };
+        overrides.get(node_type).or_else(|| match self {
+            Self::V3_6 => SOCKETS.get(node_type),
+            Self::V4_0 => SOCKETS.get(node_type),
+            Self::V4_2 => SOCKETS_4_0
+                .get(node_type)
+                .or_else(|| SOCKETS.get(node_type)),
+        })
+    }
+}
+
+impl BlenderVersion {
+    /// A node's socket, from its name after aliasing: the identifier to check it with,
+    /// and the key the generated code uses for it. Both are the name itself, except for
+    /// sockets with a copy per data type (see [`typed_socket`]), which are keyed by index.
+    pub fn resolve_socket(
+        self,
+        node: &Node,
+        node_type: &str,
+        socket: &str,
+        is_input: bool,
+    ) -> (String, String) {
+        let data_type = node
+            .attributes()
+            .into_iter()
+            .find(|(name, _)| *name == "data_type")
+            .map(|(_, value)| value);
+        let typed = data_type.and_then(|d| typed_socket(node_type, socket, d.trim_matches('\'')));
+        match typed {
+            Some(identifier) => {
+                let key = self
+                    .sockets(node_type)
+                    .and_then(|sockets| sockets.index(&identifier, is_input))
+                    .map_or_else(|| identifier.clone(), |i| i.to_string());
+                (identifier, key)
+            }
+            None => (socket.to_owned(), socket.to_owned()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct NodeSockets {
+    pub inputs: &'static [&'static str],
+    pub outputs: &'static [&'static str],
+    pub dynamic: bool,
+}
+
+impl NodeSockets {
+    pub const DYNAMIC: Self = Self {
+        inputs: &[],
+        outputs: &[],
+        dynamic: true,
+    };
+
+    /// Checks a socket key as it will appear in the generated code:
+    /// either a socket name or an index.
+    pub fn has(&self, socket: &str, is_input: bool) -> bool {
+        let sockets = if is_input { self.inputs } else { self.outputs };
+        self.dynamic
+            || match socket.parse::<usize>() {
+                Ok(i) => i < sockets.len(),
+                Err(_) => sockets.contains(&socket),
+            }
+    }
+
+    /// The position of a socket, by identifier.
+    pub fn index(&self, socket: &str, is_input: bool) -> Option<usize> {
+        let sockets = if is_input { self.inputs } else { self.outputs };
+        sockets.iter().position(|s| *s == socket)
+    }
+}
+
+/// The `data_type`s that [`typed_socket`] knows.
+pub const DATA_TYPES: [&str; 3] = ["FLOAT", "VECTOR", "RGBA"];
+
+/// The identifier of the socket that an aliased socket name stands for on a node of
+/// `node_type` whose `data_type` attribute is `data_type`, for node types that have a
+/// copy of some sockets per data type, all with the same name. `None` for other sockets.
+///
+/// Blender looks sockets up by name and finds the first copy, which is the float one,
+/// so the generated code addresses these by index instead.
+pub fn typed_socket(node_type: &str, socket: &str, data_type: &str) -> Option<String> {
+    if node_type != "ShaderNodeMix" {
+        return None;
+    }
+    let suffix = match data_type {
+        "FLOAT" => "Float",
+        "VECTOR" => "Vector",
+        "RGBA" => "Color",
+        _ => return None,
+    };
+    match socket {
+        // The vector factor is only used in non-uniform mode, which nothing sets.
+        "Factor" => Some("Factor_Float".into()),
+        "A" | "B" | "Result" => Some(format!("{socket}_{suffix}")),
+        _ => None,
+    }
+}
+
+macro_rules! sockets {
+    ([$($i:literal),*$(,)?] => [$($o:literal),*$(,)?]) => {
+        NodeSockets {
+            inputs: &[$($i),*],
+            outputs: &[$($o),*],
+            dynamic: false,
+        }
+    };
+}
+
+/// A socket that doesn't exist on the node it's used with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SocketMismatch {
+    pub version: BlenderVersion,
+    pub group: String,
+    pub node: String,
+    pub node_type: &'static str,
+    /// `None` when the node type itself doesn't exist in this version.
+    pub socket: Option<(String, bool)>,
+}
+
+impl std::fmt::Display for SocketMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            version,
+            group,
+            node,
+            node_type,
+            socket,
+        } = self;
+        match socket {
+            None => write!(
+                f,
+                "{group} / {node}: Blender {version} has no {node_type} node"
+            ),
+            Some((socket, is_input)) => write!(
+                f,
+                "{group} / {node}: {node_type} in Blender {version} has no {} socket {socket:?}",
+                if *is_input { "input" } else { "output" }
+            ),
+        }
+    }
+}
+
+/// Sockets shared by every supported version.
+static SOCKETS: phf::Map<&'static str, NodeSockets> = phf_map! {
+    "ShaderNodeGroup" => NodeSockets::DYNAMIC,
+    "NodeGroupInput" => NodeSockets::DYNAMIC,
+    "NodeGroupOutput" => NodeSockets::DYNAMIC,
+    "NodeReroute" => sockets!(["Input"] => ["Output"]),
+    "ShaderNodeOutputMaterial" => sockets!(["Surface", "Volume", "Displacement"] => []),
+    "ShaderNodeBump" => sockets!(["Strength", "Distance", "Height", "Normal"] => ["Normal"]),
+    "ShaderNodeBevel" => sockets!(["Radius", "Normal"] => ["Normal"]),
+    "ShaderNodeMixShader" => sockets!(["Fac", "Shader", "Shader"] => ["Shader"]),
+    "ShaderNodeAddShader" => sockets!(["Shader", "Shader"] => ["Shader"]),
+    "ShaderNodeMath" => sockets!(["Value", "Value", "Value"] => ["Value"]),
+    "ShaderNodeVectorMath" => sockets!(["Vector", "Vector", "Vector", "Scale"] => ["Vector", "Value"]),
+    "ShaderNodeMapping" => sockets!(["Vector", "Location", "Rotation", "Scale"] => ["Vector"]),
+    "ShaderNodeValToRGB" => sockets!(["Fac"] => ["Color", "Alpha"]),
+    "ShaderNodeRGBCurve" => sockets!(["Fac", "Color"] => ["Color"]),
+    "ShaderNodeValue" => sockets!([] => ["Value"]),
+    "ShaderNodeRGB" => sockets!([] => ["Color"]),
+    "ShaderNodeCombineXYZ" => sockets!(["X", "Y", "Z"] => ["Vector"]),
+    "ShaderNodeSeparateXYZ" => sockets!(["Vector"] => ["X", "Y", "Z"]),
+    "ShaderNodeMapRange" => sockets!(
+        [
+            "Value", "From Min", "From Max", "To Min", "To Max", "Steps", "Vector",
+            "From_Min_FLOAT3", "From_Max_FLOAT3", "To_Min_FLOAT3", "To_Max_FLOAT3",
+            "Steps_FLOAT3",
+        ] => ["Result", "Vector"]
+    ),
+    // Identifiers; each data type has its own copy of the sockets, all with the same name.
+    "ShaderNodeMix" => sockets!(
+        [
+            "Factor_Float", "Factor_Vector", "A_Float", "B_Float", "A_Vector", "B_Vector",
+            "A_Color", "B_Color",
+        ] => ["Result_Float", "Result_Vector", "Result_Color"]
+    ),
+    "ShaderNodeBrightContrast" => sockets!(["Color", "Bright", "Contrast"] => ["Color"]),
+    "ShaderNodeNormalMap" => sockets!(["Strength", "Color"] => ["Normal"]),
+    "ShaderNodeVectorTransform" => sockets!(["Vector"] => ["Vector"]),
+    "ShaderNodeUVMap" => sockets!([] => ["UV"]),
+    "ShaderNodeTexImage" => sockets!(["Vector"] => ["Color", "Alpha"]),
+    "ShaderNodeTexCoord" => sockets!(
+        [] => ["Generated", "Normal", "UV", "Object", "Camera", "Window", "Reflection"]
+    ),
+    "ShaderNodeObjectInfo" => sockets!(
+        [] => ["Location", "Color", "Alpha", "Object Index", "Material Index", "Random"]
+    ),
+    "ShaderNodeNewGeometry" => sockets!(
+        [] => [
+            "Position", "Normal", "Tangent", "True Normal", "Incoming", "Parametric",
+            "Backfacing", "Pointiness", "Random Per Island",
+        ]
+    ),
+    "ShaderNodeLayerWeight" => sockets!(["Blend", "Normal"] => ["Fresnel", "Facing"]),
+    "ShaderNodeBsdfDiffuse" => sockets!(["Color", "Roughness", "Normal"] => ["BSDF"]),
+    "ShaderNodeBsdfTranslucent" => sockets!(["Color", "Normal"] => ["BSDF"]),
+    "ShaderNodeBsdfTransparent" => sockets!(["Color"] => ["BSDF"]),
+    "ShaderNodeVolumeAbsorption" => sockets!(["Color", "Density"] => ["Volume"]),
+    "ShaderNodeEmission" => sockets!(["Color", "Strength"] => ["Emission"]),
+};
+
+static SOCKETS_3_6: phf::Map<&'static str, NodeSockets> = phf_map! {
+    "ShaderNodeBsdfGlossy" => sockets!(["Color", "Roughness", "Normal"] => ["BSDF"]),
+    "ShaderNodeTexNoise" => sockets!(
+        ["Vector", "W", "Scale", "Detail", "Roughness", "Distortion"] => ["Fac", "Color"]
+    ),
+    "ShaderNodeTexVoronoi" => sockets!(
+        ["Vector", "W", "Scale", "Smoothness", "Exponent", "Randomness"]
+            => ["Distance", "Color", "Position", "W", "Radius"]
+    ),
+    "ShaderNodeBsdfPrincipled" => sockets!(
+        [
+            "Base Color", "Subsurface", "Subsurface Radius", "Subsurface Color",
+            "Subsurface IOR", "Subsurface Anisotropy", "Metallic", "Specular", "Specular Tint",
+            "Roughness", "Anisotropic", "Anisotropic Rotation", "Sheen", "Sheen Tint",
+            "Clearcoat", "Clearcoat Roughness", "IOR", "Transmission", "Transmission Roughness",
+            "Emission", "Emission Strength", "Alpha", "Normal", "Clearcoat Normal", "Tangent",
+        ] => ["BSDF"]
+    ),
+};
+
+static SOCKETS_4_0: phf::Map<&'static str, NodeSockets> = phf_map! {
+    // The Glossy BSDF absorbed the Anisotropic BSDF and kept its type name.
+    "ShaderNodeBsdfAnisotropic" => sockets!(
+        ["Color", "Roughness", "Anisotropy", "Rotation", "Normal", "Tangent"] => ["BSDF"]
+    ),
+    "ShaderNodeTexNoise" => sockets!(
+        ["Vector", "W", "Scale", "Detail", "Roughness", "Lacunarity", "Distortion"]
+            => ["Fac", "Color"]
+    ),
+    "ShaderNodeTexVoronoi" => sockets!(
+        [
+            "Vector", "W", "Scale", "Detail", "Roughness", "Lacunarity", "Smoothness",
+            "Exponent", "Randomness",
+        ] => ["Distance", "Color", "Position", "W", "Radius"]
+    ),
+    "ShaderNodeBsdfPrincipled" => sockets!(
+        [
+            "Base Color", "Metallic", "Roughness", "IOR", "Alpha", "Normal", "Weight",
+            "Subsurface Weight", "Subsurface Radius", "Subsurface Scale", "Subsurface IOR",
+            "Subsurface Anisotropy", "Specular IOR Level", "Specular Tint", "Anisotropic",
+            "Anisotropic Rotation", "Tangent", "Transmission Weight", "Coat Weight",
+            "Coat Roughness", "Coat IOR", "Coat Tint", "Coat Normal", "Sheen Weight",
+            "Sheen Roughness", "Sheen Tint", "Emission Color", "Emission Strength",
+        ] => ["BSDF"]
+    ),
+};
+
+static SOCKETS_4_2: phf::Map<&'static str, NodeSockets> = phf_map! {
+    "ShaderNodeTexNoise" => sockets!(
+        [
+            "Vector", "W", "Scale", "Detail", "Roughness", "Lacunarity", "Offset", "Gain",
+            "Distortion",
+        ] => ["Fac", "Color"]
+    ),
+    "ShaderNodeBsdfPrincipled" => sockets!(
+        [
+            "Base Color", "Metallic", "Roughness", "IOR", "Alpha", "Normal", "Weight",
+            "Subsurface Weight", "Subsurface Radius", "Subsurface Scale", "Subsurface IOR",
+            "Subsurface Anisotropy", "Specular IOR Level", "Specular Tint", "Anisotropic",
+            "Anisotropic Rotation", "Tangent", "Transmission Weight", "Coat Weight",
+            "Coat Roughness", "Coat IOR", "Coat Tint", "Coat Normal", "Sheen Weight",
+            "Sheen Roughness", "Sheen Tint", "Emission Color", "Emission Strength",
+            "Thin Film Thickness", "Thin Film IOR",
+        ] => ["BSDF"]
+    ),
+};
@@ -0,0 +1,5 @@
+# mypy: disable-error-code="attr-defined"
+# Generated by xml2py. Can be run directly from Blender's text editor.
+
+import bpy
+import os.path
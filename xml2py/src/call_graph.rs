@@ -0,0 +1,358 @@
+//! Which groups use which, across all of an `Eyesight`'s materials and groups.
+//!
+//! Custom groups count as defined, and nodes that one stands in for count as using it.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use eyesight_xml::nodes::Node;
+use eyesight_xml::schema::{Eyesight, Shader};
+
+use crate::custom_groups;
+
+pub struct CallGraph<'a> {
+    /// The groups each defined group uses.
+    groups: BTreeMap<&'a str, BTreeSet<&'a str>>,
+    /// The groups each material uses.
+    materials: BTreeMap<&'a str, BTreeSet<&'a str>>,
+}
+
+/// The groups and materials that use a group directly.
+#[derive(Debug, Default)]
+pub struct Users<'a> {
+    pub groups: BTreeSet<&'a str>,
+    pub materials: BTreeSet<&'a str>,
+}
+
+impl<'a> CallGraph<'a> {
+    pub fn new(eyesight: &'a Eyesight) -> Self {
+        let calls = |shader: &'a Shader| {
+            shader
+                .nodes
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Group(g) => Some(&*g.group_name),
+                    _ => custom_groups::for_node(node).map(|g| g.name),
+                })
+                .collect::<BTreeSet<_>>()
+        };
+        Self {
+            groups: eyesight
+                .groups
+                .iter()
+                .map(|g| (&*g.name, calls(&g.shader)))
+                .collect(),
+            materials: eyesight
+                .materials
+                .iter()
+                .map(|m| (&*m.name, calls(&m.shader)))
+                .collect(),
+        }
+    }
+
+    pub fn is_group(&self, name: &str) -> bool {
+        self.groups.contains_key(name)
+    }
+
+    pub fn is_material(&self, name: &str) -> bool {
+        self.materials.contains_key(name)
+    }
+
+    /// Whether `name` is a group defined in the XML or a custom group.
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.is_group(name) || custom_groups::get(name).is_some()
+    }
+
+    /// The groups a group or material uses directly, whether they're defined or not.
+    pub fn callees(&self, name: &str) -> impl Iterator<Item = &'a str> + '_ {
+        self.groups
+            .get(name)
+            .or_else(|| self.materials.get(name))
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// The defined groups that the named groups and materials use, directly or not.
+    /// Root groups are included. Undefined groups are left out; see `undefined`.
+    /// Custom groups are included too, but don't use anything.
+    pub fn reachable<'r>(&self, roots: impl IntoIterator<Item = &'r str>) -> BTreeSet<&'a str> {
+        let mut visited = BTreeSet::new();
+        let mut unvisited = vec![];
+
+        for root in roots {
+            if let Some((&name, _)) = self.groups.get_key_value(root) {
+                unvisited.push(name);
+            } else if let Some(custom) = custom_groups::get(root) {
+                unvisited.push(custom.name);
+            } else {
+                unvisited.extend(self.callees(root));
+            }
+        }
+
+        while let Some(name) = unvisited.pop() {
+            if !self.is_defined(name) || !visited.insert(name) {
+                continue;
+            }
+            unvisited.extend(self.callees(name));
+        }
+
+        visited
+    }
+
+    /// The groups and materials that use `group` directly.
+    pub fn users(&self, group: &str) -> Users<'a> {
+        let using = |calls: &BTreeMap<&'a str, BTreeSet<&'a str>>| {
+            calls
+                .iter()
+                .filter(|(_, callees)| callees.contains(group))
+                .map(|(&name, _)| name)
+                .collect()
+        };
+        Users {
+            groups: using(&self.groups),
+            materials: using(&self.materials),
+        }
+    }
+
+    /// The materials that use `group`, directly or not.
+    pub fn materials_using(&self, group: &str) -> BTreeSet<&'a str> {
+        self.materials
+            .keys()
+            .filter(|&&material| {
+                let callees = &self.materials[material];
+                callees.contains(group) || self.reachable([material]).contains(group)
+            })
+            .copied()
+            .collect()
+    }
+
+    /// Each set of groups that use each other, directly or not, in name order.
+    pub fn cycles(&self) -> Vec<Vec<&'a str>> {
+        // Tarjan's strongly connected components.
+        struct State<'a> {
+            index: BTreeMap<&'a str, usize>,
+            low_link: BTreeMap<&'a str, usize>,
+            stack: Vec<&'a str>,
+            cycles: Vec<Vec<&'a str>>,
+        }
+
+        fn visit<'a>(graph: &CallGraph<'a>, name: &'a str, state: &mut State<'a>) {
+            let index = state.index.len();
+            state.index.insert(name, index);
+            state.low_link.insert(name, index);
+            state.stack.push(name);
+
+            for callee in graph.callees(name).filter(|c| graph.is_group(c)) {
+                if !state.index.contains_key(callee) {
+                    visit(graph, callee, state);
+                    let low = state.low_link[name].min(state.low_link[callee]);
+                    state.low_link.insert(name, low);
+                } else if state.stack.contains(&callee) {
+                    let low = state.low_link[name].min(state.index[callee]);
+                    state.low_link.insert(name, low);
+                }
+            }
+
+            if state.low_link[name] == state.index[name] {
+                let start = state.stack.iter().rposition(|&n| n == name).unwrap();
+                let mut component = state.stack.split_off(start);
+                let calls_itself = graph.callees(name).any(|c| c == name);
+                if component.len() > 1 || calls_itself {
+                    component.sort();
+                    state.cycles.push(component);
+                }
+            }
+        }
+
+        let mut state = State {
+            index: BTreeMap::new(),
+            low_link: BTreeMap::new(),
+            stack: vec![],
+            cycles: vec![],
+        };
+        for &name in self.groups.keys() {
+            if !state.index.contains_key(name) {
+                visit(self, name, &mut state);
+            }
+        }
+
+        state.cycles.sort();
+        state.cycles
+    }
+
+    /// Every defined group, each after the groups it uses, and otherwise in name order.
+    /// Groups in a cycle can't be ordered that way, so they come last.
+    pub fn definition_order(&self) -> Vec<&'a str> {
+        let mut order = vec![];
+        let mut placed = BTreeSet::<&str>::new();
+
+        loop {
+            let ready = self
+                .groups
+                .iter()
+                .filter(|(name, _)| !placed.contains(*name))
+                .filter(|(_, callees)| {
+                    callees
+                        .iter()
+                        .all(|c| placed.contains(c) || !self.is_group(c))
+                })
+                .map(|(&name, _)| name)
+                .collect::<Vec<_>>();
+
+            if ready.is_empty() {
+                break;
+            }
+
+            placed.extend(&ready);
+            order.extend(ready);
+        }
+
+        order.extend(self.groups.keys().filter(|name| !placed.contains(*name)));
+        order
+    }
+
+    /// Defined groups that no material uses, directly or not.
+    pub fn unused(&self) -> Vec<&'a str> {
+        let used = self.reachable(self.materials.keys().copied());
+        self.groups
+            .keys()
+            .filter(|name| !used.contains(*name))
+            .copied()
+            .collect()
+    }
+
+    /// Groups that are used but not defined, with what uses them.
+    pub fn undefined(&self) -> BTreeMap<&'a str, Users<'a>> {
+        self.groups
+            .values()
+            .chain(self.materials.values())
+            .flatten()
+            .filter(|name| !self.is_defined(name))
+            .map(|&name| (name, self.users(name)))
+            .collect()
+    }
+
+    /// A summary of the whole graph, for `xml2py deps`.
+    pub fn report(&self) -> String {
+        let undefined = self
+            .undefined()
+            .into_iter()
+            .map(|(name, users)| {
+                let users = users.groups.into_iter().chain(users.materials);
+                format!("{name} (used by {})", users.collect::<Vec<_>>().join(", "))
+            })
+            .collect();
+
+        let sections: [(&str, Vec<String>); 4] = [
+            (
+                "definition order",
+                self.definition_order()
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            ),
+            (
+                "cycles",
+                self.cycles().iter().map(|c| c.join(", ")).collect(),
+            ),
+            ("undefined groups", undefined),
+            (
+                "unused groups",
+                self.unused().into_iter().map(String::from).collect(),
+            ),
+        ];
+
+        let mut s = String::new();
+        for (heading, lines) in sections {
+            if !s.is_empty() {
+                s += "\n";
+            }
+            writeln!(s, "{heading}:").unwrap();
+            if lines.is_empty() {
+                s += "  (none)\n";
+            }
+            for line in lines {
+                writeln!(s, "  {line}").unwrap();
+            }
+        }
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A shader with a group node for each of `calls`, plus any `extra` nodes.
+    fn shader(calls: &[&str], extra: &str) -> String {
+        let nodes = calls
+            .iter()
+            .enumerate()
+            .map(|(i, name)| format!(r#"<group name="call{i}" group_name="{name}"/>"#))
+            .collect::<String>();
+        format!(
+            r#"<shader><group_output name="out"/>{nodes}{extra}<connect from_node="out" from_socket="A" to_node="out" to_socket="B"/></shader>"#
+        )
+    }
+
+    fn eyesight() -> Eyesight {
+        let group = |name: &str, calls: &[&str], extra: &str| {
+            format!(r#"<group name="{name}">{}</group>"#, shader(calls, extra))
+        };
+        let xml = format!(
+            r#"<eyesight>
+                <material name="M" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance">{}</material>
+                {}{}{}{}{}{}
+            </eyesight>"#,
+            shader(&["A", "Is Slope", "Missing"], ""),
+            group("A", &["B"], ""),
+            group("B", &["C"], ""),
+            group("C", &["B"], ""),
+            group("D", &["D"], ""),
+            group("E", &[], ""),
+            group("F", &["Missing"], r#"<uv_degradation name="uv"/>"#),
+        );
+        quick_xml::de::from_str(&xml).unwrap()
+    }
+
+    #[test]
+    fn reachable_follows_defined_groups() {
+        let eyesight = eyesight();
+        let graph = CallGraph::new(&eyesight);
+        assert_eq!(
+            graph.reachable(["M"]),
+            BTreeSet::from(["A", "B", "C", "Is Slope"])
+        );
+        assert_eq!(
+            graph.reachable(["F"]),
+            BTreeSet::from(["F", "UV Degradation"])
+        );
+        assert_eq!(graph.materials_using("C"), BTreeSet::from(["M"]));
+        assert!(graph.materials_using("F").is_empty());
+    }
+
+    #[test]
+    fn cycles_and_order() {
+        let eyesight = eyesight();
+        let graph = CallGraph::new(&eyesight);
+        assert_eq!(graph.cycles(), [vec!["B", "C"], vec!["D"]]);
+        assert_eq!(graph.definition_order(), ["E", "F", "A", "B", "C", "D"]);
+    }
+
+    #[test]
+    fn unused_and_undefined() {
+        let eyesight = eyesight();
+        let graph = CallGraph::new(&eyesight);
+        assert_eq!(graph.unused(), ["D", "E", "F"]);
+
+        let undefined = graph.undefined();
+        assert_eq!(undefined.keys().copied().collect::<Vec<_>>(), ["Missing"]);
+        assert_eq!(undefined["Missing"].groups, BTreeSet::from(["F"]));
+        assert_eq!(undefined["Missing"].materials, BTreeSet::from(["M"]));
+
+        assert!(graph
+            .report()
+            .contains("undefined groups:\n  Missing (used by F, M)\n"));
+    }
+}
@@ -0,0 +1,274 @@
+//! Command-line parsing.
+//!
+//! Options can go anywhere after the subcommand. Whatever isn't an option
+//! is left for the subcommand to interpret.
+
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::blender::BlenderVersion;
+use crate::codegen::Backend;
+use crate::config::{self, Config, Layout, OnConflict, OnSocketConflict, Passes};
+
+pub const USAGE: &str = "\
+usage: xml2py [command] [options]
+
+commands:
+  convert                 generate Python for the root groups (the default)
+  list-groups             print the name of every group
+  list-materials          print the name of every material
+  families                sort the materials into families that differ only in color
+  show <name>             print a group's or material's interface, nodes and links
+  deps [name]             print the groups a group or material uses and what uses it,
+                          or without a name, report on every group
+  graph <name> [--dot | --mermaid] [--aliases] [--expand]
+                          draw a group or material
+  package                 build a Blender add-on zip (studio_materials.zip by default)
+  list-passes             print every transform pass, whether it's enabled, and what it
+                          needs and guarantees
+
+options:
+  -c, --config <path>     the project configuration (default: xml2py.toml, if there is one);
+                          the options below override it
+  -i, --input <path>      an Eyesight XML file; repeatable, merged in order
+                          (default: settings.xml and CustomColorSettings.xml from the
+                          Studio install, which STUDIO_HOME can point at)
+  --on-conflict <policy>  what to do when inputs define a group or material differently:
+                          error (the default), first, last or rename
+  --on-socket-conflict <policy>
+                          what to do when group nodes use a group's socket as different
+                          types: error (the default) or most-used
+  -r, --root <name>       a group or material to convert; repeatable
+                          (default: Solid, Trans Group Base)
+  -o, --output <path>     where to write the result (default: standard output)
+  --bpy                   generate plain bpy calls instead of node_dsl calls
+  --blender-version <v>   the Blender release to target (default: 4.2)
+  --mapping <path>        a mapping file to use instead of the built-in one
+  --enable <pass>         run a transform pass (see list-passes); repeatable
+  --disable <pass>        skip a transform pass; repeatable
+  --dump-passes <dir>     write a debug dump of the XML after each pass to <dir>
+  --pass-report           print how much each pass changed and how long it took";
+
+const DEFAULT_ROOTS: [&str; 2] = ["Solid", "Trans Group Base"];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Command {
+    Convert,
+    ListGroups,
+    ListMaterials,
+    Families,
+    Show,
+    Deps,
+    Graph,
+    Package,
+    ListPasses,
+}
+
+impl FromStr for Command {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "convert" => Self::Convert,
+            "list-groups" => Self::ListGroups,
+            "list-materials" => Self::ListMaterials,
+            "families" => Self::Families,
+            "show" => Self::Show,
+            "deps" => Self::Deps,
+            "graph" => Self::Graph,
+            "package" => Self::Package,
+            "list-passes" => Self::ListPasses,
+            _ => return Err(format!("unknown command {s:?}")),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub inputs: Vec<PathBuf>,
+    pub on_conflict: OnConflict,
+    pub on_socket_conflict: OnSocketConflict,
+    pub roots: Vec<String>,
+    pub output: Option<PathBuf>,
+    pub layout: Layout,
+    pub backend: Backend,
+    pub blender_version: BlenderVersion,
+    pub mapping: Option<PathBuf>,
+    /// The passes' parameters, and whether the configuration enables them.
+    pub passes: Passes,
+    /// Passes to run or skip regardless, by name.
+    pub enable: Vec<String>,
+    pub disable: Vec<String>,
+    /// A directory to write the XML to after each pass.
+    pub dump_passes: Option<PathBuf>,
+    /// Whether to print what each pass did.
+    pub pass_report: bool,
+}
+
+/// Splits the arguments (without the program name) into a command, the options,
+/// and the arguments left over for the command.
+pub fn parse(mut args: Vec<String>) -> Result<(Command, Options, Vec<String>), String> {
+    let command = match args.first() {
+        Some(arg) if !arg.starts_with('-') => args.remove(0).parse()?,
+        _ => Command::Convert,
+    };
+
+    let config = match take_option(&mut args, &["-c", "--config"])? {
+        Some(path) => load_config(path.as_ref())?,
+        None if Path::new(config::DEFAULT_PATH).exists() => {
+            load_config(config::DEFAULT_PATH.as_ref())?
+        }
+        None => Config::default(),
+    };
+
+    let mut inputs = take_options(&mut args, &["-i", "--input"])?
+        .into_iter()
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+    if inputs.is_empty() {
+        inputs = config.inputs;
+    }
+    if inputs.is_empty() && command != Command::ListPasses {
+        inputs = default_inputs()?;
+    }
+
+    let on_conflict = match take_option(&mut args, &["--on-conflict"])? {
+        Some(policy) => parse_on_conflict(&policy)?,
+        None => config.on_conflict.unwrap_or_default(),
+    };
+
+    let on_socket_conflict = match take_option(&mut args, &["--on-socket-conflict"])? {
+        Some(policy) => parse_on_socket_conflict(&policy)?,
+        None => config.on_socket_conflict.unwrap_or_default(),
+    };
+
+    let mut roots = take_options(&mut args, &["-r", "--root"])?;
+    if roots.is_empty() {
+        roots = config.roots;
+    }
+    if roots.is_empty() {
+        roots = DEFAULT_ROOTS.map(String::from).to_vec();
+    }
+
+    let output = take_option(&mut args, &["-o", "--output"])?
+        .map(PathBuf::from)
+        .or(config.output.path);
+
+    let backend = if take_flag(&mut args, "--bpy") {
+        Backend::Bpy
+    } else {
+        config.backend.unwrap_or_default()
+    };
+
+    let blender_version = match take_option(&mut args, &["--blender-version"])? {
+        Some(version) => version.parse()?,
+        None => config.blender_version.unwrap_or_default(),
+    };
+
+    let mapping = take_option(&mut args, &["--mapping"])?
+        .map(PathBuf::from)
+        .or(config.mapping);
+
+    let enable = take_options(&mut args, &["--enable"])?;
+    let disable = take_options(&mut args, &["--disable"])?;
+    let dump_passes = take_option(&mut args, &["--dump-passes"])?.map(PathBuf::from);
+    let pass_report = take_flag(&mut args, "--pass-report");
+
+    let options = Options {
+        inputs,
+        on_conflict,
+        on_socket_conflict,
+        roots,
+        output,
+        layout: config.output.layout.unwrap_or_default(),
+        backend,
+        blender_version,
+        mapping,
+        passes: config.passes,
+        enable,
+        disable,
+        dump_passes,
+        pass_report,
+    };
+
+    Ok((command, options, args))
+}
+
+fn load_config(path: &Path) -> Result<Config, String> {
+    Config::load(path).map_err(|e| format!("{}: {e}", path.display()))
+}
+
+/// `settings.xml` and `CustomColorSettings.xml` from the first Studio install that has them.
+fn default_inputs() -> Result<Vec<PathBuf>, String> {
+    let install = studio_paths::find_installs()
+        .into_iter()
+        .find(|install| install.settings_xml().is_some())
+        .ok_or("couldn't find a Studio installation; pass --input or set STUDIO_HOME")?;
+    Ok(install
+        .settings_xml()
+        .into_iter()
+        .chain(install.custom_color_settings_xml())
+        .collect())
+}
+
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let len = args.len();
+    args.retain(|a| a != name);
+    args.len() != len
+}
+
+/// Removes every `<name> <value>` (or `<name>=<value>`) from the arguments
+/// and returns the values in order.
+fn take_options(args: &mut Vec<String>, names: &[&str]) -> Result<Vec<String>, String> {
+    let mut values = vec![];
+    let mut rest = vec![];
+    let mut args_iter = std::mem::take(args).into_iter();
+    while let Some(arg) = args_iter.next() {
+        if names.contains(&&*arg) {
+            values.push(
+                args_iter
+                    .next()
+                    .ok_or_else(|| format!("{arg} needs a value"))?,
+            );
+        } else if let Some(value) = names
+            .iter()
+            .find_map(|name| arg.strip_prefix(name)?.strip_prefix('='))
+        {
+            values.push(value.to_owned());
+        } else {
+            rest.push(arg);
+        }
+    }
+    *args = rest;
+    Ok(values)
+}
+
+/// Like `take_options`, for options that may only be given once.
+fn take_option(args: &mut Vec<String>, names: &[&str]) -> Result<Option<String>, String> {
+    let mut values = take_options(args, names)?;
+    match values.len() {
+        0 | 1 => Ok(values.pop()),
+        _ => Err(format!(
+            "{} was given more than once",
+            names[names.len() - 1]
+        )),
+    }
+}
+
+fn parse_on_conflict(policy: &str) -> Result<OnConflict, String> {
+    Ok(match policy {
+        "error" => OnConflict::Error,
+        "first" => OnConflict::First,
+        "last" => OnConflict::Last,
+        "rename" => OnConflict::Rename,
+        _ => return Err(format!("unknown conflict policy {policy:?}")),
+    })
+}
+
+fn parse_on_socket_conflict(policy: &str) -> Result<OnSocketConflict, String> {
+    Ok(match policy {
+        "error" => OnSocketConflict::Error,
+        "most-used" => OnSocketConflict::MostUsed,
+        _ => return Err(format!("unknown socket conflict policy {policy:?}")),
+    })
+}
@@ -1,172 +1,797 @@
-use std::collections::{BTreeMap, BTreeSet, HashSet};
-
-use heck::{AsSnakeCase, ToSnakeCase};
-
-use crate::groups::Interface;
-use crate::lookups::{INPUT_ALIASES, OUTPUT_ALIASES};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::blender::{BlenderVersion, SocketMismatch};
+use crate::call_graph::CallGraph;
+use crate::custom_groups;
+use crate::groups::{InputDetails, Interface};
+use crate::identifiers::FunctionNames;
+use crate::layout::{self, LayoutEdge, LayoutOptions, Slot};
+use crate::mapping;
+use crate::python::{Arg, Expr, FunctionDef, Module, Stmt};
 use eyesight_xml::nodes::{python_enum, INode, Node};
-use eyesight_xml::schema::{Eyesight, Group, Link};
+use eyesight_xml::schema::{Eyesight, Group, Link, Material, Shader};
 use eyesight_xml::Named;
+use serde_derive::Deserialize;
+
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    /// Calls into the external `node_dsl` and `custom_nodes` modules.
+    #[default]
+    NodeDsl,
+    /// Plain `bpy` API calls, with the custom groups embedded.
+    /// The result can be run directly from Blender's text editor.
+    Bpy,
+}
+
+pub fn the_big_kahuna(
+    eyesight: &Eyesight,
+    groups_to_convert: &BTreeSet<&str>,
+    backend: Backend,
+    version: BlenderVersion,
+) -> Result<String, Vec<SocketMismatch>> {
+    let mut prelude = match backend {
+        Backend::NodeDsl => include_str!("header.py").to_owned(),
+        Backend::Bpy => include_str!("bpy_header.py").to_owned(),
+    };
+    if backend == Backend::NodeDsl {
+        for line in custom_groups::import_lines() {
+            prelude += &line;
+            prelude += "\n";
+        }
+    }
+    prelude += include_str!("helpers.py");
+    if backend == Backend::Bpy {
+        prelude += include_str!("custom_nodes.py");
+    }
+
+    let mut module = Module::default();
+    module
+        .body
+        .push(Stmt::Raw(prelude.lines().map(String::from).collect()));
+
+    let functions = group_functions(eyesight, groups_to_convert, backend, version)?;
+    let function_names = functions.iter().map(|f| f.name.clone()).collect::<Vec<_>>();
+    module.body.extend(functions.into_iter().map(Stmt::Def));
+
+    if backend == Backend::Bpy {
+        let is_main = Expr::raw("__name__ == \"__main__\"");
+        let calls = function_names
+            .into_iter()
+            .map(|f| Stmt::Expr(Expr::name(f).call(vec![])))
+            .collect();
+        module.body.push(Stmt::If(is_main, calls));
+    }
+
+    Ok(module.print())
+}
 
-pub fn the_big_kahuna(eyesight: &Eyesight, groups_to_convert: &HashSet<&str>) -> String {
-    let interfaces = crate::groups::check_interfaces(eyesight);
+/// The node_dsl output as a package with a module per group, named after the group's function,
+/// plus `helpers.py`. Each module imports the groups it uses from the others.
+pub fn split_modules(
+    eyesight: &Eyesight,
+    groups_to_convert: &BTreeSet<&str>,
+    version: BlenderVersion,
+) -> Result<Vec<(String, String)>, Vec<SocketMismatch>> {
+    let functions = group_functions(eyesight, groups_to_convert, Backend::NodeDsl, version)?;
+    let function_names = FunctionNames::new(eyesight);
+
+    let mut files = vec![(
+        "helpers.py".to_owned(),
+        format!("import bpy\nimport os.path\n{}", include_str!("helpers.py")),
+    )];
+
+    for function in functions {
+        let group = eyesight
+            .groups
+            .iter()
+            .find(|g| function_names.group(&g.name) == function.name)
+            .unwrap();
+        let used = group
+            .shader
+            .nodes
+            .iter()
+            .filter(|node| custom_groups::for_node(node).is_none())
+            .filter_map(|node| match node {
+                Node::Group(g) if groups_to_convert.contains(&*g.group_name) => {
+                    Some(function_names.group(&g.group_name))
+                }
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>();
 
-    let mut file = include_str!("header.py").to_owned();
+        let mut header = include_str!("header.py")
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>();
+        header.extend(custom_groups::import_lines());
+        header.push("from .helpers import load_image".into());
+        header.extend(used.iter().map(|f| format!("from .{f} import {f}")));
+
+        let mut module = Module::default();
+        module.body.push(Stmt::Raw(header));
+        let file_name = format!("{}.py", function.name);
+        module.body.push(Stmt::Def(function));
+        files.push((file_name, module.print()));
+    }
 
-    for group in &eyesight.groups {
-        if !groups_to_convert.contains(&*group.name) {
+    Ok(files)
+}
+
+/// A `<name>_node_group` function for each of the groups, each after the groups it uses.
+pub fn group_functions(
+    eyesight: &Eyesight,
+    groups_to_convert: &BTreeSet<&str>,
+    backend: Backend,
+    version: BlenderVersion,
+) -> Result<Vec<FunctionDef>, Vec<SocketMismatch>> {
+    let interfaces = crate::groups::check_interfaces(eyesight).interfaces;
+    let function_names = FunctionNames::new(eyesight);
+
+    let mut functions = vec![];
+    let mut errors = vec![];
+
+    for name in CallGraph::new(eyesight).definition_order() {
+        if !groups_to_convert.contains(name) {
             continue;
         }
+        let group = eyesight.groups.iter().find(|g| g.name == name).unwrap();
 
         let Some(interface) = interfaces.get(&group.name) else {
             continue;
         };
 
-        let function_body = group_to_python(group, interface);
+        let (params, body) = match backend {
+            Backend::NodeDsl => (
+                vec!["graph: ShaderGraph".into()],
+                group_to_python(group, interface, version, &function_names, &mut errors),
+            ),
+            Backend::Bpy => (
+                vec![],
+                group_to_bpy(group, interface, version, &function_names, &mut errors),
+            ),
+        };
 
-        file += &format!(
-            "def {}_node_group(graph: ShaderGraph):\n",
-            AsSnakeCase(&group.name)
-        );
+        functions.push(FunctionDef {
+            name: function_names.group(&group.name),
+            params,
+            body,
+        });
+    }
 
-        for line in function_body {
-            file += "    ";
-            file += &line;
-            file += "\n";
-        }
-        file += "\n\n";
+    if !errors.is_empty() {
+        return Err(errors);
     }
 
-    file
+    Ok(functions)
 }
 
-fn get_socket_key(s: &str) -> String {
-    if let Ok(n) = s.parse::<u32>() {
-        n.to_string()
-    } else {
-        format!("{s:?}") // escapes and quotes
+/// A `<name>_material` function for each of the materials, using plain `bpy` calls.
+/// Each one returns the existing material if there is one, or builds it.
+pub fn material_functions(
+    materials: &[&Material],
+    function_names: &FunctionNames,
+    version: BlenderVersion,
+) -> Result<Vec<FunctionDef>, Vec<SocketMismatch>> {
+    let mut errors = vec![];
+    let functions = materials
+        .iter()
+        .map(|material| FunctionDef {
+            name: function_names.material(&material.name),
+            params: vec![],
+            body: material_to_bpy(material, version, function_names, &mut errors),
+        })
+        .collect();
+
+    if !errors.is_empty() {
+        return Err(errors);
     }
-}
 
-fn group_to_python(group: &Group, interface: &Interface) -> Vec<String> {
-    let mut lines = vec![];
+    Ok(functions)
+}
 
-    for (name, data_type) in &interface.inputs {
-        lines.push(format!(
-            "graph.input(bpy.types.{}, \"{name}\")",
-            data_type.python_type()
-        ));
-    }
-    for (name, data_type) in &interface.outputs {
-        lines.push(format!(
-            "graph.output(bpy.types.{}, \"{name}\")",
-            data_type.python_type()
-        ));
+fn socket_key(s: &str) -> Expr {
+    match s.parse::<u32>() {
+        Ok(n) => Expr::Int(n.into()),
+        Err(_) => Expr::str(s),
     }
+}
 
-    lines.push("".into());
+/// How a node gets created, before any attributes or inputs are set.
+enum Constructor {
+    Node(&'static str),
+    Math(String),
+    /// Name of the Python function that builds the node group.
+    Group(String),
+}
 
-    let tiers = topographic_sort(&group.shader.nodes, &group.shader.links);
+enum InputValue {
+    Literal(String),
+    Link { node: String, socket: Expr },
+}
 
-    let mut inbound_edges = BTreeMap::<&str, Vec<&Link>>::new();
-    for link in &group.shader.links {
-        inbound_edges.entry(&link.to_node).or_default().push(link);
+/// Everything both backends need to know to emit a single node.
+struct NodeCall<'a> {
+    /// The node's name in Blender.
+    name: String,
+    /// The variable the node is assigned to.
+    var: String,
+    /// `None` for reroutes added by the layout.
+    node: Option<&'a Node>,
+    constructor: Constructor,
+    attributes: Vec<(&'a str, String)>,
+    /// Destination socket keys, already aliased.
+    inputs: Vec<(Expr, InputValue)>,
+    location: (i32, i32),
+}
+
+impl NodeCall<'_> {
+    fn after(&self, node: &str) -> Vec<String> {
+        self.node.map(|n| n.after(node)).unwrap_or_default()
     }
+}
 
-    let x_coordinates = (0..).step_by(150);
-    let y_coordinates = (0..).step_by(200);
+/// Rough height of a node in the editor: a header, plus a row per socket and per property.
+fn estimated_height(sockets: usize, attributes: usize) -> f32 {
+    40.0 + 22.0 * sockets as f32 + 26.0 * attributes as f32
+}
 
-    let node_iterator = tiers.iter().zip(x_coordinates).flat_map(|(tier, x)| {
-        tier.iter()
-            .zip(std::iter::repeat(x))
-            .zip(y_coordinates.clone())
-    });
+/// Builds the calls for every node in a group's or material's shader, targeting the given
+/// Blender version. Sockets that the version's nodes don't have are added to `errors`.
+/// Node variables can't be any of `reserved`, or shadow any of the functions.
+fn node_calls<'a>(
+    owner: &str,
+    shader: &'a Shader,
+    version: BlenderVersion,
+    function_names: &FunctionNames,
+    reserved: &[&str],
+    errors: &mut Vec<SocketMismatch>,
+) -> Vec<NodeCall<'a>> {
+    let mapping = mapping::get();
+    let nodes = &shader.nodes;
+    let mut scope = function_names.local_scope(reserved.iter().copied());
+    let vars = nodes
+        .iter()
+        .map(|node| scope.add(node.name()))
+        .collect::<Vec<_>>();
+    let index_of = |name: &str| nodes.iter().position(|n| n.name() == name);
+
+    for node in nodes {
+        let node_type = mapping.node_type(node, version);
+        check_socket(owner, node.name(), node_type, None, version, errors);
+    }
 
-    for ((node, x), y) in node_iterator {
-        let var_name = node.name();
-        let type_name = node.python_type();
+    let mut inputs = nodes.iter().map(|_| vec![]).collect::<Vec<_>>();
+    for (node, node_inputs) in nodes.iter().zip(&mut inputs) {
+        for input in node.inputs_override() {
+            node_inputs.push((
+                input.name.clone(),
+                InputValue::Literal(input.value.to_string()),
+            ));
+        }
+    }
 
-        let first_arg = match node {
-            Node::Group(group) => group.group_name.to_snake_case() + "_node_group",
-            Node::UvDegradation(_) => "uv_degradation_node_group".into(),
-            Node::ProjectToAxisPlane(_) => "project_to_axis_plane_node_group".into(),
-            Node::Math(math) => python_enum(math.operation),
-            _ => format!("bpy.types.{}", node.python_type()),
+    // Source socket keys, aliased, with a layout port for each distinct one.
+    let mut ports = BTreeMap::<(usize, String), usize>::new();
+    let mut edges = vec![];
+    let mut edge_links = vec![];
+    for link in &shader.links {
+        let (Some(from), Some(to)) = (index_of(&link.from_node), index_of(&link.to_node)) else {
+            continue;
         };
+        let src_socket = output_key(owner, &nodes[from], &link.from_socket, version, errors);
+        let next_port = ports.len();
+        let from_port = *ports.entry((from, src_socket.clone())).or_insert(next_port);
+        edges.push(LayoutEdge {
+            from,
+            from_port,
+            to,
+        });
+        edge_links.push((link, src_socket));
+    }
+
+    let heights = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| {
+            let outputs = ports.keys().filter(|(from, _)| *from == i).count().max(1);
+            let linked = edges.iter().filter(|e| e.to == i).count();
+            estimated_height(inputs[i].len() + linked + outputs, node.attributes().len())
+        })
+        .collect::<Vec<_>>();
+
+    let layout = layout::layout(&heights, &edges, &LayoutOptions::default());
+
+    let reroute_names = (0..layout.reroute_locations.len())
+        .map(|_| {
+            let name = (1..)
+                .map(|n| format!("reroute_{n}"))
+                .find(|name| !scope.contains(name))
+                .unwrap();
+            scope.add(&name)
+        })
+        .collect::<Vec<_>>();
+    let mut reroute_sources = vec![None; reroute_names.len()];
+
+    for ((edge, (link, src_socket)), route) in edges.iter().zip(edge_links).zip(&layout.routes) {
+        let mut src = (vars[edge.from].clone(), socket_key(&src_socket));
+        for &reroute in route {
+            reroute_sources[reroute].get_or_insert_with(|| src.clone());
+            src = (reroute_names[reroute].clone(), Expr::Int(0));
+        }
+        let (node, socket) = src;
+        inputs[edge.to].push((link.to_socket.clone(), InputValue::Link { node, socket }));
+    }
 
-        let method_name = match node {
-            Node::Group(_) | Node::UvDegradation(_) | Node::ProjectToAxisPlane(_) => "group_node",
-            Node::Math(_) => "math_node",
-            _ => "node",
+    let to_location = |(x, y): (f32, f32)| (x.round() as i32, y.round() as i32);
+
+    let mut inputs = inputs.into_iter().map(Some).collect::<Vec<_>>();
+    let mut calls = vec![];
+
+    for slot in layout.layers.iter().flatten() {
+        let i = match *slot {
+            Slot::Node(i) => i,
+            Slot::Reroute(r) => {
+                let (node, socket) = reroute_sources[r].clone().unwrap();
+                calls.push(NodeCall {
+                    name: reroute_names[r].clone(),
+                    var: reroute_names[r].clone(),
+                    node: None,
+                    constructor: Constructor::Node("NodeReroute"),
+                    attributes: vec![],
+                    inputs: vec![(Expr::Int(0), InputValue::Link { node, socket })],
+                    location: to_location(layout.reroute_locations[r]),
+                });
+                continue;
+            }
         };
 
-        lines.extend([
-            format!("{var_name} = graph.{method_name}("),
-            format!("    {first_arg},"),
-        ]);
+        let node = &nodes[i];
+        let type_name = mapping.node_type(node, version);
 
-        let mut has_attributes = false;
-        for (name, val) in node.attributes() {
-            has_attributes = true;
-            lines.push(format!("    {name}={val},"));
-        }
+        let constructor = match (node, custom_groups::for_node(node)) {
+            (_, Some(custom)) => Constructor::Group(custom.function.into()),
+            (Node::Group(group), None) => {
+                Constructor::Group(function_names.group(&group.group_name))
+            }
+            (Node::Math(math), None) => Constructor::Math(python_enum(math.operation)),
+            _ => Constructor::Node(type_name),
+        };
 
-        let mut inputs = Vec::<(String, String)>::new();
+        let inputs = inputs[i]
+            .take()
+            .unwrap()
+            .into_iter()
+            .map(|(dst_socket, value)| {
+                let dst_socket = mapping
+                    .input_alias(version, type_name, &dst_socket)
+                    .unwrap_or(&dst_socket);
+                let (identifier, key) = version.resolve_socket(node, type_name, dst_socket, true);
+                check_socket(
+                    owner,
+                    node.name(),
+                    type_name,
+                    Some((&identifier, true)),
+                    version,
+                    errors,
+                );
+                (socket_key(&key), value)
+            })
+            .collect();
+
+        calls.push(NodeCall {
+            name: node.name().to_owned(),
+            var: vars[i].clone(),
+            node: Some(node),
+            constructor,
+            attributes: node.attributes(),
+            inputs,
+            location: to_location(layout.node_locations[i]),
+        });
+    }
 
-        for input in node.inputs_override() {
-            inputs.push((input.name.clone(), input.value.to_string()));
+    calls
+}
+
+/// Adds a mismatch to `errors` if the version has no such node type, or, given a socket,
+/// if the node type doesn't have it.
+fn check_socket(
+    owner: &str,
+    node: &str,
+    node_type: &'static str,
+    socket: Option<(&str, bool)>,
+    version: BlenderVersion,
+    errors: &mut Vec<SocketMismatch>,
+) {
+    let mismatch = match (version.sockets(node_type), socket) {
+        (None, _) => Some(None),
+        (Some(sockets), Some((socket, is_input))) if !sockets.has(socket, is_input) => {
+            Some(Some((socket.to_owned(), is_input)))
         }
+        _ => None,
+    };
+    if let Some(socket) = mismatch {
+        errors.push(SocketMismatch {
+            version,
+            group: owner.to_owned(),
+            node: node.to_owned(),
+            node_type,
+            socket,
+        });
+    }
+}
 
-        for link in inbound_edges
-            .get(&var_name)
-            .map(|x| &**x)
-            .unwrap_or_default()
-        {
-            let src_node = &link.from_node;
-            let mut src_socket = &*link.from_socket;
-
-            if let Some(src_node_obj) = group.shader.nodes.iter().find(|n| n.name() == src_node) {
-                if let Some(alias_table) = OUTPUT_ALIASES.get(src_node_obj.python_type()) {
-                    if let Some(alias) = alias_table.get(src_socket) {
-                        src_socket = alias;
-                    }
-                }
-            }
+/// The key the generated code uses for one of a node's outputs, after aliasing.
+/// An output the version's node doesn't have is added to `errors`.
+fn output_key(
+    owner: &str,
+    node: &Node,
+    socket: &str,
+    version: BlenderVersion,
+    errors: &mut Vec<SocketMismatch>,
+) -> String {
+    let mapping = mapping::get();
+    let node_type = mapping.node_type(node, version);
+    let socket = mapping
+        .output_alias(version, node_type, socket)
+        .unwrap_or(socket);
+    let (identifier, key) = version.resolve_socket(node, node_type, socket, false);
+    check_socket(
+        owner,
+        node.name(),
+        node_type,
+        Some((&identifier, false)),
+        version,
+        errors,
+    );
+    key
+}
+
+fn location(x: i32, y: i32) -> Expr {
+    Expr::Tuple(vec![Expr::Int(x.into()), Expr::Int(y.into())])
+}
+
+/// The attributes to set on a group's interface socket: its default, range and tooltip.
+fn socket_settings(details: Option<&InputDetails>) -> Vec<(&'static str, Expr)> {
+    let Some(details) = details else {
+        return vec![];
+    };
+    let mut settings = vec![];
+    if let Some(default) = details.default {
+        settings.push(("default_value", Expr::raw(default.to_string())));
+    }
+    if let Some((min, max)) = details.range {
+        settings.push(("min_value", Expr::raw(min.to_string())));
+        settings.push(("max_value", Expr::raw(max.to_string())));
+    }
+    if let Some(description) = details.description() {
+        settings.push(("description", Expr::str(description)));
+    }
+    settings
+}
+
+fn group_to_python(
+    group: &Group,
+    interface: &Interface,
+    version: BlenderVersion,
+    function_names: &FunctionNames,
+    errors: &mut Vec<SocketMismatch>,
+) -> Vec<Stmt> {
+    let mut body = vec![];
+
+    let sockets = interface
+        .inputs
+        .iter()
+        .map(|socket| ("input", socket))
+        .chain(interface.outputs.iter().map(|socket| ("output", socket)));
+    for (method_name, (name, data_type)) in sockets {
+        let socket_type = Expr::name(format!(
+            "bpy.types.{}",
+            mapping::get().data_type(*data_type)
+        ));
+        // node_dsl only takes a socket's type and name, so defaults, ranges and tooltips
+        // are left to the bpy backend.
+        let call = Expr::name("graph").attr(method_name).call(vec![
+            Arg::Positional(socket_type),
+            Arg::Positional(Expr::str(name)),
+        ]);
+        body.push(Stmt::Expr(call));
+    }
+
+    body.push(Stmt::Blank);
+
+    let calls = node_calls(
+        &group.name,
+        &group.shader,
+        version,
+        function_names,
+        &[],
+        errors,
+    );
+    for call in calls {
+        let var = Expr::name(&call.var);
+        let after = call.after(&format!("{}.node", call.var));
+
+        let (method_name, first_arg) = match call.constructor {
+            Constructor::Group(function_name) => ("group_node", Expr::name(function_name)),
+            Constructor::Math(operation) => ("math_node", Expr::raw(operation)),
+            Constructor::Node(type_name) => ("node", Expr::name(format!("bpy.types.{type_name}"))),
+        };
+
+        let mut args = vec![Arg::Positional(first_arg)];
 
-            let src_socket = get_socket_key(src_socket);
-            inputs.push((link.to_socket.clone(), format!("{src_node}[{src_socket}]")))
+        let has_attributes = !call.attributes.is_empty();
+        for (name, val) in call.attributes {
+            args.push(Arg::kw(name, Expr::raw(val)));
         }
 
-        if !inputs.is_empty() {
+        if !call.inputs.is_empty() {
+            let inputs = call
+                .inputs
+                .into_iter()
+                .map(|(dst_socket, value)| {
+                    let value = match value {
+                        InputValue::Literal(value) => Expr::raw(value),
+                        InputValue::Link { node, socket } => Expr::name(node).index(socket),
+                    };
+                    (dst_socket, value)
+                })
+                .collect();
+
             if has_attributes {
-                lines.push("    inputs={".into());
+                args.push(Arg::kw("inputs", Expr::Dict(inputs)));
             } else {
-                lines.push("    {".into());
+                args.push(Arg::Positional(Expr::Dict(inputs)));
             }
+        }
 
-            for (dst_socket, value) in inputs {
-                let mut dst_socket = &*dst_socket;
-                if let Some(alias) = INPUT_ALIASES.get(type_name).and_then(|m| m.get(dst_socket)) {
-                    dst_socket = alias;
-                }
-                let dst_socket = get_socket_key(dst_socket);
+        let constructor = Expr::name("graph").attr(method_name).call(args);
+        body.push(Stmt::Assign(var.clone(), constructor));
 
-                lines.push(format!("        {dst_socket}: {value},"));
-            }
+        let (x, y) = call.location;
+        body.push(Stmt::Assign(
+            var.attr("node").attr("location"),
+            location(x, y),
+        ));
+        body.push(Stmt::Raw(after));
+        body.push(Stmt::Blank);
+    }
 
-            lines.push("    },".into());
+    body
+}
+
+fn group_to_bpy(
+    group: &Group,
+    interface: &Interface,
+    version: BlenderVersion,
+    function_names: &FunctionNames,
+    errors: &mut Vec<SocketMismatch>,
+) -> Vec<Stmt> {
+    let tree = || Expr::name("tree");
+    let tree_name = || Expr::str(&group.name);
+
+    let mut body = vec![
+        Stmt::Assign(
+            tree(),
+            Expr::name("bpy.data.node_groups.get").call(vec![Arg::Positional(tree_name())]),
+        ),
+        Stmt::If(
+            Expr::raw("tree is not None"),
+            vec![Stmt::Return(Some(tree()))],
+        ),
+        Stmt::Assign(
+            tree(),
+            Expr::name("bpy.data.node_groups.new").call(vec![
+                Arg::Positional(tree_name()),
+                Arg::Positional(Expr::str("ShaderNodeTree")),
+            ]),
+        ),
+    ];
+
+    let sockets = interface
+        .inputs
+        .iter()
+        .map(|socket| ("INPUT", socket))
+        .chain(interface.outputs.iter().map(|socket| ("OUTPUT", socket)));
+    for (in_out, (name, data_type)) in sockets {
+        let socket_type = Expr::str(mapping::get().data_type(*data_type));
+        let call = if version.has_group_interface() {
+            tree().attr("interface").attr("new_socket").call(vec![
+                Arg::Positional(Expr::str(name)),
+                Arg::kw("in_out", Expr::str(in_out)),
+                Arg::kw("socket_type", socket_type),
+            ])
+        } else {
+            let sockets = if in_out == "INPUT" {
+                "inputs"
+            } else {
+                "outputs"
+            };
+            tree().attr(sockets).attr("new").call(vec![
+                Arg::Positional(socket_type),
+                Arg::Positional(Expr::str(name)),
+            ])
+        };
+        let settings = socket_settings(interface.details.get(name).filter(|_| in_out == "INPUT"));
+        if settings.is_empty() {
+            body.push(Stmt::Expr(call));
+            continue;
+        }
+        let socket = || Expr::name("socket");
+        body.push(Stmt::Assign(socket(), call));
+        for (attribute, value) in settings {
+            body.push(Stmt::Assign(socket().attr(attribute), value));
         }
+    }
+
+    body.push(Stmt::Assign(Expr::name("nodes"), tree().attr("nodes")));
+    body.push(Stmt::Assign(Expr::name("links"), tree().attr("links")));
+    body.push(Stmt::Blank);
 
-        lines.push(")".into());
-        lines.push(format!("{var_name}.node.location = ({x}, {y})"));
-        lines.extend(node.after());
-        lines.push("".into());
+    let calls = node_calls(
+        &group.name,
+        &group.shader,
+        version,
+        function_names,
+        &[],
+        errors,
+    );
+    nodes_to_bpy(calls, &mut body);
+
+    body.push(Stmt::Return(Some(tree())));
+
+    body
+}
+
+fn material_to_bpy(
+    material: &Material,
+    version: BlenderVersion,
+    function_names: &FunctionNames,
+    errors: &mut Vec<SocketMismatch>,
+) -> Vec<Stmt> {
+    let mat = || Expr::name("mat");
+    let mat_name = || Expr::str(&material.name);
+
+    let mut body = vec![
+        Stmt::Assign(
+            mat(),
+            Expr::name("bpy.data.materials.get").call(vec![Arg::Positional(mat_name())]),
+        ),
+        Stmt::If(
+            Expr::raw("mat is not None"),
+            vec![Stmt::Return(Some(mat()))],
+        ),
+        Stmt::Assign(
+            mat(),
+            Expr::name("bpy.data.materials.new").call(vec![Arg::Positional(mat_name())]),
+        ),
+        Stmt::Assign(mat().attr("use_nodes"), Expr::raw("True")),
+        Stmt::Assign(Expr::name("nodes"), mat().attr("node_tree").attr("nodes")),
+        Stmt::Assign(Expr::name("links"), mat().attr("node_tree").attr("links")),
+        Stmt::Expr(Expr::name("nodes.clear").call(vec![])),
+        Stmt::Blank,
+    ];
+
+    let calls = node_calls(
+        &material.name,
+        &material.shader,
+        version,
+        function_names,
+        &["output"],
+        errors,
+    );
+    let vars = calls
+        .iter()
+        .filter_map(|call| Some((call.name.clone(), (call.var.clone(), call.node?))))
+        .collect::<BTreeMap<_, _>>();
+
+    // Eyesight links to the material output by name instead of declaring a node for it.
+    let output_x = calls.iter().map(|c| c.location.0).max().unwrap_or(0) + 300;
+    let output = || Expr::name("output");
+    let output_links = material
+        .shader
+        .links
+        .iter()
+        .filter(|link| link.to_node == "output")
+        .collect::<Vec<_>>();
+
+    nodes_to_bpy(calls, &mut body);
+
+    body.push(Stmt::Assign(
+        output(),
+        Expr::name("nodes.new").call(vec![Arg::Positional(Expr::str("ShaderNodeOutputMaterial"))]),
+    ));
+    body.push(Stmt::Assign(
+        output().attr("location"),
+        location(output_x, 0),
+    ));
+    for link in output_links {
+        // Like any other link, one from a node that doesn't exist is left out.
+        let Some((var, node)) = vars.get(&link.from_node) else {
+            continue;
+        };
+        let src_socket = output_key(&material.name, node, &link.from_socket, version, errors);
+        let src = Expr::name(var)
+            .attr("outputs")
+            .index(socket_key(&src_socket));
+        let output_type = "ShaderNodeOutputMaterial";
+        let dst_socket = mapping::get()
+            .input_alias(version, output_type, &link.to_socket)
+            .unwrap_or(&link.to_socket);
+        check_socket(
+            &material.name,
+            &link.to_node,
+            output_type,
+            Some((dst_socket, true)),
+            version,
+            errors,
+        );
+        let dst = output().attr("inputs").index(socket_key(dst_socket));
+        let link = Expr::name("links.new").call(vec![Arg::Positional(src), Arg::Positional(dst)]);
+        body.push(Stmt::Expr(link));
     }
+    body.push(Stmt::Blank);
 
-    lines
+    body.push(Stmt::Return(Some(mat())));
+
+    body
+}
+
+/// Creates each node with `nodes.new`, then sets its attributes and inputs.
+fn nodes_to_bpy(calls: Vec<NodeCall>, body: &mut Vec<Stmt>) {
+    for call in calls {
+        let after = call.after(&call.var);
+        let var_name = &*call.var;
+        let var = || Expr::name(var_name);
+        let new_node = |type_name: &str| {
+            let call = Expr::name("nodes.new").call(vec![Arg::Positional(Expr::str(type_name))]);
+            Stmt::Assign(var(), call)
+        };
+
+        match call.constructor {
+            Constructor::Group(function_name) => {
+                body.push(new_node("ShaderNodeGroup"));
+                let tree = Expr::name(function_name).call(vec![]);
+                body.push(Stmt::Assign(var().attr("node_tree"), tree));
+            }
+            Constructor::Math(operation) => {
+                body.push(new_node("ShaderNodeMath"));
+                body.push(Stmt::Assign(var().attr("operation"), Expr::raw(operation)));
+            }
+            Constructor::Node(type_name) => {
+                body.push(new_node(type_name));
+            }
+        }
+
+        let (x, y) = call.location;
+        body.push(Stmt::Assign(var().attr("name"), Expr::str(&call.name)));
+        body.push(Stmt::Assign(var().attr("location"), location(x, y)));
+
+        for (name, val) in call.attributes {
+            body.push(Stmt::Assign(var().attr(name), Expr::raw(val)));
+        }
+
+        for (dst_socket, value) in call.inputs {
+            let dst = var().attr("inputs").index(dst_socket);
+            match value {
+                InputValue::Literal(value) => {
+                    body.push(Stmt::Assign(dst.attr("default_value"), Expr::raw(value)));
+                }
+                InputValue::Link { node, socket } => {
+                    let src = Expr::name(node).attr("outputs").index(socket);
+                    let link = Expr::name("links.new")
+                        .call(vec![Arg::Positional(src), Arg::Positional(dst)]);
+                    body.push(Stmt::Expr(link));
+                }
+            }
+        }
+
+        body.push(Stmt::Raw(after));
+        body.push(Stmt::Blank);
+    }
 }
 
-fn topographic_sort<'a>(nodes: &'a [Node], links: &[Link]) -> Vec<Vec<&'a Node>> {
+pub(crate) fn topographic_sort<'a>(nodes: &'a [Node], links: &[Link]) -> Vec<Vec<&'a Node>> {
     let mut inbound_edges = BTreeMap::<&str, BTreeSet<&str>>::new();
     for link in links {
         inbound_edges
@@ -202,3 +827,54 @@ fn topographic_sort<'a>(nodes: &'a [Node], links: &[Link]) -> Vec<Vec<&'a Node>>
 
     tiers
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material_code(links: &str) -> Result<String, Vec<String>> {
+        let xml = format!(
+            r#"<eyesight><material name="M" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance"><shader>
+                <diffuse_bsdf name="d"/>
+                <mix_closure name="mix"><input name="Fac" type="float" value="0.5"/></mix_closure>
+                <connect from_node="d" from_socket="BSDF" to_node="mix" to_socket="Shader1"/>
+                {links}
+            </shader></material></eyesight>"#
+        );
+        let eyesight = quick_xml::de::from_str::<Eyesight>(&xml).unwrap();
+        let materials = eyesight.materials.iter().collect::<Vec<_>>();
+        let function_names = FunctionNames::new(&eyesight);
+        match material_functions(&materials, &function_names, BlenderVersion::default()) {
+            Ok(functions) => Ok(Module {
+                body: functions.into_iter().map(Stmt::Def).collect(),
+            }
+            .print()),
+            Err(errors) => Err(errors.iter().map(ToString::to_string).collect()),
+        }
+    }
+
+    #[test]
+    fn output_links_are_aliased() {
+        let code = material_code(
+            r#"<connect from_node="mix" from_socket="Closure" to_node="output" to_socket="Surface"/>"#,
+        )
+        .unwrap();
+        assert!(code.contains(r#"links.new(mix.outputs["Shader"], output.inputs["Surface"])"#));
+    }
+
+    #[test]
+    fn output_links_are_checked() {
+        let errors = material_code(
+            r#"<connect from_node="d" from_socket="Closure" to_node="output" to_socket="Surface"/>
+               <connect from_node="mix" from_socket="Closure" to_node="output" to_socket="Bogus"/>"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                "M / d: ShaderNodeBsdfDiffuse in Blender 4.2 has no output socket \"Closure\"",
+                "M / output: ShaderNodeOutputMaterial in Blender 4.2 has no input socket \"Bogus\"",
+            ]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+//! `xml2py.toml`, the project configuration.
+//!
+//! Everything in it is optional; command-line options override it.
+//! Paths are relative to the directory the file is in.
+//!
+//! ```toml
+//! inputs = ["settings.xml", "CustomColorSettings.xml"]
+//! on_conflict = "error"         # or "first", "last", "rename"; see `OnConflict`
+//! on_socket_conflict = "error"  # or "most-used"; see `OnSocketConflict`
+//! roots = ["Solid", "Trans Group Base"]
+//! blender_version = "4.2"
+//! backend = "bpy"               # or "node-dsl"
+//! mapping = "mapping.toml"
+//!
+//! [output]
+//! path = "generated/groups.py"
+//! layout = "file"               # or "split": a module per group in the `path` directory
+//!
+//! [passes]
+//! patches = ["patches/glitter-fix.toml"]  # see `patch` for the format
+//!
+//! [passes.beautify_names]
+//! enabled = true
+//! node_replacements = [["Anitique", "Antique"]]
+//! group_suffixes = ["-GROUP", "Group"]
+//! material_replacements = [["Trans Trans", "Trans"], ["Trans ", "Trans-"]]
+//! node_case = "snake"           # or "title", "pascal", "keep"
+//! group_case = "title"
+//! material_case = "title"
+//! name_map = "names.toml"       # where to write the original and beautified names
+//!
+//! [passes.polyfills]
+//! enabled = false
+//! ```
+
+use std::path::{Path, PathBuf};
+
+use serde_derive::Deserialize;
+
+use crate::blender::BlenderVersion;
+use crate::codegen::Backend;
+
+/// The file that is read when there is no `--config`, if it exists.
+pub const DEFAULT_PATH: &str = "xml2py.toml";
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub inputs: Vec<PathBuf>,
+    pub on_conflict: Option<OnConflict>,
+    pub on_socket_conflict: Option<OnSocketConflict>,
+    #[serde(default)]
+    pub roots: Vec<String>,
+    #[serde(default, deserialize_with = "blender_version")]
+    pub blender_version: Option<BlenderVersion>,
+    pub backend: Option<Backend>,
+    pub mapping: Option<PathBuf>,
+    #[serde(default)]
+    pub output: Output,
+    #[serde(default)]
+    pub passes: Passes,
+}
+
+/// What to do when inputs define a group or material with the same name differently.
+/// Identical definitions are always merged into one.
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnConflict {
+    /// Stop, and say how the definitions differ.
+    #[default]
+    Error,
+    /// Keep the definition that was merged first.
+    First,
+    /// Keep the definition that was merged last.
+    Last,
+    /// Keep both, renaming the later one after the file it's from.
+    /// Uses of it from that file are renamed too.
+    Rename,
+}
+
+/// What to do when group nodes use a group's socket as different types.
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnSocketConflict {
+    /// Stop, and list every conflict.
+    #[default]
+    Error,
+    /// Warn, and give the socket the type that the most materials and groups use it as.
+    MostUsed,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Output {
+    pub path: Option<PathBuf>,
+    pub layout: Option<Layout>,
+}
+
+#[derive(Deserialize, Debug, Default, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// Everything in one module.
+    #[default]
+    File,
+    /// A module per group, in a directory, importing the groups it uses from the others.
+    Split,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Passes {
+    #[serde(default)]
+    pub beautify_names: BeautifyNames,
+    #[serde(default)]
+    pub polyfills: Toggle,
+    /// The built-in patch that makes the Normal group's roughness depend on slope.
+    #[serde(default)]
+    pub slope_roughness: Toggle,
+    #[serde(default)]
+    pub principled_v2: Toggle,
+    /// Patch files to apply, in order, after the built-in ones. See `patch`.
+    #[serde(default)]
+    pub patches: Vec<PathBuf>,
+}
+
+/// A pass without parameters.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Toggle {
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+impl Default for Toggle {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct BeautifyNames {
+    pub enabled: bool,
+    /// Typos to fix in node names, in order, before they're made snake_case.
+    pub node_replacements: Vec<(String, String)>,
+    /// Trimmed from the end of group names before they're made Title Case.
+    pub group_suffixes: Vec<String>,
+    /// Applied in order after material names are made Title Case.
+    pub material_replacements: Vec<(String, String)>,
+    pub node_case: Case,
+    pub group_case: Case,
+    pub material_case: Case,
+    /// Where to write which name each node, group and material ended up with.
+    pub name_map: Option<PathBuf>,
+}
+
+/// How a beautified name is capitalized and punctuated.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Case {
+    /// As it is, once the replacements are made.
+    Keep,
+    Snake,
+    Title,
+    Pascal,
+}
+
+impl Default for BeautifyNames {
+    fn default() -> Self {
+        let pairs = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|&(from, to)| (from.to_owned(), to.to_owned()))
+                .collect()
+        };
+        Self {
+            enabled: true,
+            node_replacements: pairs(&[
+                ("Anitique", "Antique"),
+                ("Anique", "Antique"),
+                ("Ghrome", "Chrome"),
+            ]),
+            group_suffixes: vec!["-GROUP".into(), "Group".into()],
+            material_replacements: pairs(&[("Trans Trans", "Trans"), ("Trans ", "Trans-")]),
+            node_case: Case::Snake,
+            group_case: Case::Title,
+            material_case: Case::Title,
+            name_map: None,
+        }
+    }
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn blender_version<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<BlenderVersion>, D::Error> {
+    use serde::de::Error;
+    let version = <Option<String> as serde::Deserialize>::deserialize(deserializer)?;
+    version
+        .map(|v| v.parse().map_err(D::Error::custom))
+        .transpose()
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let s = std::fs::read_to_string(path)?;
+        let mut config = toml::from_str::<Self>(&s)?;
+
+        let dir = path.parent().unwrap_or(Path::new(""));
+        for input in &mut config.inputs {
+            *input = dir.join(&*input);
+        }
+        if let Some(mapping) = &mut config.mapping {
+            *mapping = dir.join(&*mapping);
+        }
+        if let Some(output) = &mut config.output.path {
+            *output = dir.join(&*output);
+        }
+        for patch in &mut config.passes.patches {
+            *patch = dir.join(&*patch);
+        }
+        if let Some(name_map) = &mut config.passes.beautify_names.name_map {
+            *name_map = dir.join(&*name_map);
+        }
+
+        Ok(config)
+    }
+}
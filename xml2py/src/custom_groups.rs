@@ -0,0 +1,99 @@
+//! Groups that are written by hand in Python instead of converted from the XML.
+//!
+//! Some are used by name, like any other group. Others stand in for Eyesight nodes that
+//! Blender has no equivalent for, and are used wherever one of those nodes is.
+//! Either way, they're declared here with their interface so the rest of xml2py
+//! can treat them like the groups it converts.
+
+use std::collections::{BTreeMap, HashMap};
+
+use eyesight_xml::nodes::{Node, SocketType};
+
+use crate::groups::Interface;
+
+#[derive(Debug)]
+pub struct CustomGroup {
+    /// The group's name, as group nodes refer to it.
+    pub name: &'static str,
+    /// The Eyesight node this group stands in for, as `Node::kind` spells it.
+    pub node_kind: Option<&'static str>,
+    /// The module that defines it, next to the generated one.
+    pub module: &'static str,
+    /// The function that builds it, or returns it if it already exists.
+    pub function: &'static str,
+    pub inputs: &'static [(&'static str, SocketType)],
+    pub outputs: &'static [(&'static str, SocketType)],
+}
+
+pub const CUSTOM_GROUPS: &[CustomGroup] = &[
+    CustomGroup {
+        name: "Is Slope",
+        node_kind: None,
+        module: "custom_nodes",
+        function: "is_slope_node_group",
+        inputs: &[],
+        outputs: &[("Factor", SocketType::Float)],
+    },
+    CustomGroup {
+        name: "Project To Axis Plane",
+        node_kind: Some("ProjectToAxisPlane"),
+        module: "custom_nodes",
+        function: "project_to_axis_plane_node_group",
+        inputs: &[],
+        outputs: &[("Vector", SocketType::Vector)],
+    },
+    CustomGroup {
+        name: "UV Degradation",
+        node_kind: Some("UvDegradation"),
+        module: "custom_nodes",
+        function: "uv_degradation_node_group",
+        inputs: &[("Strength", SocketType::Float)],
+        outputs: &[("UV", SocketType::Vector)],
+    },
+];
+
+/// The custom group called `name`.
+pub fn get(name: &str) -> Option<&'static CustomGroup> {
+    CUSTOM_GROUPS.iter().find(|g| g.name == name)
+}
+
+/// The custom group a node uses: the one it refers to, or the one it stands in for.
+pub fn for_node(node: &Node) -> Option<&'static CustomGroup> {
+    match node {
+        Node::Group(g) => get(&g.group_name),
+        _ => CUSTOM_GROUPS
+            .iter()
+            .find(|g| g.node_kind == Some(node.kind())),
+    }
+}
+
+/// `from .<module> import ...` for every custom group, a line per module.
+pub fn import_lines() -> Vec<String> {
+    let mut modules = BTreeMap::<&str, Vec<&str>>::new();
+    for group in CUSTOM_GROUPS {
+        modules
+            .entry(group.module)
+            .or_default()
+            .push(group.function);
+    }
+    modules
+        .into_iter()
+        .map(|(module, functions)| format!("from .{module} import {}", functions.join(", ")))
+        .collect()
+}
+
+impl CustomGroup {
+    pub fn interface(&self) -> Interface {
+        let sockets = |sockets: &[(&str, SocketType)]| {
+            sockets
+                .iter()
+                .map(|&(name, data_type)| (name.to_owned(), data_type))
+                .collect()
+        };
+        Interface {
+            inputs: sockets(self.inputs),
+            outputs: sockets(self.outputs),
+            details: HashMap::new(),
+        }
+    }
+}
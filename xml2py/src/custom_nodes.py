@@ -0,0 +1,162 @@
+
+# Stand-ins for the Eyesight nodes that have no Blender equivalent.
+# These approximate Studio's behaviour closely enough for previews;
+# they are not derived from Eyesight's source.
+
+
+def _new_group(name: str) -> tuple[bpy.types.ShaderNodeTree, bool]:
+    if tree := bpy.data.node_groups.get(name):
+        return tree, False
+    return bpy.data.node_groups.new(name, "ShaderNodeTree"), True
+
+
+def _new_socket(tree, name: str, in_out: str, socket_type: str):
+    # Blender 4.0 replaced tree.inputs and tree.outputs with tree.interface.
+    if hasattr(tree, "interface"):
+        return tree.interface.new_socket(name, in_out=in_out, socket_type=socket_type)
+    sockets = tree.inputs if in_out == 'INPUT' else tree.outputs
+    return sockets.new(socket_type, name)
+
+
+def _math(tree, operation: str, a, b=None, location=(0, 0)):
+    node = tree.nodes.new("ShaderNodeMath")
+    node.operation = operation
+    node.location = location
+    for i, value in enumerate((a, b)):
+        if value is None:
+            continue
+        if isinstance(value, bpy.types.NodeSocket):
+            tree.links.new(value, node.inputs[i])
+        else:
+            node.inputs[i].default_value = value
+    return node.outputs[0]
+
+
+def _object_normal(tree, location=(0, 0)):
+    geometry = tree.nodes.new("ShaderNodeNewGeometry")
+    geometry.location = location
+    transform = tree.nodes.new("ShaderNodeVectorTransform")
+    transform.vector_type = 'NORMAL'
+    transform.convert_from = 'WORLD'
+    transform.convert_to = 'OBJECT'
+    transform.location = (location[0] + 200, location[1])
+    tree.links.new(geometry.outputs["Normal"], transform.inputs["Vector"])
+    separate = tree.nodes.new("ShaderNodeSeparateXYZ")
+    separate.location = (location[0] + 400, location[1])
+    tree.links.new(transform.outputs["Vector"], separate.inputs["Vector"])
+    return [
+        _math(tree, 'ABSOLUTE', separate.outputs[axis], location=(location[0] + 600, location[1] - 150 * axis))
+        for axis in range(3)
+    ]
+
+
+def is_slope_node_group():
+    """1.0 on faces that are neither horizontal nor vertical, 0.0 elsewhere."""
+    tree, is_new = _new_group("Is Slope")
+    if not is_new:
+        return tree
+
+    _new_socket(tree, "Factor", 'OUTPUT', "NodeSocketFloat")
+
+    _, _, z = _object_normal(tree)
+    above = _math(tree, 'GREATER_THAN', z, 0.05, location=(800, 0))
+    below = _math(tree, 'LESS_THAN', z, 0.95, location=(800, -150))
+    both = _math(tree, 'MULTIPLY', above, below, location=(1000, 0))
+
+    output = tree.nodes.new("NodeGroupOutput")
+    output.location = (1200, 0)
+    tree.links.new(both, output.inputs["Factor"])
+    return tree
+
+
+def project_to_axis_plane_node_group():
+    """Object-space position flattened onto the plane facing the dominant normal axis."""
+    tree, is_new = _new_group("Project To Axis Plane")
+    if not is_new:
+        return tree
+
+    _new_socket(tree, "Vector", 'OUTPUT', "NodeSocketVector")
+
+    coordinates = tree.nodes.new("ShaderNodeTexCoord")
+    separate = tree.nodes.new("ShaderNodeSeparateXYZ")
+    separate.location = (200, 300)
+    tree.links.new(coordinates.outputs["Object"], separate.inputs["Vector"])
+    px, py, pz = separate.outputs
+
+    ax, ay, az = _object_normal(tree, location=(0, -300))
+
+    # One-hot masks for the dominant axis, preferring Z, then Y, then X.
+    z_over_x = _math(tree, 'GREATER_THAN', az, ax, location=(800, -300))
+    z_over_y = _math(tree, 'GREATER_THAN', az, ay, location=(800, -450))
+    use_z = _math(tree, 'MULTIPLY', z_over_x, z_over_y, location=(1000, -300))
+    y_over_x = _math(tree, 'GREATER_THAN', ay, ax, location=(800, -600))
+    not_z = _math(tree, 'SUBTRACT', 1.0, use_z, location=(1000, -450))
+    use_y = _math(tree, 'MULTIPLY', not_z, y_over_x, location=(1200, -450))
+    use_x = _math(tree, 'SUBTRACT', not_z, use_y, location=(1200, -600))
+
+    # Z-facing keeps (x, y), Y-facing keeps (x, z), X-facing keeps (y, z).
+    u = _math(tree, 'ADD',
+              _math(tree, 'MULTIPLY', px, _math(tree, 'ADD', use_z, use_y, location=(1400, -300)), location=(1600, 0)),
+              _math(tree, 'MULTIPLY', py, use_x, location=(1600, -150)),
+              location=(1800, 0))
+    v = _math(tree, 'ADD',
+              _math(tree, 'ADD',
+                    _math(tree, 'MULTIPLY', py, use_z, location=(1600, -300)),
+                    _math(tree, 'MULTIPLY', pz, use_y, location=(1600, -450)),
+                    location=(1800, -300)),
+              _math(tree, 'MULTIPLY', pz, use_x, location=(1600, -600)),
+              location=(2000, -300))
+
+    combine = tree.nodes.new("ShaderNodeCombineXYZ")
+    combine.location = (2200, 0)
+    tree.links.new(u, combine.inputs[0])
+    tree.links.new(v, combine.inputs[1])
+
+    output = tree.nodes.new("NodeGroupOutput")
+    output.location = (2400, 0)
+    tree.links.new(combine.outputs[0], output.inputs["Vector"])
+    return tree
+
+
+def uv_degradation_node_group():
+    """The UV map, jittered by a fine noise to imitate worn printing."""
+    tree, is_new = _new_group("UV Degradation")
+    if not is_new:
+        return tree
+
+    strength = _new_socket(tree, "Strength", 'INPUT', "NodeSocketFloat")
+    strength.default_value = 0.002
+    _new_socket(tree, "UV", 'OUTPUT', "NodeSocketVector")
+
+    group_input = tree.nodes.new("NodeGroupInput")
+    uv = tree.nodes.new("ShaderNodeUVMap")
+    uv.location = (0, 200)
+
+    noise = tree.nodes.new("ShaderNodeTexNoise")
+    noise.location = (200, 0)
+    noise.inputs["Scale"].default_value = 400.0
+    tree.links.new(uv.outputs["UV"], noise.inputs["Vector"])
+
+    centre = tree.nodes.new("ShaderNodeVectorMath")
+    centre.operation = 'SUBTRACT'
+    centre.location = (400, 0)
+    tree.links.new(noise.outputs["Color"], centre.inputs[0])
+    centre.inputs[1].default_value = (0.5, 0.5, 0.5)
+
+    scale = tree.nodes.new("ShaderNodeVectorMath")
+    scale.operation = 'SCALE'
+    scale.location = (600, 0)
+    tree.links.new(centre.outputs["Vector"], scale.inputs[0])
+    tree.links.new(group_input.outputs["Strength"], scale.inputs["Scale"])
+
+    offset = tree.nodes.new("ShaderNodeVectorMath")
+    offset.operation = 'ADD'
+    offset.location = (800, 100)
+    tree.links.new(uv.outputs["UV"], offset.inputs[0])
+    tree.links.new(scale.outputs["Vector"], offset.inputs[1])
+
+    output = tree.nodes.new("NodeGroupOutput")
+    output.location = (1000, 100)
+    tree.links.new(offset.outputs["Vector"], output.inputs["UV"])
+    return tree
+
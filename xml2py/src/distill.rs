@@ -1,95 +1,245 @@
-use std::collections::BTreeMap;
+//! Sorting materials into families: materials that are the same but for their values,
+//! like SOLID-BLUE and SOLID-RED, which differ only in color.
 
-use eyesight_xml::nodes::{Node, NodeInputValue};
+use std::collections::BTreeSet;
+
+use eyesight_xml::nodes::{InputsMut, Node, NodeInputValue, Vec3};
 use eyesight_xml::schema::Material;
 use eyesight_xml::Named;
 
-pub fn distill_materials(materials: &[Material]) {
-    let mut materials = materials
+use crate::merge::shader_diff;
+
+/// Materials whose canonical forms (see [`without_values`]) are the same.
+struct Family {
+    name: String,
+    canonical: Material,
+    /// The members' names, with their parameters: the values that differ between them.
+    members: Vec<(String, Parameters)>,
+}
+
+/// What [`without_values`] took out of a material: where each value was, and what it was.
+type Parameters = Vec<(String, String)>;
+
+/// Sorts the materials into families, each named after the groups its members use,
+/// and lists the members with the values that set them apart. Materials that are alone
+/// are listed last, each with the family it's closest to and how it differs from it.
+pub fn distill_materials(materials: &[Material]) -> String {
+    let mut clusters: Vec<(Material, Vec<(String, Parameters)>)> = vec![];
+    for material in materials {
+        let (canonical, parameters) = without_values(material.clone());
+        let member = (material.name.clone(), parameters);
+        match clusters.iter_mut().find(|(c, _)| *c == canonical) {
+            Some((_, members)) => members.push(member),
+            None => clusters.push((canonical, vec![member])),
+        }
+    }
+
+    let (families, loners): (Vec<_>, Vec<_>) = clusters
+        .into_iter()
+        .partition(|(_, members)| members.len() > 1);
+
+    let mut taken = BTreeSet::new();
+    let families = families
+        .into_iter()
+        .map(|(canonical, mut members)| {
+            keep_varying(&mut members);
+            let base = family_name(&canonical, &members);
+            let name = (1..)
+                .map(|n| match n {
+                    1 => base.clone(),
+                    _ => format!("{base} {n}"),
+                })
+                .find(|name| !taken.contains(name))
+                .unwrap();
+            taken.insert(name.clone());
+            Family {
+                name,
+                canonical,
+                members,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let mut s = String::new();
+    for family in &families {
+        s += &format!("{} ({} materials)\n", family.name, family.members.len());
+        for (name, parameters) in &family.members {
+            let parameters = parameters
+                .iter()
+                .map(|(place, value)| format!("{place} = {value}"))
+                .collect::<Vec<_>>();
+            s += &format!("  {name}: {}\n", parameters.join(", "));
+        }
+        s += "\n";
+    }
+
+    for (canonical, members) in loners {
+        let name = &members[0].0;
+        s += &format!("{name} fits no family\n");
+        let nearest = families
+            .iter()
+            .map(|family| (family, material_diff(&family.canonical, &canonical)))
+            .min_by_key(|(_, diff)| diff.len());
+        match nearest {
+            Some((family, diff)) => {
+                s += &format!("  nearest: {}\n", family.name);
+                for line in diff {
+                    s += &format!("    {line}\n");
+                }
+            }
+            None => s += "  there are no families\n",
+        }
+    }
+
+    s
+}
+
+/// A family's name: the groups its members use, or failing that, the start of the
+/// name that they share.
+fn family_name(canonical: &Material, members: &[(String, Parameters)]) -> String {
+    let groups = canonical
+        .shader
+        .nodes
         .iter()
-        .cloned()
-        .map(|m| (m.name.clone(), m))
-        .collect::<BTreeMap<_, _>>();
+        .filter_map(|node| match node {
+            Node::Group(g) => Some(&*g.group_name),
+            _ => None,
+        })
+        .collect::<BTreeSet<_>>();
+    if !groups.is_empty() {
+        return groups.into_iter().collect::<Vec<_>>().join(" + ");
+    }
 
-    let mut f = |name: &str| {
-        let zero = without_color(materials[name].clone());
-        extract_colors_like(&zero, &mut materials)
-    };
+    let first = &members[0].0;
+    let prefix = first.split('-').next().unwrap();
+    let shared = members
+        .iter()
+        .all(|(name, _)| name.starts_with(&format!("{prefix}-")));
+    if shared {
+        prefix.to_owned()
+    } else {
+        first.clone()
+    }
+}
 
-    let _solid = f("SOLID-BLUE");
-    let _chrome = f("CHROME-GREEN");
-    let _glitter = f("GLITTER-TRANS_BRIGHT_GREEN");
-    let _metal = f("METAL-COPPER");
-    let _milky = f("MILKY-GLOW_IN_DARK_RED");
-    // milky opaque/trans/white / non-gitd seem to each be slightly unique
-    let _pearl_flat = f("PEARL-BLUE");
-    let _pearl = f("PEARL-GOLD");
-    let _rubber = f("RUBBER-BLUE");
-    let _rubber_trans = f("RUBBER-TRANS_RED");
-    let _satin = f("SATIN-TRANS_DARK_PINK");
-    let _speckle = f("SPECKLE-BLACK_GOLD");
-    let _trans = f("TRANS-AQUA");
-    let _glowing_neon = f("TRANS-GLOWING_NEON_MAGENTA");
-    let _luminous = f("TRANS-LUMINOUS_CYAN");
-    let _luminous_soft = f("TRANS-LUMINOUS_SOFT_GREEN");
-    let _trans_neon = f("TRANS-NEON_YELLOW");
-    let _translucent = f("TRANS-TRANSLUCENT_LIGHT_BLUE");
-
-    for name in materials.keys() {
-        println!("{name}");
+/// How `b` differs from `a`, like [`shader_diff`], and in the material's own settings.
+fn material_diff(a: &Material, b: &Material) -> Vec<String> {
+    let mut diff = shader_diff(&a.shader, &b.shader);
+    let mut settings = b.clone();
+    settings.shader = a.shader.clone();
+    if settings != *a {
+        diff.push("~ material settings".to_owned());
     }
+    diff
 }
 
-fn extract_colors_like(
-    zero: &Material,
-    materials: &mut BTreeMap<String, Material>,
-) -> Vec<Material> {
-    let names = materials.keys().cloned().collect::<Vec<_>>();
-
-    let mut matches = vec![];
-    for name in &names {
-        let uncolored = without_color(materials[name].clone());
-        if uncolored == *zero {
-            matches.push(materials.remove(name).unwrap());
-        }
+/// Leaves the members only the parameters whose values aren't the same for all of them.
+fn keep_varying(members: &mut [(String, Parameters)]) {
+    let first = members[0].1.clone();
+    let varies = |i: usize| members.iter().any(|(_, p)| p.get(i) != first.get(i));
+    let varying = (0..first.len())
+        .filter(|&i| varies(i))
+        .collect::<BTreeSet<_>>();
+    for (_, parameters) in members {
+        let mut i = 0;
+        parameters.retain(|_| {
+            i += 1;
+            varying.contains(&(i - 1))
+        });
     }
-    matches
 }
 
-fn without_color(mut material: Material) -> Material {
+/// The material with every literal value zeroed and its name cleared, and its nodes and
+/// links in a fixed order, so that materials that differ only in their values compare
+/// equal. The values that were taken out are returned with it, in node order, so
+/// materials that compare equal have their values at the same places.
+fn without_values(mut material: Material) -> (Material, Parameters) {
+    let mut parameters = vec![];
+    material.shader.nodes.sort_by_key(|n| n.name().to_owned());
+
     for node in &mut material.shader.nodes {
-        if let Node::Color(color_node) = node {
-            if matches!(
-                &*color_node.name,
-                "RGB" | "RGB_GlowDark" | "RGB_Chip" | "RGB_White" | "RGB_Second"
-            ) {
-                color_node.value.0 = [0.0; 3];
-            }
-        } else if let Node::Group(group_node) = node {
-            if matches!(
-                &*group_node.group_name,
-                "PEARL-GROUP" | "PEARL-FLAT-GROUP" | "SATIN-GROUP"
-            ) {
-                for input in &mut group_node.inputs_ {
-                    input.value = Some(NodeInputValue::Float(0.0));
-                }
-            }
-        } else if let Node::Value(value_node) = node {
-            if value_node.name == "XOffset" {
-                value_node.value = 0.0;
-            }
-        } else if let Node::Emission(emission_node) = node {
-            for input in &mut emission_node.inputs {
-                if input.name == "Color" {
-                    input.value = NodeInputValue::Color(Default::default())
+        let node_name = node.name().to_owned();
+        match node {
+            Node::Color(n) => parameters.push((node_name.clone(), take(&mut n.value))),
+            Node::Vector(n) => parameters.push((node_name.clone(), take(&mut n.value))),
+            Node::Value(n) => parameters.push((node_name.clone(), take(&mut n.value))),
+            Node::Group(n) => {
+                for input in &mut n.inputs_ {
+                    if let Some(value) = &mut input.value {
+                        parameters.push((format!("{node_name}.{}", input.name), take_input(value)));
+                    }
                 }
             }
+            _ => {}
+        }
+
+        for input in node.inputs_mut().into_iter().flatten() {
+            let value = take_input(&mut input.value);
+            parameters.push((format!("{node_name}.{}", input.name), value));
         }
     }
 
     material.name.clear();
-    material.shader.nodes.sort_by_key(|n| n.name().to_owned());
     material.shader.links.sort();
 
-    material
+    (material, parameters)
+}
+
+/// Zeroes a value, returning what it was.
+fn take<T: Default + ToString>(value: &mut T) -> String {
+    std::mem::take(value).to_string()
+}
+
+/// Zeroes an input's value, keeping its type, and returns what it was.
+fn take_input(value: &mut NodeInputValue) -> String {
+    let zero = match value {
+        NodeInputValue::Float(_) => NodeInputValue::Float(0.0),
+        NodeInputValue::Vector(_) => NodeInputValue::Vector(Vec3::default()),
+        NodeInputValue::Int(_) => NodeInputValue::Int(0),
+        NodeInputValue::Color(_) => NodeInputValue::Color(Vec3::default()),
+        NodeInputValue::Boolean(_) => NodeInputValue::Boolean(false),
+    };
+    std::mem::replace(value, zero).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use eyesight_xml::schema::Eyesight;
+
+    use super::*;
+
+    fn material(name: &str, color: &str, extra: &str) -> String {
+        format!(
+            r#"<material name="{name}" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance">
+    <shader>
+      <color name="tint" value="{color}"/>
+      <diffuse_bsdf name="diffuse">
+        <input name="Roughness" type="float" value="0.5"/>
+      </diffuse_bsdf>
+      {extra}
+      <connect from_node="tint" from_socket="Color" to_node="diffuse" to_socket="Color"/>
+      <connect from_node="diffuse" from_socket="BSDF" to_node="output" to_socket="Surface"/>
+    </shader>
+  </material>"#
+        )
+    }
+
+    #[test]
+    fn recolored_materials_form_a_family() {
+        let xml = format!(
+            "<eyesight>{}{}{}</eyesight>",
+            material("SOLID-BLUE", "0 0 1", ""),
+            material("SOLID-RED", "1 0 0", ""),
+            material("ODD", "0 1 0", r#"<value name="offset" value="2"/>"#),
+        );
+        let eyesight = quick_xml::de::from_str::<Eyesight>(&xml).unwrap();
+        let s = distill_materials(&eyesight.materials);
+
+        let blue = Vec3([0.0, 0.0, 1.0]);
+        let red = Vec3([1.0, 0.0, 0.0]);
+        assert!(s.starts_with(&format!(
+            "SOLID (2 materials)\n  SOLID-BLUE: tint = {blue}\n  SOLID-RED: tint = {red}\n"
+        )));
+        assert!(s.contains("ODD fits no family\n  nearest: SOLID\n    + node offset"));
+    }
 }
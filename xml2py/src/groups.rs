@@ -1,55 +1,417 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap};
+
+use heck::ToSnakeCase;
+
+use crate::custom_groups::{self, CUSTOM_GROUPS};
+use crate::mapping;
 
 use eyesight_xml::{
-    nodes::{GroupReference, Node, SocketType},
+    nodes::{INode, Node, NodeInputValue, SocketType},
     schema::{Eyesight, Group},
+    Named,
 };
 
-pub fn check_interfaces(eyesight: &Eyesight) -> HashMap<String, Interface> {
+/// The interface of every group whose socket types are all known, custom groups included.
+///
+/// Sockets are in the order the XML first mentions them: links from the group's input
+/// or to its output node, then the sockets of group nodes that use it. The mapping's
+/// `[interfaces]` table can move sockets to the front. Custom groups have the sockets
+/// they're declared with, and using one they don't have is an error (see [`InterfaceError`]).
+///
+/// A socket's type comes from the group nodes that use the group, or failing that, from
+/// what it's linked to inside the group (see [`infer_from_bodies`]). Uses that disagree
+/// are reported as conflicts, and the type most of them agree on is used. Groups with a
+/// socket whose type can't be told are left out, and reported too.
+///
+/// Inputs also get the details that make a group added by hand in Blender behave the
+/// way it does in Studio: see [`InputDetails`].
+pub fn check_interfaces(eyesight: &Eyesight) -> InterfaceReport {
     let mut interfaces = eyesight
         .groups
         .iter()
-        .map(|group| (group.name.clone(), discover_sockets(&group)))
+        .map(|group| (group.name.clone(), discover_sockets(group)))
         .collect::<HashMap<_, _>>();
 
-    let mut unused_groups = interfaces.keys().cloned().collect::<HashSet<_>>();
+    let mut errors = vec![];
+    for custom in CUSTOM_GROUPS {
+        if let Some(group) = eyesight.groups.iter().find(|g| g.name == custom.name) {
+            errors.push(InterfaceError::DefinedInXml {
+                group: group.name.clone(),
+                owner: group.span.to_string(),
+            });
+        }
+        interfaces.insert(custom.name.to_owned(), custom.interface().into());
+    }
 
-    let shader_materials = eyesight.materials.iter().map(|m| &m.shader);
-    let group_materials = eyesight.groups.iter().map(|g| &g.shader);
-    let all_nodes = shader_materials
-        .chain(group_materials)
-        .flat_map(|g| &g.nodes);
+    let mut uses = BTreeMap::<Slot, Uses>::new();
+    for custom in CUSTOM_GROUPS {
+        let sockets = [(Side::Input, custom.inputs), (Side::Output, custom.outputs)];
+        for (side, sockets) in sockets {
+            for &(socket, data_type) in sockets {
+                let slot = (custom.name.to_owned(), side, socket.to_owned());
+                uses.insert(slot, vec![(data_type, vec!["its declaration".into()])]);
+            }
+        }
+    }
+
+    let material_shaders = eyesight
+        .materials
+        .iter()
+        .map(|m| (format!("material {}", m.name), &m.shader));
+    let group_shaders = eyesight
+        .groups
+        .iter()
+        .map(|g| (format!("group {}", g.name), &g.shader));
+    let all_nodes = material_shaders
+        .chain(group_shaders)
+        .flat_map(|(owner, shader)| shader.nodes.iter().map(move |node| (owner.clone(), node)));
+
+    for (owner, node) in all_nodes {
+        // Nodes that a custom group stands in for only say what their inputs are.
+        let (group_name, inputs, outputs): (_, Vec<_>, Vec<_>) = match node {
+            Node::Group(node) => (
+                &*node.group_name,
+                node.inputs_
+                    .iter()
+                    .map(|i| (&*i.name, i.data_type))
+                    .collect(),
+                node.outputs
+                    .iter()
+                    .map(|o| (&*o.name, o.data_type))
+                    .collect(),
+            ),
+            _ => match custom_groups::for_node(node) {
+                Some(custom) => (
+                    custom.name,
+                    node.inputs()
+                        .iter()
+                        .map(|i| (&*i.name, value_type(&i.value)))
+                        .collect(),
+                    vec![],
+                ),
+                None => continue,
+            },
+        };
+        // `CallGraph::undefined` reports uses of groups that don't exist.
+        if !interfaces.contains_key(group_name) {
+            continue;
+        }
+        let sockets = [(Side::Input, inputs), (Side::Output, outputs)];
+        for (side, sockets) in sockets {
+            let interface = interfaces.get_mut(group_name).unwrap();
+            for (socket, data_type) in sockets {
+                if !add_used_socket(group_name, interface, side, socket) {
+                    errors.push(InterfaceError::Undeclared {
+                        group: group_name.to_owned(),
+                        side,
+                        socket: socket.to_owned(),
+                        owner: owner.clone(),
+                    });
+                    continue;
+                }
+                let slot = (group_name.to_owned(), side, socket.to_owned());
+                let types = uses.entry(slot).or_default();
+                let owners = match types.iter_mut().find(|(t, _)| *t == data_type) {
+                    Some((_, owners)) => owners,
+                    None => {
+                        types.push((data_type, vec![]));
+                        &mut types.last_mut().unwrap().1
+                    }
+                };
+                if !owners.contains(&owner) {
+                    owners.push(owner.clone());
+                }
+            }
+        }
 
-    for node in all_nodes {
         if let Node::Group(node) = node {
-            if node.group_name != "Is Slope" {
-                check_socket_types(node, &mut interfaces);
+            let observed = &mut interfaces.get_mut(group_name).unwrap().observed;
+            for input in &node.inputs_ {
+                let Some(value) = input.value else { continue };
+                let values = observed.entry(input.name.clone()).or_default();
+                match values.iter_mut().find(|(v, _)| *v == value) {
+                    Some((_, count)) => *count += 1,
+                    None => values.push((value, 1)),
+                }
             }
-            unused_groups.remove(&node.group_name);
         }
     }
 
+    let mut conflicts = vec![];
+    for ((group, side, socket), types) in uses {
+        // A custom group's declaration comes first, and always wins.
+        let mut chosen = types
+            .iter()
+            .max_by_key(|(_, owners)| owners.len())
+            .unwrap()
+            .0;
+        if custom_groups::get(&group).is_some() {
+            chosen = types[0].0;
+        }
+        *slot(&mut interfaces, &group, side, &socket).unwrap() = Some(chosen);
+        if types.len() > 1 {
+            conflicts.push(SocketConflict {
+                group,
+                side,
+                socket,
+                uses: types,
+                chosen,
+            });
+        }
+    }
+
+    let ambiguous = infer_from_bodies(eyesight, &mut interfaces);
+
     let mut complete = HashMap::new();
+    let mut unresolved = vec![];
+
+    let mut interfaces = interfaces.into_iter().collect::<Vec<_>>();
+    interfaces.sort_by(|(a, _), (b, _)| a.cmp(b));
 
     for (name, incomplete) in interfaces {
-        if unused_groups.contains(&name) {
-            eprintln!("{name}");
+        let mut interface = Interface::default();
+        let mut observed = incomplete.observed;
+        let mut unknown = vec![];
+        let sockets = [
+            (Side::Input, incomplete.inputs, &mut interface.inputs),
+            (Side::Output, incomplete.outputs, &mut interface.outputs),
+        ];
+        for (side, incomplete, complete) in sockets {
+            for (socket_name, data_type) in incomplete {
+                match data_type {
+                    Some(data_type) => complete.push((socket_name, data_type)),
+                    None => unknown.push((side, socket_name)),
+                }
+            }
+        }
+        if !unknown.is_empty() {
+            for (side, socket) in unknown {
+                let key = (name.clone(), side, socket);
+                let candidates = ambiguous.get(&key).cloned().unwrap_or_default();
+                let (group, side, socket) = key;
+                unresolved.push(UnresolvedSocket {
+                    group,
+                    side,
+                    socket,
+                    candidates,
+                });
+            }
             continue;
         }
-
-        let mut interface = Interface::default();
-        for (socket_name, data_type) in incomplete.inputs {
-            let data_type = data_type.unwrap_or_else(|| panic!("{name} / {socket_name}"));
-            interface.inputs.insert(socket_name, data_type);
+        let group = eyesight.groups.iter().find(|g| g.name == name);
+        for (socket_name, data_type) in &interface.inputs {
+            let observed = observed.remove(socket_name).unwrap_or_default();
+            if let Some(details) = input_details(group, socket_name, *data_type, observed) {
+                interface.details.insert(socket_name.clone(), details);
+            }
         }
-        for (socket_name, data_type) in incomplete.outputs {
-            let data_type = data_type.unwrap_or_else(|| panic!("{name} / {socket_name}"));
-            interface.outputs.insert(socket_name, data_type);
+        if let Some(order) = mapping::get().socket_order(&name) {
+            let sockets = [
+                (Side::Input, &mut interface.inputs, &order.inputs),
+                (Side::Output, &mut interface.outputs, &order.outputs),
+            ];
+            for (side, sockets, order) in sockets {
+                for socket in reorder(sockets, order) {
+                    errors.push(InterfaceError::NotInOrder {
+                        group: name.clone(),
+                        side,
+                        socket,
+                    });
+                }
+            }
         }
         complete.insert(name, interface);
     }
 
-    complete
+    InterfaceReport {
+        interfaces: complete,
+        conflicts,
+        unresolved,
+        errors,
+    }
+}
+
+/// Every interface that could be worked out, and what got in the way of the others.
+#[derive(Debug, Default)]
+pub struct InterfaceReport {
+    pub interfaces: HashMap<String, Interface>,
+    pub conflicts: Vec<SocketConflict>,
+    pub unresolved: Vec<UnresolvedSocket>,
+    pub errors: Vec<InterfaceError>,
+}
+
+/// The types a socket is used as, each with the materials and groups that use it so.
+type Uses = Vec<(SocketType, Vec<String>)>;
+
+/// A group socket that group nodes use as more than one type.
+#[derive(Debug, Clone)]
+pub struct SocketConflict {
+    pub group: String,
+    pub side: Side,
+    pub socket: String,
+    /// In the order they're first seen, or for a custom group, its declared type first.
+    pub uses: Uses,
+    /// The type the interface has anyway: a custom group's declared one, or else the
+    /// one the most materials and groups use.
+    pub chosen: SocketType,
+}
+
+impl std::fmt::Display for SocketConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {} {:?} is used as",
+            self.group,
+            self.side.name(),
+            self.socket
+        )?;
+        for (i, (data_type, owners)) in self.uses.iter().enumerate() {
+            let sep = if i == 0 { " " } else { "; and as " };
+            write!(f, "{sep}{data_type:?} by {}", owners.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// A group socket whose type can't be told, which keeps its group from being converted.
+#[derive(Debug, Clone)]
+pub struct UnresolvedSocket {
+    pub group: String,
+    pub side: Side,
+    pub socket: String,
+    /// The types it's linked to inside the group, if it's linked to more than one.
+    pub candidates: Vec<SocketType>,
+}
+
+impl std::fmt::Display for UnresolvedSocket {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (group, kind, socket) = (&self.group, self.side.name(), &self.socket);
+        if self.candidates.is_empty() {
+            return write!(f, "{group}: nothing says what type {kind} {socket:?} is");
+        }
+        let types = self
+            .candidates
+            .iter()
+            .map(|t| format!("{t:?}"))
+            .collect::<Vec<_>>();
+        write!(
+            f,
+            "{group}: {kind} {socket:?} could be {}",
+            types.join(" or ")
+        )
+    }
+}
+
+/// Something that doesn't fit a group's interface, which is worked out without it.
+#[derive(Debug, Clone)]
+pub enum InterfaceError {
+    /// The XML defines a group with a custom group's name. The custom group is used.
+    DefinedInXml { group: String, owner: String },
+    /// A material or group uses a socket that a custom group isn't declared with.
+    Undeclared {
+        group: String,
+        side: Side,
+        socket: String,
+        owner: String,
+    },
+    /// The mapping's `[interfaces]` order names a socket that the group doesn't have.
+    NotInOrder {
+        group: String,
+        side: Side,
+        socket: String,
+    },
+}
+
+impl std::fmt::Display for InterfaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DefinedInXml { group, owner } => write!(
+                f,
+                "{group}: defined at {owner}, but it's a custom group, which is used instead"
+            ),
+            Self::Undeclared {
+                group,
+                side,
+                socket,
+                owner,
+            } => write!(
+                f,
+                "{group}: {owner} uses {} {socket:?}, which the custom group isn't declared with",
+                side.name()
+            ),
+            Self::NotInOrder {
+                group,
+                side,
+                socket,
+            } => write!(
+                f,
+                "{group}: the mapping's socket order names {} {socket:?}, which it doesn't have",
+                side.name()
+            ),
+        }
+    }
+}
+
+/// Words in socket names for floats that only make sense from 0 to 1.
+const FACTOR_WORDS: &[&str] = &[
+    "fac",
+    "factor",
+    "rough",
+    "roughness",
+    "metallic",
+    "alpha",
+    "blend",
+    "weight",
+];
+
+fn is_factor(socket: &str) -> bool {
+    socket
+        .to_snake_case()
+        .split('_')
+        .any(|word| FACTOR_WORDS.contains(&word))
+}
+
+/// The details of a group's input, given the values its uses give it.
+///
+/// A float input is treated as a factor, from 0 to 1, if its name says so or it's linked
+/// to a socket whose name does, unless a use gives it a value outside that range.
+fn input_details(
+    group: Option<&Group>,
+    socket: &str,
+    data_type: SocketType,
+    mut observed: Vec<(NodeInputValue, usize)>,
+) -> Option<InputDetails> {
+    observed.retain(|(value, _)| value_type(value) == data_type);
+    // A stable sort, so that ties stay in the order they were first seen.
+    observed.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+    let linked_to_factor = group.is_some_and(|group| {
+        let input_node = group
+            .shader
+            .nodes
+            .iter()
+            .find(|n| matches!(n, Node::GroupInput(_)));
+        group.shader.links.iter().any(|link| {
+            input_node.is_some_and(|n| n.name() == link.from_node)
+                && link.from_socket == socket
+                && is_factor(&link.to_socket)
+        })
+    });
+    let in_range = observed
+        .iter()
+        .all(|(value, _)| matches!(value, NodeInputValue::Float(n) if (0.0..=1.0).contains(n)));
+    let range =
+        (data_type == SocketType::Float && (is_factor(socket) || linked_to_factor) && in_range)
+            .then_some((0.0, 1.0));
+
+    if observed.is_empty() && range.is_none() {
+        return None;
+    }
+    Some(InputDetails {
+        default: observed.first().map(|&(value, _)| value),
+        observed,
+        range,
+    })
 }
 
 fn discover_sockets(group: &Group) -> IncompleteInterface {
@@ -65,56 +427,539 @@ fn discover_sockets(group: &Group) -> IncompleteInterface {
         }
     }
 
+    // A link straight from the input node to the output node is on both.
     for link in &group.shader.links {
         if Some(&link.from_node) == input_node_name {
-            interface.inputs.insert(link.from_socket.clone(), None);
-        } else if Some(&link.to_node) == output_node_name {
-            interface.outputs.insert(link.to_socket.clone(), None);
+            socket_entry(&mut interface.inputs, &link.from_socket);
+        }
+        if Some(&link.to_node) == output_node_name {
+            socket_entry(&mut interface.outputs, &link.to_socket);
         }
     }
 
     interface
 }
 
-fn check_socket_types(
-    node: &GroupReference,
+/// Which of a group's interfaces a socket is on, or which side of a node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Side {
+    Input,
+    Output,
+}
+
+impl Side {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Input => "input",
+            Self::Output => "output",
+        }
+    }
+}
+
+/// A group's interface socket: the group, which side it's on, and its name.
+type Slot = (String, Side, String);
+
+/// One end of a link, as far as its type goes.
+enum End<'a> {
+    /// A socket of a group's interface, whose type may not be known yet: one of the
+    /// group's own, through its input or output node, or one of a group it uses.
+    Interface(&'a str, Side, &'a str),
+    /// A socket of an ordinary node, or of a group node that says what type it is.
+    Node(Option<SocketType>),
+}
+
+/// The end of a link at `socket`, on the `side` of the node called `node_name`.
+fn end<'a>(group: &'a Group, node_name: &str, socket: &'a str, side: Side) -> End<'a> {
+    let Some(node) = group.shader.nodes.iter().find(|n| n.name() == node_name) else {
+        return End::Node(None);
+    };
+    match node {
+        Node::GroupInput(_) => End::Interface(&group.name, Side::Input, socket),
+        Node::GroupOutput(_) => End::Interface(&group.name, Side::Output, socket),
+        Node::Group(g) => {
+            let declared = match side {
+                Side::Input => g
+                    .inputs_
+                    .iter()
+                    .find(|i| i.name == socket)
+                    .map(|i| i.data_type),
+                Side::Output => g
+                    .outputs
+                    .iter()
+                    .find(|o| o.name == socket)
+                    .map(|o| o.data_type),
+            };
+            match declared {
+                Some(data_type) => End::Node(Some(data_type)),
+                None => End::Interface(&g.group_name, side, socket),
+            }
+        }
+        _ => End::Node(known_socket_type(node, socket, side)),
+    }
+}
+
+fn slot<'a>(
+    interfaces: &'a mut HashMap<String, IncompleteInterface>,
+    group: &str,
+    side: Side,
+    socket: &str,
+) -> Option<&'a mut Option<SocketType>> {
+    let interface = interfaces.get_mut(group)?;
+    let sockets = match side {
+        Side::Input => &mut interface.inputs,
+        Side::Output => &mut interface.outputs,
+    };
+    sockets
+        .iter_mut()
+        .find(|(n, _)| n == socket)
+        .map(|(_, t)| t)
+}
+
+/// Gives the sockets that no group node's usage settles the type of whatever their links
+/// inside a group say: the type of the node socket at the other end, or of the other
+/// group's interface socket, once that's known. This goes round until nothing changes,
+/// so types make their way through any number of nested groups.
+///
+/// A socket that's linked to sockets of different types is left as it is, and returned
+/// with the types it could be.
+fn infer_from_bodies(
+    eyesight: &Eyesight,
     interfaces: &mut HashMap<String, IncompleteInterface>,
-) {
-    let interface = interfaces.get_mut(&node.group_name).unwrap();
+) -> BTreeMap<Slot, Vec<SocketType>> {
+    loop {
+        let mut candidates = BTreeMap::<Slot, Vec<SocketType>>::new();
+
+        for group in &eyesight.groups {
+            for link in &group.shader.links {
+                let ends = [
+                    end(group, &link.from_node, &link.from_socket, Side::Output),
+                    end(group, &link.to_node, &link.to_socket, Side::Input),
+                ];
+                let types = ends.each_ref().map(|end| match *end {
+                    End::Interface(g, side, socket) => {
+                        slot(interfaces, g, side, socket).and_then(|t| *t)
+                    }
+                    End::Node(data_type) => data_type,
+                });
+                let Some(data_type) = types[0].or(types[1]) else {
+                    continue;
+                };
+                for (end, known) in ends.iter().zip(types) {
+                    let &End::Interface(g, side, socket) = end else {
+                        continue;
+                    };
+                    if known.is_some() || slot(interfaces, g, side, socket).is_none() {
+                        continue;
+                    }
+                    let types = candidates
+                        .entry((g.to_owned(), side, socket.to_owned()))
+                        .or_default();
+                    if !types.contains(&data_type) {
+                        types.push(data_type);
+                    }
+                }
+            }
+        }
 
-    for input in &node.inputs_ {
-        check_socket_type(&mut interface.inputs, &input.name, input.data_type);
+        let mut changed = false;
+        for ((group, side, socket), types) in &candidates {
+            if let [data_type] = types[..] {
+                *slot(interfaces, group, *side, socket).unwrap() = Some(data_type);
+                changed = true;
+            }
+        }
+        if !changed {
+            candidates.retain(|_, types| types.len() > 1);
+            return candidates;
+        }
     }
+}
+
+/// The type of a node's socket, from the literal value it's given, or for sockets whose
+/// type doesn't depend on how the node is set up, from the kind of node.
+fn known_socket_type(node: &Node, socket: &str, side: Side) -> Option<SocketType> {
+    use SocketType::{Closure, Color, Float, Vector};
 
-    for output in &node.outputs {
-        check_socket_type(&mut interface.outputs, &output.name, output.data_type);
+    if side == Side::Input {
+        if let Some(input) = node.inputs().iter().find(|i| i.name == socket) {
+            return Some(value_type(&input.value));
+        }
     }
+
+    let data_type = match (side, node, socket) {
+        (Side::Input, Node::PrincipledBsdf(_), _) => match socket {
+            "BaseColor" | "SubsurfaceColor" | "Emission" | "EmissionColor" => Color,
+            "Normal" | "ClearcoatNormal" | "Tangent" | "SubsurfaceRadius" => Vector,
+            _ => Float,
+        },
+        (Side::Input, _, "Fac" | "Factor") => Float,
+        (Side::Input, _, "Color" | "Color1" | "Color2") => Color,
+        (Side::Input, _, "Normal" | "Tangent") => Vector,
+        (
+            Side::Input,
+            Node::SwitchClosure(_) | Node::MixClosure(_) | Node::AddClosure(_),
+            "Shader1" | "Shader2" | "Closure1" | "Closure2",
+        ) => Closure,
+        (
+            Side::Input,
+            Node::Math(_) | Node::MixValue(_) | Node::SwitchFloat(_),
+            "Value1" | "Value2" | "Value3" | "ValueDisable" | "ValueEnable",
+        ) => Float,
+        (Side::Input, Node::Vector(_), "X" | "Y" | "Z") => Float,
+        (Side::Input, Node::VectorMath(_), "Scale") => Float,
+        (Side::Input, Node::Mapping(_), _) => Vector,
+        (
+            Side::Input,
+            Node::VectorMath(_) | Node::MixVector(_),
+            "Vector1" | "Vector2" | "Vector3" | "A" | "B",
+        ) => Vector,
+        (
+            Side::Input,
+            Node::NoiseTexture(_)
+            | Node::VoronoiTexture(_)
+            | Node::ImageTexture(_)
+            | Node::VectorTransform(_),
+            "Vector",
+        ) => Vector,
+        (Side::Input, Node::Bump(_), "Distance" | "Height") => Float,
+        (Side::Input, Node::LayerWeight(_), "Blend") => Float,
+        (Side::Input, Node::AbsorptionVolume(_), "Density") => Float,
+        (Side::Input, _, "Roughness" | "Strength" | "Scale" | "Detail" | "Distortion") => Float,
+        (Side::Input, _, _) => return None,
+
+        (
+            Side::Output,
+            Node::DiffuseBsdf(_)
+            | Node::GlossyBsdf(_)
+            | Node::PrincipledBsdf(_)
+            | Node::TranslucentBsdf(_)
+            | Node::TransparentBsdf(_)
+            | Node::Emission(_)
+            | Node::AbsorptionVolume(_)
+            | Node::SwitchClosure(_)
+            | Node::MixClosure(_)
+            | Node::AddClosure(_),
+            _,
+        ) => Closure,
+        (Side::Output, Node::TextureCoordinate(_) | Node::Uvmap(_), _) => Vector,
+        (
+            Side::Output,
+            Node::Math(_) | Node::Value(_) | Node::MixValue(_) | Node::SwitchFloat(_),
+            _,
+        ) => Float,
+        (
+            Side::Output,
+            Node::Mix(_) | Node::Color(_) | Node::RgbCurves(_) | Node::BrightnessContrast(_),
+            _,
+        ) => Color,
+        (
+            Side::Output,
+            Node::Bump(_)
+            | Node::NormalMap(_)
+            | Node::RoundingEdgeNormal(_)
+            | Node::Mapping(_)
+            | Node::Vector(_)
+            | Node::MixVector(_)
+            | Node::VectorTransform(_),
+            _,
+        ) => Vector,
+        (Side::Output, _, "Color") => Color,
+        (Side::Output, _, "Normal" | "Vector" | "Position" | "Location") => Vector,
+        (
+            Side::Output,
+            _,
+            "Fac" | "Value" | "Alpha" | "Distance" | "Fresnel" | "Facing" | "Random",
+        ) => Float,
+        (Side::Output, _, _) => return None,
+    };
+    Some(data_type)
 }
 
-fn check_socket_type(
-    sockets: &mut HashMap<String, Option<SocketType>>,
+/// Adds a socket that a use of a group mentions. A custom group must already have it:
+/// returns whether it does.
+fn add_used_socket(
+    group_name: &str,
+    interface: &mut IncompleteInterface,
+    side: Side,
     name: &str,
-    usage_type: SocketType,
-) {
-    let data_type = sockets.entry(name.into()).or_insert_with(|| {
-        // println!("unknown socket: {name} {usage_type:?}");
-        None
-    });
+) -> bool {
+    let sockets = match side {
+        Side::Input => &mut interface.inputs,
+        Side::Output => &mut interface.outputs,
+    };
+    if custom_groups::get(group_name).is_some() && !sockets.iter().any(|(n, _)| n == name) {
+        return false;
+    }
+    socket_entry(sockets, name);
+    true
+}
 
-    match data_type {
-        None => *data_type = Some(usage_type),
-        Some(existing) => assert_eq!(usage_type, *existing),
+pub fn value_type(value: &NodeInputValue) -> SocketType {
+    match value {
+        NodeInputValue::Float(_) => SocketType::Float,
+        NodeInputValue::Vector(_) => SocketType::Vector,
+        NodeInputValue::Int(_) => SocketType::Int,
+        NodeInputValue::Color(_) => SocketType::Color,
+        NodeInputValue::Boolean(_) => SocketType::Boolean,
     }
 }
 
+/// Sockets in interface order, with their names.
+type Sockets<T> = Vec<(String, T)>;
+
+/// The socket called `name`, which is added to the end if it's new.
+fn socket_entry<'a>(
+    sockets: &'a mut Sockets<Option<SocketType>>,
+    name: &str,
+) -> &'a mut Option<SocketType> {
+    let i = match sockets.iter().position(|(n, _)| n == name) {
+        Some(i) => i,
+        None => {
+            sockets.push((name.into(), None));
+            sockets.len() - 1
+        }
+    };
+    &mut sockets[i].1
+}
+
+/// Moves the sockets named in `order` to the front, in that order. Returns the names
+/// that aren't sockets.
+fn reorder(sockets: &mut Sockets<SocketType>, order: &[String]) -> Vec<String> {
+    sockets.sort_by_key(|(name, _)| order.iter().position(|o| o == name).unwrap_or(order.len()));
+    order
+        .iter()
+        .filter(|name| !sockets.iter().any(|(n, _)| n == *name))
+        .cloned()
+        .collect()
+}
+
 #[derive(Default, Debug)]
 struct IncompleteInterface {
-    inputs: HashMap<String, Option<SocketType>>,
-    outputs: HashMap<String, Option<SocketType>>,
+    inputs: Sockets<Option<SocketType>>,
+    outputs: Sockets<Option<SocketType>>,
+    /// The values uses give each input, with how many give each, in the order they're seen.
+    observed: HashMap<String, Vec<(NodeInputValue, usize)>>,
+}
+
+impl From<Interface> for IncompleteInterface {
+    fn from(interface: Interface) -> Self {
+        let incomplete = |sockets: Sockets<SocketType>| {
+            sockets
+                .into_iter()
+                .map(|(name, data_type)| (name, Some(data_type)))
+                .collect()
+        };
+        Self {
+            inputs: incomplete(interface.inputs),
+            outputs: incomplete(interface.outputs),
+            observed: HashMap::new(),
+        }
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct Interface {
-    pub inputs: HashMap<String, SocketType>,
-    pub outputs: HashMap<String, SocketType>,
+    pub inputs: Sockets<SocketType>,
+    pub outputs: Sockets<SocketType>,
+    /// Inputs' defaults and ranges, by name, for the inputs that have any.
+    pub details: HashMap<String, InputDetails>,
+}
+
+/// How a group input is set up, beyond its type.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InputDetails {
+    /// The value most uses give it, or the first of them if there's a tie.
+    pub default: Option<NodeInputValue>,
+    /// Every value uses give it, with how many give each, most common first.
+    pub observed: Vec<(NodeInputValue, usize)>,
+    /// The smallest and largest values a factor-like float should take.
+    pub range: Option<(f32, f32)>,
+}
+
+impl InputDetails {
+    /// Whether the uses disagree about the default.
+    pub fn is_disputed(&self) -> bool {
+        self.observed.len() > 1
+    }
+
+    /// A tooltip for the socket, saying what values the uses give it if they disagree.
+    pub fn description(&self) -> Option<String> {
+        if !self.is_disputed() {
+            return None;
+        }
+        let values = self
+            .observed
+            .iter()
+            .map(|(value, count)| {
+                let uses = if *count == 1 { "use" } else { "uses" };
+                format!("{value} in {count} {uses}")
+            })
+            .collect::<Vec<_>>();
+        Some(format!("Studio sets this to {}", values.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn material(name: &str, nodes: &str) -> String {
+        format!(
+            r#"<material name="{name}" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance"><shader>{nodes}<connect from_node="g" from_socket="BSDF" to_node="output" to_socket="Surface"/></shader></material>"#
+        )
+    }
+
+    fn group(name: &str, nodes: &str, links: &[(&str, &str)]) -> String {
+        let links = links
+            .iter()
+            .map(|(from, to)| {
+                let (from_node, from_socket) = from.split_once('.').unwrap();
+                let (to_node, to_socket) = to.split_once('.').unwrap();
+                format!(
+                    r#"<connect from_node="{from_node}" from_socket="{from_socket}" to_node="{to_node}" to_socket="{to_socket}"/>"#
+                )
+            })
+            .collect::<String>();
+        format!(
+            r#"<group name="{name}"><shader><group_input name="in"/><group_output name="out"/>{nodes}{links}</shader></group>"#
+        )
+    }
+
+    fn report(elements: &[String]) -> InterfaceReport {
+        let xml = format!("<eyesight>{}</eyesight>", elements.concat());
+        check_interfaces(&quick_xml::de::from_str(&xml).unwrap())
+    }
+
+    fn strings<T: ToString>(items: &[T]) -> Vec<String> {
+        items.iter().map(T::to_string).collect()
+    }
+
+    fn sockets(pairs: &[(&str, SocketType)]) -> Sockets<SocketType> {
+        pairs.iter().map(|&(n, t)| (n.to_owned(), t)).collect()
+    }
+
+    #[test]
+    fn types_flow_through_nested_groups() {
+        let report = report(&[
+            group(
+                "Outer",
+                r#"<group name="inner" group_name="Inner"/>"#,
+                &[
+                    ("in.Amount", "inner.Amount"),
+                    ("inner.Result", "out.Result"),
+                ],
+            ),
+            group(
+                "Inner",
+                r#"<diffuse_bsdf name="d"/>"#,
+                &[("in.Amount", "d.Roughness"), ("d.BSDF", "out.Result")],
+            ),
+        ]);
+        assert!(report.unresolved.is_empty() && report.conflicts.is_empty());
+        for name in ["Outer", "Inner"] {
+            let interface = &report.interfaces[name];
+            assert_eq!(interface.inputs, sockets(&[("Amount", SocketType::Float)]));
+            assert_eq!(
+                interface.outputs,
+                sockets(&[("Result", SocketType::Closure)])
+            );
+        }
+        // Linked to a roughness, so it's a factor; Outer's is only linked to a group.
+        assert_eq!(
+            report.interfaces["Inner"].details["Amount"],
+            InputDetails {
+                default: None,
+                observed: vec![],
+                range: Some((0.0, 1.0)),
+            }
+        );
+        assert!(report.interfaces["Outer"].details.is_empty());
+    }
+
+    #[test]
+    fn untold_and_ambiguous_sockets_are_unresolved() {
+        let report = report(&[
+            group(
+                "Ambiguous",
+                r#"<diffuse_bsdf name="d"/>"#,
+                &[("in.X", "d.Roughness"), ("in.X", "d.Color")],
+            ),
+            group("Untold", "", &[("in.Y", "out.Z")]),
+        ]);
+        assert!(!report.interfaces.contains_key("Ambiguous"));
+        assert!(!report.interfaces.contains_key("Untold"));
+        assert_eq!(
+            strings(&report.unresolved),
+            [
+                "Ambiguous: input \"X\" could be Float or Color",
+                "Untold: nothing says what type input \"Y\" is",
+                "Untold: nothing says what type output \"Z\" is",
+            ]
+        );
+    }
+
+    #[test]
+    fn uses_settle_types_and_defaults() {
+        let uses = |value: &str, data_type: &str| {
+            format!(
+                r#"<group name="g" group_name="G"><input name="Fac" type="{data_type}" value="{value}"/></group>"#
+            )
+        };
+        let report = report(&[
+            material("M1", &uses("0.5", "float")),
+            material("M2", &uses("0.25", "float")),
+            material("M3", &uses("1 0 0", "color")),
+            group("G", "", &[("in.Fac", "out.Result")]),
+        ]);
+        assert_eq!(
+            strings(&report.conflicts),
+            [
+                "G: input \"Fac\" is used as Float by material M1, material M2; \
+              and as Color by material M3"
+            ]
+        );
+
+        let interface = &report.interfaces["G"];
+        assert_eq!(interface.inputs, sockets(&[("Fac", SocketType::Float)]));
+        assert_eq!(interface.outputs, sockets(&[("Result", SocketType::Float)]));
+        let details = &interface.details["Fac"];
+        assert_eq!(details.default, Some(NodeInputValue::Float(0.5)));
+        assert_eq!(details.range, Some((0.0, 1.0)));
+        assert_eq!(
+            details.description().unwrap(),
+            "Studio sets this to 0.5 in 1 use, 0.25 in 1 use"
+        );
+    }
+
+    #[test]
+    fn custom_groups_keep_their_declarations() {
+        let report = report(&[
+            material(
+                "M",
+                r#"<group name="g" group_name="UV Degradation"><input name="Bogus" type="float" value="1"/></group>
+                   <uv_degradation name="uv"><input name="Strength" type="color" value="1 1 1"/></uv_degradation>"#,
+            ),
+            group("Is Slope", "", &[("in.Fac", "out.Factor")]),
+        ]);
+        assert_eq!(
+            strings(&report.errors),
+            [
+                "Is Slope: defined at (unknown), but it's a custom group, which is used instead",
+                "UV Degradation: material M uses input \"Bogus\", \
+                 which the custom group isn't declared with",
+            ]
+        );
+        assert_eq!(
+            strings(&report.conflicts),
+            [
+                "UV Degradation: input \"Strength\" is used as Float by its declaration; \
+              and as Color by material M"
+            ]
+        );
+        assert_eq!(
+            report.interfaces["UV Degradation"].inputs,
+            sockets(&[("Strength", SocketType::Float)])
+        );
+        assert!(report.interfaces["Is Slope"].inputs.is_empty());
+    }
 }
@@ -0,0 +1,224 @@
+//! Python identifiers for the names in the XML.
+//!
+//! Node variables and group and material functions are named after what they stand
+//! for, but those names can be anything: they can start with a digit, be keywords,
+//! or be names that the generated code already uses. Each [`Scope`] hands out valid
+//! names that are distinct from each other and from the ones it reserves.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use heck::AsSnakeCase;
+
+use eyesight_xml::schema::Eyesight;
+
+use crate::custom_groups;
+
+pub const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// Names the generated code refers to, other than the ones it gives to nodes and functions:
+/// the headers' imports and helpers, the backends' locals, and the locals of nodes' `after`
+/// statements.
+pub const GENERATED: &[&str] = &[
+    "bpy",
+    "os",
+    "ShaderGraph",
+    "load_image",
+    "graph",
+    "tree",
+    "nodes",
+    "links",
+    "mat",
+    "socket",
+    "elements",
+    "original_elements",
+    "pos",
+    "rgba",
+    "e",
+];
+
+/// `name`, with anything that can't be in an identifier replaced by an underscore,
+/// and an underscore in front if it would start with a digit.
+pub fn sanitize(name: &str) -> String {
+    let mut s = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    if s.is_empty() || s.starts_with(|c: char| c.is_ascii_digit()) {
+        s.insert(0, '_');
+    }
+    s
+}
+
+/// The names in use in a module or function.
+#[derive(Debug, Clone)]
+pub struct Scope {
+    reserved: BTreeSet<String>,
+    taken: BTreeSet<String>,
+}
+
+impl Scope {
+    /// A scope where the keywords, [`GENERATED`] and `reserved` can't be used.
+    pub fn new<'a>(reserved: impl IntoIterator<Item = &'a str>) -> Self {
+        Self {
+            reserved: KEYWORDS
+                .iter()
+                .chain(GENERATED)
+                .copied()
+                .chain(reserved)
+                .map(String::from)
+                .collect(),
+            taken: BTreeSet::new(),
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.reserved.contains(name) || self.taken.contains(name)
+    }
+
+    /// A valid identifier for `name` that nothing else in the scope has. A reserved name
+    /// gets an underscore after it; one that's taken gets a number, starting from 2.
+    pub fn add(&mut self, name: &str) -> String {
+        let mut base = sanitize(name);
+        if self.reserved.contains(&base) {
+            base.push('_');
+        }
+        let mut name = base.clone();
+        for n in 2.. {
+            if !self.contains(&name) {
+                break;
+            }
+            name = format!("{base}_{n}");
+        }
+        self.taken.insert(name.clone());
+        name
+    }
+}
+
+/// The functions that build groups and materials, by the group or material's name.
+/// They share the module scope with the custom groups' functions.
+#[derive(Debug, Clone)]
+pub struct FunctionNames {
+    groups: BTreeMap<String, String>,
+    materials: BTreeMap<String, String>,
+    scope: Scope,
+}
+
+impl FunctionNames {
+    pub fn new(eyesight: &Eyesight) -> Self {
+        let mut scope = Scope::new(custom_groups::CUSTOM_GROUPS.iter().map(|g| g.function));
+        let mut names = |names: Vec<&str>, suffix: &str| {
+            let mut map = BTreeMap::new();
+            for name in names {
+                if !map.contains_key(name) {
+                    let function = scope.add(&format!("{}_{suffix}", AsSnakeCase(name)));
+                    map.insert(name.to_owned(), function);
+                }
+            }
+            map
+        };
+        let groups = names(
+            eyesight.groups.iter().map(|g| &*g.name).collect(),
+            "node_group",
+        );
+        let materials = names(
+            eyesight.materials.iter().map(|m| &*m.name).collect(),
+            "material",
+        );
+        Self {
+            groups,
+            materials,
+            scope,
+        }
+    }
+
+    /// The function for a group, which may be a custom group or not defined at all.
+    pub fn group(&self, name: &str) -> String {
+        if let Some(custom) = custom_groups::get(name) {
+            return custom.function.to_owned();
+        }
+        match self.groups.get(name) {
+            Some(function) => function.clone(),
+            None => sanitize(&format!("{}_node_group", AsSnakeCase(name))),
+        }
+    }
+
+    pub fn material(&self, name: &str) -> String {
+        self.materials[name].clone()
+    }
+
+    /// A scope for a function's locals, which can't shadow any of the functions.
+    pub fn local_scope<'a>(&self, reserved: impl IntoIterator<Item = &'a str>) -> Scope {
+        let mut scope = Scope::new(reserved);
+        scope.reserved.extend(self.scope.taken.iter().cloned());
+        scope.reserved.extend(self.scope.reserved.iter().cloned());
+        scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eyesight_xml::schema::{Group, Shader};
+
+    use super::*;
+
+    #[test]
+    fn sanitize_makes_identifiers() {
+        assert_eq!(sanitize("Trans-Clear 2"), "Trans_Clear_2");
+        assert_eq!(sanitize("2x"), "_2x");
+        assert_eq!(sanitize(""), "_");
+        assert_eq!(sanitize("café"), "caf_");
+    }
+
+    #[test]
+    fn scope_avoids_reserved_and_taken_names() {
+        let mut scope = Scope::new(["group_input"]);
+        assert_eq!(scope.add("class"), "class_");
+        assert_eq!(scope.add("tree"), "tree_");
+        assert_eq!(scope.add("group_input"), "group_input_");
+        assert_eq!(scope.add("mix"), "mix");
+        assert_eq!(scope.add("mix"), "mix_2");
+        assert_eq!(scope.add("mix-"), "mix_");
+        assert_eq!(scope.add("mix_2"), "mix_2_2");
+        assert!(scope.contains("mix_2"));
+        assert!(scope.contains("bpy"));
+        assert!(!scope.contains("other"));
+    }
+
+    #[test]
+    fn function_names_are_distinct() {
+        let group = |name: &str| Group {
+            name: name.into(),
+            shader: Shader::default(),
+            span: Default::default(),
+        };
+        let eyesight = Eyesight {
+            version: None,
+            materials: vec![],
+            groups: vec![group("Trans Clear"), group("Trans-Clear"), group("Normal")],
+        };
+        let names = FunctionNames::new(&eyesight);
+        assert_eq!(names.group("Trans Clear"), "trans_clear_node_group");
+        assert_eq!(names.group("Trans-Clear"), "trans_clear_node_group_2");
+        assert_eq!(names.group("Normal"), "normal_node_group");
+        assert_eq!(names.group("Undefined"), "undefined_node_group");
+
+        let custom = &custom_groups::CUSTOM_GROUPS[0];
+        assert_eq!(names.group(custom.name), custom.function);
+
+        // Locals can't shadow the functions.
+        let mut locals = names.local_scope([]);
+        assert_eq!(locals.add("normal_node_group"), "normal_node_group_");
+        assert_eq!(locals.add(custom.function), format!("{}_", custom.function));
+    }
+}
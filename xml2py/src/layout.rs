@@ -0,0 +1,373 @@
+//! Layered (Sugiyama-style) graph layout.
+//!
+//! Knows nothing about shaders or Python: callers describe nodes by their estimated height
+//! and edges by their endpoints, and get back locations in Blender's node editor units
+//! (x grows to the right, y grows upwards, a location is the node's top-left corner).
+//!
+//! The steps are the usual ones:
+//! 1. assign layers by longest path, then pull each node right up against its consumers,
+//! 2. split edges spanning several layers with reroute nodes,
+//! 3. reduce crossings with alternating barycentric sweeps,
+//! 4. stack each layer vertically using the node heights, centred on y = 0.
+
+use std::collections::BTreeMap;
+
+#[derive(Debug, Clone)]
+pub struct LayoutOptions {
+    /// Horizontal distance between the left edges of adjacent layers.
+    pub layer_spacing: f32,
+    /// Vertical gap between nodes in the same layer.
+    pub node_spacing: f32,
+    /// Height reserved for a reroute node.
+    pub reroute_height: f32,
+    /// Number of barycentric sweeps (alternating down and up) to try.
+    pub sweeps: usize,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            layer_spacing: 300.0,
+            node_spacing: 40.0,
+            reroute_height: 20.0,
+            sweeps: 12,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LayoutEdge {
+    pub from: usize,
+    /// Identifies the output the edge leaves from.
+    /// Long edges leaving the same output of the same node share their reroutes.
+    pub from_port: usize,
+    pub to: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Slot {
+    Node(usize),
+    Reroute(usize),
+}
+
+#[derive(Debug, Clone)]
+pub struct Layout {
+    /// Location of each input node.
+    pub node_locations: Vec<(f32, f32)>,
+    /// Location of each reroute node.
+    pub reroute_locations: Vec<(f32, f32)>,
+    /// For each input edge, the reroutes it passes through, from source to destination.
+    pub routes: Vec<Vec<usize>>,
+    /// Nodes and reroutes by layer, each layer ordered top to bottom.
+    /// Every edge goes from an earlier layer to a later one, except edges within cycles.
+    pub layers: Vec<Vec<Slot>>,
+}
+
+pub fn layout(heights: &[f32], edges: &[LayoutEdge], options: &LayoutOptions) -> Layout {
+    let node_layers = assign_layers(heights.len(), edges);
+
+    let mut layers = vec![Vec::<Slot>::new(); node_layers.iter().max().map_or(0, |l| l + 1)];
+    for (i, &layer) in node_layers.iter().enumerate() {
+        layers[layer].push(Slot::Node(i));
+    }
+
+    // Split long edges.
+    let mut reroute_layers = vec![];
+    let mut shared = BTreeMap::<(usize, usize, usize), usize>::new();
+    let mut routes = vec![];
+    let mut segments = vec![];
+    for edge in edges {
+        let (start, end) = (node_layers[edge.from], node_layers[edge.to]);
+        let mut route = vec![];
+        let mut prev = Slot::Node(edge.from);
+        for (layer, slots) in layers.iter_mut().enumerate().take(end).skip(start + 1) {
+            let reroute = *shared
+                .entry((edge.from, edge.from_port, layer))
+                .or_insert_with(|| {
+                    reroute_layers.push(layer);
+                    slots.push(Slot::Reroute(reroute_layers.len() - 1));
+                    reroute_layers.len() - 1
+                });
+            route.push(reroute);
+            segments.push((prev, Slot::Reroute(reroute)));
+            prev = Slot::Reroute(reroute);
+        }
+        if end > start {
+            segments.push((prev, Slot::Node(edge.to)));
+        }
+        routes.push(route);
+    }
+    segments.sort();
+    segments.dedup();
+
+    let slot_layer = |slot: Slot| match slot {
+        Slot::Node(i) => node_layers[i],
+        Slot::Reroute(i) => reroute_layers[i],
+    };
+
+    let mut predecessors = BTreeMap::<Slot, Vec<Slot>>::new();
+    let mut successors = BTreeMap::<Slot, Vec<Slot>>::new();
+    for &(from, to) in &segments {
+        if slot_layer(to) == slot_layer(from) + 1 {
+            predecessors.entry(to).or_default().push(from);
+            successors.entry(from).or_default().push(to);
+        }
+    }
+
+    let mut best = layers.clone();
+    let mut best_crossings = count_crossings(&layers, &successors);
+
+    for sweep in 0..options.sweeps {
+        if best_crossings == 0 {
+            break;
+        }
+        if sweep % 2 == 0 {
+            for i in 1..layers.len() {
+                let (before, after) = layers.split_at_mut(i);
+                reorder(&mut after[0], &before[i - 1], &predecessors);
+            }
+        } else {
+            for i in (0..layers.len().saturating_sub(1)).rev() {
+                let (before, after) = layers.split_at_mut(i + 1);
+                reorder(&mut before[i], &after[0], &successors);
+            }
+        }
+        let crossings = count_crossings(&layers, &successors);
+        if crossings < best_crossings {
+            best_crossings = crossings;
+            best = layers.clone();
+        }
+    }
+
+    let layers = best;
+
+    let mut node_locations = vec![(0.0, 0.0); heights.len()];
+    let mut reroute_locations = vec![(0.0, 0.0); reroute_layers.len()];
+    for (layer_index, layer) in layers.iter().enumerate() {
+        let height_of = |slot: &Slot| match *slot {
+            Slot::Node(i) => heights[i],
+            Slot::Reroute(_) => options.reroute_height,
+        };
+        let total = layer.iter().map(height_of).sum::<f32>()
+            + options.node_spacing * layer.len().saturating_sub(1) as f32;
+
+        let x = layer_index as f32 * options.layer_spacing;
+        let mut y = total / 2.0;
+        for slot in layer {
+            let location = (x, y);
+            match *slot {
+                Slot::Node(i) => node_locations[i] = location,
+                Slot::Reroute(i) => reroute_locations[i] = location,
+            }
+            y -= height_of(slot) + options.node_spacing;
+        }
+    }
+
+    Layout {
+        node_locations,
+        reroute_locations,
+        routes,
+        layers,
+    }
+}
+
+/// Longest-path layering from the sources, followed by pulling every node
+/// as far right as its successors allow. Edges that close a cycle are ignored.
+fn assign_layers(node_count: usize, edges: &[LayoutEdge]) -> Vec<usize> {
+    let mut in_degree = vec![0usize; node_count];
+    let mut outgoing = vec![vec![]; node_count];
+    for edge in edges {
+        if edge.from != edge.to {
+            in_degree[edge.to] += 1;
+            outgoing[edge.from].push(edge.to);
+        }
+    }
+
+    // Kahn's algorithm, always taking the lowest index so the result is deterministic.
+    // Nodes stuck in a cycle are released in index order once nothing else is ready.
+    let mut order = vec![];
+    let mut done = vec![false; node_count];
+    let mut ready = (0..node_count)
+        .filter(|&i| in_degree[i] == 0)
+        .collect::<std::collections::BTreeSet<_>>();
+    while order.len() < node_count {
+        let next = match ready.pop_first() {
+            Some(next) => next,
+            None => (0..node_count).find(|&i| !done[i]).unwrap(),
+        };
+        if done[next] {
+            continue;
+        }
+        done[next] = true;
+        order.push(next);
+        for &succ in &outgoing[next] {
+            in_degree[succ] = in_degree[succ].saturating_sub(1);
+            if in_degree[succ] == 0 && !done[succ] {
+                ready.insert(succ);
+            }
+        }
+    }
+
+    let mut position = vec![0; node_count];
+    for (i, &node) in order.iter().enumerate() {
+        position[node] = i;
+    }
+    let is_forward = |from: usize, to: usize| position[from] < position[to];
+
+    let mut layers = vec![0; node_count];
+    for &node in &order {
+        for &succ in &outgoing[node] {
+            if is_forward(node, succ) {
+                layers[succ] = layers[succ].max(layers[node] + 1);
+            }
+        }
+    }
+
+    for &node in order.iter().rev() {
+        let forward_successors = outgoing[node]
+            .iter()
+            .filter(|&&succ| is_forward(node, succ))
+            .map(|&succ| layers[succ]);
+        if let Some(min) = forward_successors.min() {
+            layers[node] = min - 1;
+        }
+    }
+
+    layers
+}
+
+fn reorder(layer: &mut [Slot], fixed: &[Slot], neighbours: &BTreeMap<Slot, Vec<Slot>>) {
+    let fixed_position = fixed
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| (*slot, i as f32))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut keyed = layer
+        .iter()
+        .enumerate()
+        .map(|(i, slot)| {
+            let positions = neighbours
+                .get(slot)
+                .into_iter()
+                .flatten()
+                .filter_map(|n| fixed_position.get(n))
+                .collect::<Vec<_>>();
+            let barycenter = if positions.is_empty() {
+                // No neighbours on the fixed side: stay put.
+                i as f32
+            } else {
+                positions.iter().copied().sum::<f32>() / positions.len() as f32
+            };
+            (barycenter, i, *slot)
+        })
+        .collect::<Vec<_>>();
+
+    keyed.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    for (dst, (_, _, slot)) in layer.iter_mut().zip(keyed) {
+        *dst = slot;
+    }
+}
+
+fn count_crossings(layers: &[Vec<Slot>], successors: &BTreeMap<Slot, Vec<Slot>>) -> usize {
+    let mut total = 0;
+    for pair in layers.windows(2) {
+        let lower_position = pair[1]
+            .iter()
+            .enumerate()
+            .map(|(i, slot)| (*slot, i))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut segments = vec![];
+        for (i, slot) in pair[0].iter().enumerate() {
+            for succ in successors.get(slot).into_iter().flatten() {
+                if let Some(&j) = lower_position.get(succ) {
+                    segments.push((i, j));
+                }
+            }
+        }
+
+        for (a, &(i1, j1)) in segments.iter().enumerate() {
+            for &(i2, j2) in &segments[a + 1..] {
+                if (i1 < i2 && j1 > j2) || (i1 > i2 && j1 < j2) {
+                    total += 1;
+                }
+            }
+        }
+    }
+    total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(from: usize, from_port: usize, to: usize) -> LayoutEdge {
+        LayoutEdge {
+            from,
+            from_port,
+            to,
+        }
+    }
+
+    #[test]
+    fn nodes_sit_just_before_their_consumers() {
+        let edges = [edge(0, 0, 1), edge(1, 0, 2), edge(3, 0, 2)];
+        assert_eq!(assign_layers(4, &edges), [0, 1, 2, 1]);
+    }
+
+    #[test]
+    fn cycles_are_broken_by_index() {
+        let edges = [edge(0, 0, 1), edge(1, 0, 0), edge(2, 0, 2)];
+        assert_eq!(assign_layers(3, &edges), [0, 1, 0]);
+    }
+
+    #[test]
+    fn long_edges_share_reroutes_per_output() {
+        let edges = [
+            edge(0, 0, 1),
+            edge(1, 0, 2),
+            edge(1, 0, 3),
+            edge(0, 0, 2),
+            edge(0, 0, 3),
+            edge(0, 1, 3),
+        ];
+        let layout = layout(&[100.0; 4], &edges, &LayoutOptions::default());
+        assert_eq!(
+            layout.routes,
+            [vec![], vec![], vec![], vec![0], vec![0], vec![1]]
+        );
+        assert_eq!(layout.reroute_locations.len(), 2);
+        assert!(layout.reroute_locations.iter().all(|&(x, _)| x == 300.0));
+        assert_eq!(layout.layers.len(), 3);
+    }
+
+    #[test]
+    fn layers_are_stacked_and_centred() {
+        let edges = [edge(0, 0, 2), edge(1, 0, 2)];
+        let layout = layout(&[100.0, 60.0, 50.0], &edges, &LayoutOptions::default());
+        assert_eq!(
+            layout.node_locations,
+            [(0.0, 100.0), (0.0, -40.0), (300.0, 25.0)]
+        );
+    }
+
+    #[test]
+    fn sweeps_remove_crossings() {
+        let layers = vec![
+            vec![Slot::Node(0), Slot::Node(1)],
+            vec![Slot::Node(2), Slot::Node(3)],
+        ];
+        let successors = BTreeMap::from([
+            (Slot::Node(0), vec![Slot::Node(3)]),
+            (Slot::Node(1), vec![Slot::Node(2)]),
+        ]);
+        assert_eq!(count_crossings(&layers, &successors), 1);
+
+        let edges = [edge(0, 0, 3), edge(1, 0, 2)];
+        let layout = layout(&[100.0; 4], &edges, &LayoutOptions::default());
+        assert_eq!(layout.layers[1], [Slot::Node(3), Slot::Node(2)]);
+        assert_eq!(count_crossings(&layout.layers, &successors), 0);
+    }
+}
@@ -1,205 +1,516 @@
+pub mod blender;
+pub mod call_graph;
 pub mod distill;
 pub mod groups;
+pub mod visualize;
 
+mod cli;
 mod codegen;
+mod config;
+mod custom_groups;
+mod identifiers;
+mod layout;
+mod package;
+mod passes;
+mod patch;
+mod polyfill;
+mod principled;
+mod python;
+
+mod mapping;
+mod merge;
+
+use std::collections::BTreeSet;
+
+use call_graph::CallGraph;
+use cli::{Command, Options};
+use config::{Layout, OnSocketConflict};
+use eyesight_xml::schema::Eyesight;
+use eyesight_xml::span::{self, Span};
+use eyesight_xml::{Named, Spanned};
+use heck::ToSnakeCase;
+use mapping::Mapping;
+use passes::Pipeline;
+use polyfill::Conversion;
+use visualize::{GraphFormat, GraphOptions};
 
-mod lookups;
+fn main() {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
 
-use std::collections::{BTreeMap, HashSet};
-use std::io::Write;
-use std::process::{Command, Stdio};
+    if args.iter().any(|a| a == "-h" || a == "--help") {
+        println!("{}", cli::USAGE);
+        return;
+    }
 
-use eyesight_xml::nodes::{
-    GroupReference, MixType, MixValue, MixVector, Node, NodeInput, VectorOperation,
-};
-use eyesight_xml::schema::{Eyesight, Link, Shader};
-use eyesight_xml::Named;
-use heck::{ToPascalCase, ToSnakeCase, ToTitleCase};
+    let (command, options, args) = cli::parse(args).unwrap_or_else(|e| {
+        eprintln!("{e}\n\n{}", cli::USAGE);
+        std::process::exit(1);
+    });
+
+    if let Some(path) = &options.mapping {
+        match Mapping::load(path) {
+            Ok(m) => mapping::init(m),
+            Err(e) => {
+                eprintln!("{}: {e}", path.display());
+                std::process::exit(1);
+            }
+        }
+    }
 
-const SETTINGS_XML: &str =
-    include_str!("/mnt/c/program files/studio 2.0/photorealisticrenderer/win/64/settings.xml");
-const CUSTOM_XML: &str =
-    include_str!("/mnt/c/program files/studio 2.0/data/CustomColors/CustomColorSettings.xml");
+    let pipeline = Pipeline::new(&options).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
 
-fn main() {
-    let eyesight_main = quick_xml::de::from_str::<Eyesight>(SETTINGS_XML).unwrap();
-    let eyesight_custom = quick_xml::de::from_str::<Eyesight>(CUSTOM_XML).unwrap();
-    let mut eyesight = merge_eyesight(eyesight_main, eyesight_custom);
+    if command == Command::ListPasses {
+        no_arguments(&args);
+        list_passes_command(&pipeline, &options);
+        return;
+    }
 
-    beautify_names(&mut eyesight);
+    let eyesight = load_eyesight(&options, &pipeline);
 
-    // handle vector average nodes, stupid annoying ugh
-    for shader in eyesight.all_shaders_mut() {
-        implement_vector_average(shader);
+    match command {
+        Command::Convert => {
+            no_arguments(&args);
+            check_interfaces(&eyesight, &options);
+            check_polyfills(&eyesight);
+            convert_command(&eyesight, &options);
+        }
+        Command::ListGroups => {
+            no_arguments(&args);
+            let names = eyesight.groups.iter().map(|g| &*g.name);
+            write_output(&options, names.map(|n| format!("{n}\n")).collect());
+        }
+        Command::ListMaterials => {
+            no_arguments(&args);
+            let names = eyesight.materials.iter().map(|m| &*m.name);
+            write_output(&options, names.map(|n| format!("{n}\n")).collect());
+        }
+        Command::Families => {
+            no_arguments(&args);
+            write_output(&options, distill::distill_materials(&eyesight.materials));
+        }
+        Command::Show => {
+            let name = one_argument(&args, "usage: xml2py show <material-or-group>");
+            check_interfaces(&eyesight, &options);
+            show_command(&eyesight, name, &options);
+        }
+        Command::Deps => {
+            let name = match &*args {
+                [] => None,
+                _ => Some(one_argument(
+                    &args,
+                    "usage: xml2py deps [material-or-group]",
+                )),
+            };
+            deps_command(&eyesight, name, &options);
+        }
+        Command::Graph => graph_command(&eyesight, &args, &options),
+        Command::Package => {
+            no_arguments(&args);
+            check_interfaces(&eyesight, &options);
+            check_polyfills(&eyesight);
+            package_command(&eyesight, &options);
+        }
+        Command::ListPasses => unreachable!(),
     }
+}
 
-    add_slope_roughness(&mut eyesight);
+/// Reads and merges the inputs, then runs the enabled passes over the result.
+fn load_eyesight(options: &Options, pipeline: &Pipeline) -> Eyesight {
+    let inputs = options
+        .inputs
+        .iter()
+        .map(|path| {
+            let xml = std::fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", path.display());
+                std::process::exit(1);
+            });
+            let mut eyesight = quick_xml::de::from_str::<Eyesight>(&xml).unwrap_or_else(|e| {
+                eprintln!("{}: {e}", path.display());
+                std::process::exit(1);
+            });
+            span::locate(&mut eyesight, &xml, path);
+            eyesight
+        })
+        .collect();
+    let mut eyesight = merge::merge(inputs, options.on_conflict).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+
+    let runs = pipeline.run(&mut eyesight).unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    for run in &runs {
+        for note in &run.report.notes {
+            eprintln!("warning: {}: {note}", run.name);
+        }
+    }
+    if options.pass_report {
+        eprint!("{}", passes::report(&runs));
+    }
 
-    let mut visited = HashSet::<&str>::new();
-    let mut unvisited = vec!["Solid", "Trans Group Base"];
+    eyesight
+}
 
-    while let Some(name) = unvisited.pop() {
-        if name == "Is Slope" {
-            // TODO: hacks like this should not be necessary
-            continue;
+/// Reports every socket conflict, every group whose interface can't be worked out and
+/// everything that doesn't fit an interface, and stops on errors.
+fn check_interfaces(eyesight: &Eyesight, options: &Options) {
+    let report = groups::check_interfaces(eyesight);
+    for unresolved in &report.unresolved {
+        eprintln!("warning: {unresolved}, so the group is skipped");
+    }
+    for error in &report.errors {
+        eprintln!("error: {error}");
+    }
+    if !report.errors.is_empty() {
+        std::process::exit(1);
+    }
+    match options.on_socket_conflict {
+        OnSocketConflict::Error => {
+            for conflict in &report.conflicts {
+                eprintln!("error: {conflict}");
+            }
+            if !report.conflicts.is_empty() {
+                eprintln!("(--on-socket-conflict most-used goes on with the most used type)");
+                std::process::exit(1);
+            }
         }
-        let group = eyesight.groups.iter().find(|g| g.name == name).unwrap();
-
-        for node in &group.shader.nodes {
-            if let Node::Group(gr) = node {
-                if !visited.contains(&*gr.group_name) {
-                    unvisited.push(&gr.group_name);
-                }
+        OnSocketConflict::MostUsed => {
+            for conflict in &report.conflicts {
+                eprintln!("warning: {conflict}; using {:?}", conflict.chosen);
             }
         }
+    }
+}
 
-        visited.insert(name);
+/// Stops if there are nodes that only the polyfills pass converts, and it was skipped.
+fn check_polyfills(eyesight: &Eyesight) {
+    let unreplaced = polyfill::unreplaced(eyesight);
+    for node in &unreplaced {
+        eprintln!("error: {node}: can't be converted without the polyfills pass");
+    }
+    if !unreplaced.is_empty() {
+        std::process::exit(1);
     }
+}
 
-    // println!("{visited:?}");
+fn no_arguments(args: &[String]) {
+    if let Some(arg) = args.first() {
+        eprintln!("unexpected argument: {arg}\n\n{}", cli::USAGE);
+        std::process::exit(1);
+    }
+}
 
-    let s = codegen::the_big_kahuna(&eyesight, &visited);
+fn one_argument<'a>(args: &'a [String], usage: &str) -> &'a str {
+    match args {
+        [arg] if !arg.starts_with('-') => arg,
+        _ => {
+            eprintln!("{usage}");
+            std::process::exit(1);
+        }
+    }
+}
 
-    let mut child = Command::new("black")
-        .args(["--skip-magic-trailing-comma", "-"])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::inherit())
-        .stderr(Stdio::null())
-        .spawn()
-        .unwrap();
+/// Writes to `--output`, or standard output if there isn't one.
+fn write_output(options: &Options, s: String) {
+    match &options.output {
+        Some(path) => std::fs::write(path, s).unwrap_or_else(|e| {
+            eprintln!("{}: {e}", path.display());
+            std::process::exit(1);
+        }),
+        None => print!("{s}"),
+    }
+}
 
-    child
-        .stdin
-        .as_mut()
-        .unwrap()
-        .write_all(s.as_bytes())
-        .unwrap();
+fn convert_command(eyesight: &Eyesight, options: &Options) {
+    let call_graph = CallGraph::new(eyesight);
+    for root in &options.roots {
+        if !call_graph.is_group(root) && !call_graph.is_material(root) {
+            eprintln!("no material or group named {root:?}");
+            std::process::exit(1);
+        }
+    }
+    let visited = call_graph.reachable(options.roots.iter().map(|r| &**r));
 
-    child.wait().unwrap();
-}
+    // println!("{visited:?}");
+
+    if options.layout == Layout::Split {
+        split_output(eyesight, &visited, options);
+        return;
+    }
 
-fn add_slope_roughness(eyesight: &mut Eyesight) {
-    let normals = eyesight
-        .groups
-        .iter_mut()
-        .find(|g| g.name == "Normal")
-        .unwrap();
-
-    normals.shader.nodes.push(Node::Group(GroupReference {
-        group_name: "Is Slope".into(),
-        inputs_: vec![],
-        outputs: vec![],
-        name: "is_slope".into(),
-    }));
-    normals.shader.nodes.push(Node::MixValue(MixValue {
-        name: "choose_roughness".into(),
-        mix_type: MixType::Mix,
-        use_clamp: true,
-        inputs: vec![NodeInput::new("A", 2.0), NodeInput::new("B", 140.0)],
-    }));
-    normals
-        .shader
-        .links
-        .push(Link::new("is_slope", "0", "choose_roughness", "Factor"));
-    normals
-        .shader
-        .links
-        .push(Link::new("choose_roughness", "0", "rough_surface", "Scale"));
+    match codegen::the_big_kahuna(eyesight, &visited, options.backend, options.blender_version) {
+        Ok(s) => write_output(options, s),
+        Err(mismatches) => {
+            for mismatch in mismatches {
+                eprintln!("error: {mismatch}");
+            }
+            std::process::exit(1);
+        }
+    }
 }
 
-fn implement_vector_average(shader: &mut Shader) {
-    // index-based iteration lets us push to shader.nodes inside the loop body
-    for i in 0..shader.nodes.len() {
-        let node = &mut shader.nodes[i];
-        let Node::VectorMath(vector_math) = node else {
-            continue;
-        };
+fn split_output(eyesight: &Eyesight, groups: &BTreeSet<&str>, options: &Options) {
+    if options.backend != codegen::Backend::NodeDsl {
+        eprintln!("the split output layout only works with the node-dsl backend");
+        std::process::exit(1);
+    }
+    let Some(dir) = &options.output else {
+        eprintln!("the split output layout needs an output directory");
+        std::process::exit(1);
+    };
+
+    let files = match codegen::split_modules(eyesight, groups, options.blender_version) {
+        Ok(files) => files,
+        Err(mismatches) => {
+            for mismatch in mismatches {
+                eprintln!("error: {mismatch}");
+            }
+            std::process::exit(1);
+        }
+    };
+
+    std::fs::create_dir_all(dir)
+        .and_then(|_| {
+            files
+                .iter()
+                .try_for_each(|(name, contents)| std::fs::write(dir.join(name), contents))
+        })
+        .unwrap_or_else(|e| {
+            eprintln!("{}: {e}", dir.display());
+            std::process::exit(1);
+        });
+}
 
-        if vector_math.operation != VectorOperation::Average {
-            continue;
-        };
+fn show_command(eyesight: &Eyesight, name: &str, options: &Options) {
+    let group = eyesight.groups.iter().find(|g| g.name == name);
+    let material = eyesight.materials.iter().find(|m| m.name == name);
+    let shader = match (group, material) {
+        (Some(group), _) => &group.shader,
+        (None, Some(material)) => &material.shader,
+        (None, None) => {
+            eprintln!("no material or group named {name:?}");
+            std::process::exit(1);
+        }
+    };
+
+    let mut s = String::new();
+    let kind = if group.is_some() { "group" } else { "material" };
+    let span = group.map_or_else(|| &material.unwrap().span, |g| &g.span);
+    s += &format!("{kind} {name}\nfrom {span}\n");
+
+    if group.is_some() {
+        let interfaces = groups::check_interfaces(eyesight).interfaces;
+        if let Some(interface) = interfaces.get(name) {
+            let sockets = [
+                ("inputs", &interface.inputs),
+                ("outputs", &interface.outputs),
+            ];
+            for (heading, sockets) in sockets {
+                s += &format!("\n{heading}:\n");
+                for (socket, data_type) in sockets {
+                    s += &format!("  {socket}: {data_type:?}");
+                    if let Some(details) = interface.details.get(socket) {
+                        if let Some(default) = details.default {
+                            s += &format!(" = {default}");
+                        }
+                        if let Some((min, max)) = details.range {
+                            s += &format!(", {min} to {max}");
+                        }
+                        if let Some(description) = details.description() {
+                            s += &format!(" ({description})");
+                        }
+                    }
+                    s += "\n";
+                }
+            }
+        }
+    }
 
-        let name = node.name().to_owned();
-        let inputs = vec![];
+    s += "\nnodes:\n";
+    for node in &shader.nodes {
+        s += &format!(
+            "  {} ({}){}\n",
+            node.name(),
+            node.kind().to_snake_case(),
+            at(node.span())
+        );
+    }
 
-        *node = Node::MixVector(MixVector { name, inputs });
+    s += "\nlinks:\n";
+    for link in &shader.links {
+        s += &format!(
+            "  {}.{} -> {}.{}{}\n",
+            link.from_node,
+            link.from_socket,
+            link.to_node,
+            link.to_socket,
+            at(&link.span)
+        );
     }
+
+    write_output(options, s);
 }
 
-fn beautify_names(eyesight: &mut Eyesight) {
-    for group in &mut eyesight.groups {
-        let pascal_name = group.name.to_pascal_case();
+/// Where something in a listing was defined, if it was read from a file.
+fn at(span: &Span) -> String {
+    match span.location {
+        Some(_) => format!(" at {span}"),
+        None => String::new(),
+    }
+}
 
-        for node in &mut group.shader.nodes {
-            beautify_node_name(node.name_mut(), &group.name, &pascal_name);
-            if let Node::Group(g) = node {
-                beautify_group_name(&mut g.group_name);
-            }
+fn deps_command(eyesight: &Eyesight, name: Option<&str>, options: &Options) {
+    fn visit(
+        call_graph: &CallGraph,
+        name: &str,
+        depth: usize,
+        seen: &mut BTreeSet<String>,
+        s: &mut String,
+    ) {
+        let indent = "  ".repeat(depth);
+        if !seen.insert(name.to_owned()) {
+            *s += &format!("{indent}{name} (see above)\n");
+            return;
+        }
+        match custom_groups::get(name) {
+            Some(custom) => *s += &format!("{indent}{name} (custom, in {})\n", custom.module),
+            None => *s += &format!("{indent}{name}\n"),
         }
 
-        for link in &mut group.shader.links {
-            beautify_node_name(&mut link.from_node, &group.name, &pascal_name);
-            beautify_node_name(&mut link.to_node, &group.name, &pascal_name);
+        for gr in call_graph.callees(name) {
+            visit(call_graph, gr, depth + 1, seen, s);
         }
+    }
+
+    let call_graph = CallGraph::new(eyesight);
+
+    let Some(name) = name else {
+        write_output(options, call_graph.report());
+        return;
+    };
 
-        beautify_group_name(&mut group.name);
+    if !call_graph.is_group(name) && !call_graph.is_material(name) {
+        eprintln!("no material or group named {name:?}");
+        std::process::exit(1);
     }
 
-    for material in &mut eyesight.materials {
-        for node in &mut material.shader.nodes {
-            if let Node::Group(g) = node {
-                beautify_group_name(&mut g.group_name);
-            }
-        }
+    let mut s = String::new();
+    visit(&call_graph, name, 0, &mut BTreeSet::new(), &mut s);
 
-        beautify_material_name(&mut material.name)
+    if call_graph.is_group(name) {
+        let users = call_graph.users(name);
+        s += "\nused by:\n";
+        for user in users.groups.iter().chain(&users.materials) {
+            s += &format!("  {user}\n");
+        }
+        s += "\nmaterials using it, directly or not:\n";
+        for material in call_graph.materials_using(name) {
+            s += &format!("  {material}\n");
+        }
     }
-}
 
-fn beautify_node_name(name: &mut String, original_group_name: &str, pascal_group_name: &str) {
-    *name = name
-        .replace("Anitique", "Antique")
-        .replace("Anique", "Antique")
-        .replace("Ghrome", "Chrome")
-        .trim_start_matches(original_group_name)
-        .trim_start_matches(pascal_group_name)
-        .to_snake_case();
+    write_output(options, s);
 }
 
-fn beautify_group_name(name: &mut String) {
-    *name = name
-        .trim_end_matches("-GROUP")
-        .trim_end_matches("Group")
-        .to_title_case()
-}
+fn list_passes_command(pipeline: &Pipeline, options: &Options) {
+    let mut s = String::new();
+    for (pass, runs) in pipeline.passes() {
+        let status = if runs { "runs" } else { "skipped" };
+        s += &format!("{} ({status})\n  {}\n", pass.name(), pass.description());
+        if !pass.after().is_empty() {
+            s += &format!("  after: {}\n", pass.after().join(", "));
+        }
+        for condition in pass.preconditions() {
+            s += &format!("  requires: {}\n", condition.description);
+        }
+        for condition in pass.postconditions() {
+            s += &format!("  ensures: {}\n", condition.description);
+        }
+    }
 
-fn beautify_material_name(name: &mut String) {
-    *name = name
-        .to_title_case()
-        .replace("Trans Trans", "Trans")
-        .replace("Trans ", "Trans-")
+    s += "\npolyfills:\n";
+    for polyfill in polyfill::POLYFILLS {
+        let conversion = match polyfill.conversion {
+            Conversion::Replace(_) => "replaced by the polyfills pass".to_owned(),
+            Conversion::CustomGroup(group) => format!("a call to the {group} custom group"),
+            Conversion::Attributes => "set up from its attributes".to_owned(),
+        };
+        s += &format!(
+            "  {} ({conversion})\n    {}\n",
+            polyfill.node_kind, polyfill.lowering
+        );
+    }
+    write_output(options, s);
 }
 
-fn merge<T: Named + PartialEq + std::fmt::Debug>(a: Vec<T>, b: Vec<T>) -> Vec<T> {
-    let mut map = a
-        .into_iter()
-        .map(|x| (x.name().to_owned(), x))
-        .collect::<BTreeMap<_, _>>();
+fn package_command(eyesight: &Eyesight, options: &Options) {
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| "studio_materials.zip".into());
 
-    for x in b {
-        if let Some(conflict) = map.get(x.name()) {
-            assert_eq!(*conflict, x);
-        } else {
-            map.insert(x.name().to_owned(), x);
-        }
+    if eyesight.version.is_none() {
+        eprintln!("warning: the XML doesn't say which Studio version it's from; using 0.0.0");
     }
 
-    map.into_values().collect()
+    let groups = CallGraph::new(eyesight).reachable(eyesight.materials.iter().map(|m| &*m.name));
+
+    let files = match package::addon_files(eyesight, &groups, options.blender_version) {
+        Ok(files) => files,
+        Err(mismatches) => {
+            for mismatch in mismatches {
+                eprintln!("error: {mismatch}");
+            }
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(e) = package::write_zip(&output, &files) {
+        eprintln!("{}: {e}", output.display());
+        std::process::exit(1);
+    }
 }
 
-fn merge_eyesight(a: Eyesight, b: Eyesight) -> Eyesight {
-    Eyesight {
-        materials: merge(a.materials, b.materials),
-        groups: merge(a.groups, b.groups),
+const GRAPH_USAGE: &str = "usage: xml2py graph <material-or-group> [--dot | --mermaid] [--aliases] [--expand] [--blender-version <version>]";
+
+fn graph_command(eyesight: &Eyesight, args: &[String], options: &Options) {
+    let mut graph_options = GraphOptions {
+        format: GraphFormat::Dot,
+        show_aliases: false,
+        expand_groups: false,
+        blender_version: options.blender_version,
+    };
+    let mut name = None;
+
+    for arg in args {
+        match &**arg {
+            "--dot" => graph_options.format = GraphFormat::Dot,
+            "--mermaid" => graph_options.format = GraphFormat::Mermaid,
+            "--aliases" => graph_options.show_aliases = true,
+            "--expand" => graph_options.expand_groups = true,
+            _ if name.is_none() && !arg.starts_with("--") => name = Some(arg),
+            _ => {
+                eprintln!("unexpected argument: {arg}\n\n{GRAPH_USAGE}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let Some(name) = name else {
+        eprintln!("{GRAPH_USAGE}");
+        std::process::exit(1);
+    };
+
+    match visualize::render_graph(eyesight, name, &graph_options) {
+        Some(s) => write_output(options, s),
+        None => {
+            eprintln!("no material or group named {name:?}");
+            std::process::exit(1);
+        }
     }
 }
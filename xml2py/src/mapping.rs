@@ -0,0 +1,350 @@
+//! The Eyesight-to-Blender mapping: node types, socket types, enum values, socket aliases
+//! and group socket order.
+//!
+//! The mapping lives in a TOML file so it can be fixed without recompiling.
+//! `mapping.toml` next to this file is the built-in default and documents the layout.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use heck::ToSnakeCase;
+use serde_derive::Deserialize;
+
+use crate::blender::{self, BlenderVersion};
+use eyesight_xml::nodes::{Node, SocketType, ENUMS};
+
+/// The layout version this build understands.
+const FORMAT: u32 = 1;
+
+const BUILTIN: &str = include_str!("mapping.toml");
+
+const VERSIONS: [BlenderVersion; 3] = [
+    BlenderVersion::V3_6,
+    BlenderVersion::V4_0,
+    BlenderVersion::V4_2,
+];
+
+type Table = BTreeMap<String, String>;
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Mapping {
+    format: u32,
+    node_types: Table,
+    data_types: Table,
+    #[serde(default)]
+    enums: BTreeMap<String, Table>,
+    #[serde(default)]
+    inputs: BTreeMap<String, Table>,
+    #[serde(default)]
+    outputs: BTreeMap<String, Table>,
+    #[serde(default)]
+    blender: BTreeMap<String, Overrides>,
+    #[serde(default)]
+    interfaces: BTreeMap<String, SocketOrder>,
+}
+
+/// Differences for a single Blender release.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct Overrides {
+    #[serde(default)]
+    node_types: Table,
+    #[serde(default)]
+    inputs: BTreeMap<String, Table>,
+    #[serde(default)]
+    outputs: BTreeMap<String, Table>,
+}
+
+/// The order of a group's interface sockets, by name. Sockets that aren't listed follow
+/// in the order they were discovered.
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct SocketOrder {
+    #[serde(default)]
+    pub inputs: Vec<String>,
+    #[serde(default)]
+    pub outputs: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct MappingError(pub Vec<String>);
+
+impl std::fmt::Display for MappingError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "invalid mapping:")?;
+        for problem in &self.0 {
+            writeln!(f, "  {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MappingError {}
+
+static MAPPING: OnceLock<Mapping> = OnceLock::new();
+
+/// Makes `mapping` the one returned by `get`. Must be called before the first `get`.
+pub fn init(mapping: Mapping) {
+    mapping.register_enums();
+    MAPPING
+        .set(mapping)
+        .expect("the mapping was already initialised");
+}
+
+/// The mapping in use, which is the built-in one unless `init` said otherwise.
+pub fn get() -> &'static Mapping {
+    MAPPING.get_or_init(|| {
+        let mapping = Mapping::builtin();
+        mapping.register_enums();
+        mapping
+    })
+}
+
+impl Mapping {
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN).expect("the built-in mapping is invalid")
+    }
+
+    pub fn load(path: &std::path::Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let s = std::fs::read_to_string(path)?;
+        Self::parse(&s)
+    }
+
+    pub fn parse(s: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mapping = toml::from_str::<Self>(s)?;
+        mapping.validate()?;
+        Ok(mapping)
+    }
+
+    fn register_enums(&self) {
+        let translations = self
+            .enums
+            .iter()
+            .flat_map(|(name, table)| {
+                table.iter().map(|(value, translation)| {
+                    ((name.clone(), value.clone()), translation.clone())
+                })
+            })
+            .collect::<HashMap<_, _>>();
+        eyesight_xml::nodes::set_enum_translations(translations);
+    }
+
+    fn overrides(&self, version: BlenderVersion) -> Option<&Overrides> {
+        self.blender.get(&version.to_string())
+    }
+
+    /// The Blender node type for an Eyesight node.
+    pub fn node_type(&self, node: &Node, version: BlenderVersion) -> &str {
+        let kind = node.kind().to_snake_case();
+        self.overrides(version)
+            .and_then(|o| o.node_types.get(&kind))
+            .or_else(|| self.node_types.get(&kind))
+            .unwrap()
+    }
+
+    pub fn data_type(&self, data_type: SocketType) -> &str {
+        &self.data_types[&format!("{data_type:?}").to_snake_case()]
+    }
+
+    pub fn input_alias(
+        &self,
+        version: BlenderVersion,
+        node_type: &str,
+        socket: &str,
+    ) -> Option<&str> {
+        let table = match self
+            .overrides(version)
+            .and_then(|o| o.inputs.get(node_type))
+        {
+            Some(table) => table,
+            None => self.inputs.get(node_type)?,
+        };
+        table.get(socket).map(|s| &**s)
+    }
+
+    pub fn output_alias(
+        &self,
+        version: BlenderVersion,
+        node_type: &str,
+        socket: &str,
+    ) -> Option<&str> {
+        let table = match self
+            .overrides(version)
+            .and_then(|o| o.outputs.get(node_type))
+        {
+            Some(table) => table,
+            None => self.outputs.get(node_type)?,
+        };
+        table.get(socket).map(|s| &**s)
+    }
+
+    /// The socket order for a group, by its beautified name.
+    pub fn socket_order(&self, group: &str) -> Option<&SocketOrder> {
+        self.interfaces.get(group)
+    }
+
+    fn validate(&self) -> Result<(), MappingError> {
+        let mut problems = vec![];
+
+        if self.format != FORMAT {
+            problems.push(format!(
+                "format {} is not supported (expected {FORMAT})",
+                self.format
+            ));
+        }
+
+        for key in self.blender.keys() {
+            if !VERSIONS.iter().any(|v| v.to_string() == *key) {
+                problems.push(format!(
+                    "[blender.{key:?}]: not one of {}",
+                    VERSIONS.map(|v| format!("{:?}", v.to_string())).join(", ")
+                ));
+            }
+        }
+
+        let kinds = Node::KINDS
+            .iter()
+            .map(|k| k.to_snake_case())
+            .collect::<Vec<_>>();
+        for kind in &kinds {
+            if !self.node_types.contains_key(kind) {
+                problems.push(format!("[node_types]: missing {kind}"));
+            }
+        }
+        for version in VERSIONS {
+            let overrides = self.overrides(version);
+            let mut node_types = self.node_types.clone();
+            if let Some(o) = overrides {
+                node_types.extend(o.node_types.clone());
+            }
+            for (kind, node_type) in &node_types {
+                if !kinds.contains(kind) {
+                    problems.push(format!("[node_types]: unknown Eyesight node {kind}"));
+                } else if version.sockets(node_type).is_none() {
+                    problems.push(format!(
+                        "[node_types]: {kind} = {node_type:?}, which Blender {version} doesn't have"
+                    ));
+                }
+            }
+        }
+
+        let socket_types = enum_values("SocketType");
+        for data_type in &socket_types {
+            if !self.data_types.contains_key(data_type) {
+                problems.push(format!("[data_types]: missing {data_type}"));
+            }
+        }
+        for (data_type, socket_type) in &self.data_types {
+            if !socket_types.contains(data_type) {
+                problems.push(format!("[data_types]: unknown socket type {data_type}"));
+            }
+            if !socket_type.starts_with("NodeSocket") {
+                problems.push(format!(
+                    "[data_types]: {data_type} = {socket_type:?} is not a NodeSocket type"
+                ));
+            }
+        }
+
+        for (name, table) in &self.enums {
+            if !ENUMS.iter().any(|(e, _)| e == name) {
+                problems.push(format!("[enums.{name}]: unknown enum"));
+                continue;
+            }
+            let values = enum_values(name);
+            for (value, translation) in table {
+                if !values.contains(value) {
+                    problems.push(format!("[enums.{name}]: unknown value {value}"));
+                }
+                let is_identifier = !translation.is_empty()
+                    && translation
+                        .chars()
+                        .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_');
+                if !is_identifier {
+                    problems.push(format!(
+                        "[enums.{name}]: {value} = {translation:?} is not a Blender enum identifier"
+                    ));
+                }
+            }
+        }
+
+        for (section, is_input) in [("inputs", true), ("outputs", false)] {
+            let base = if is_input {
+                &self.inputs
+            } else {
+                &self.outputs
+            };
+
+            let mut node_types = base.keys().collect::<Vec<_>>();
+            for o in self.blender.values() {
+                node_types.extend(if is_input {
+                    o.inputs.keys()
+                } else {
+                    o.outputs.keys()
+                });
+            }
+
+            for node_type in node_types {
+                if VERSIONS.iter().all(|v| v.sockets(node_type).is_none()) {
+                    problems.push(format!("[{section}.{node_type}]: unknown node type"));
+                }
+            }
+
+            for version in VERSIONS {
+                let overrides =
+                    self.overrides(version)
+                        .map(|o| if is_input { &o.inputs } else { &o.outputs });
+                let mut tables = base.clone();
+                if let Some(overrides) = overrides {
+                    tables.extend(overrides.clone());
+                }
+                for (node_type, table) in &tables {
+                    let Some(sockets) = version.sockets(node_type) else {
+                        continue;
+                    };
+                    for (alias, socket) in table {
+                        let typed = blender::DATA_TYPES
+                            .iter()
+                            .filter_map(|d| blender::typed_socket(node_type, socket, d))
+                            .any(|identifier| sockets.has(&identifier, is_input));
+                        if !typed && !sockets.has(socket, is_input) {
+                            problems.push(format!(
+                                "[{section}.{node_type}]: {alias} = {socket:?}, \
+                                 which Blender {version} doesn't have"
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        for (group, order) in &self.interfaces {
+            for (kind, names) in [("inputs", &order.inputs), ("outputs", &order.outputs)] {
+                for (i, name) in names.iter().enumerate() {
+                    if names[..i].contains(name) {
+                        problems.push(format!(
+                            "[interfaces.{group:?}]: {kind} lists {name:?} twice"
+                        ));
+                    }
+                }
+            }
+        }
+
+        problems.sort();
+        problems.dedup();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(MappingError(problems))
+        }
+    }
+}
+
+/// The XML spelling of an enum's values.
+fn enum_values(name: &str) -> Vec<String> {
+    ENUMS
+        .iter()
+        .find(|(e, _)| *e == name)
+        .map(|(_, variants)| variants.iter().map(|v| v.to_snake_case()).collect())
+        .unwrap_or_default()
+}
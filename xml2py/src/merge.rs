@@ -0,0 +1,360 @@
+//! Combining several Eyesight files into one, like `settings.xml` with
+//! `CustomColorSettings.xml`, or a color pack on top of both.
+//!
+//! Groups and materials are merged by name. Identical definitions become one;
+//! what happens to different ones is up to the [`OnConflict`] policy.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use eyesight_xml::nodes::Node;
+use eyesight_xml::schema::{Eyesight, Group, Material, Shader};
+use eyesight_xml::span::Span;
+use eyesight_xml::{Named, Spanned};
+use heck::ToSnakeCase;
+
+use crate::config::OnConflict;
+
+/// A group or material, as far as merging is concerned.
+trait Definition: Named + PartialEq {
+    const KIND: &str;
+    fn span(&self) -> &Span;
+    /// How `other` differs from `self`, a line per difference.
+    fn diff(&self, other: &Self) -> Vec<String>;
+}
+
+impl Definition for Group {
+    const KIND: &str = "group";
+
+    fn span(&self) -> &Span {
+        &self.span
+    }
+
+    fn diff(&self, other: &Self) -> Vec<String> {
+        shader_diff(&self.shader, &other.shader)
+    }
+}
+
+impl Definition for Material {
+    const KIND: &str = "material";
+
+    fn span(&self) -> &Span {
+        &self.span
+    }
+
+    fn diff(&self, other: &Self) -> Vec<String> {
+        let mut diff = shader_diff(&self.shader, &other.shader);
+        if diff.is_empty() {
+            diff.push("~ material settings".to_owned());
+        }
+        diff
+    }
+}
+
+/// `-` for what's only in `a`, `+` for what's only in `b`, and `~` for nodes in both that differ,
+/// with where each was defined.
+pub fn shader_diff(a: &Shader, b: &Shader) -> Vec<String> {
+    let mut diff = vec![];
+
+    let nodes = |shader: &Shader| {
+        shader
+            .nodes
+            .iter()
+            .map(|n| (n.name().to_owned(), n.clone()))
+            .collect::<BTreeMap<_, _>>()
+    };
+    let (a_nodes, b_nodes) = (nodes(a), nodes(b));
+    let describe = |node: &Node| format!("{} ({})", node.name(), node.kind().to_snake_case());
+    for (name, node) in &a_nodes {
+        match b_nodes.get(name) {
+            None => diff.push(format!("- node {} at {}", describe(node), node.span())),
+            Some(other) if other != node => diff.push(format!(
+                "~ node {} at {} and {}",
+                describe(node),
+                node.span(),
+                other.span()
+            )),
+            Some(_) => {}
+        }
+    }
+    for (name, node) in &b_nodes {
+        if !a_nodes.contains_key(name) {
+            diff.push(format!("+ node {} at {}", describe(node), node.span()));
+        }
+    }
+
+    fn links(shader: &Shader) -> BTreeMap<String, &Span> {
+        shader
+            .links
+            .iter()
+            .map(|l| {
+                let link = format!(
+                    "{}.{} -> {}.{}",
+                    l.from_node, l.from_socket, l.to_node, l.to_socket
+                );
+                (link, &l.span)
+            })
+            .collect()
+    }
+    let (a_links, b_links) = (links(a), links(b));
+    for (link, span) in &a_links {
+        if !b_links.contains_key(link) {
+            diff.push(format!("- link {link} at {span}"));
+        }
+    }
+    for (link, span) in &b_links {
+        if !a_links.contains_key(link) {
+            diff.push(format!("+ link {link} at {span}"));
+        }
+    }
+
+    diff
+}
+
+/// Merges the inputs, earlier ones first. Under [`OnConflict::Error`], every conflict
+/// is reported at once.
+pub fn merge(inputs: Vec<Eyesight>, on_conflict: OnConflict) -> Result<Eyesight, String> {
+    let mut merged = Eyesight {
+        version: None,
+        materials: vec![],
+        groups: vec![],
+    };
+    let mut errors = vec![];
+
+    for mut next in inputs {
+        merged.version = merged.version.or(next.version.take());
+        if on_conflict == OnConflict::Rename {
+            rename_conflicts(&merged, &mut next);
+        }
+        merge_definitions(&mut merged.groups, next.groups, on_conflict, &mut errors);
+        merge_definitions(
+            &mut merged.materials,
+            next.materials,
+            on_conflict,
+            &mut errors,
+        );
+    }
+
+    if !errors.is_empty() {
+        return Err(errors.join("\n"));
+    }
+
+    merged.groups.sort_by(|a, b| a.name.cmp(&b.name));
+    merged.materials.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(merged)
+}
+
+fn merge_definitions<T: Definition>(
+    merged: &mut Vec<T>,
+    next: Vec<T>,
+    on_conflict: OnConflict,
+    errors: &mut Vec<String>,
+) {
+    for definition in next {
+        let Some(existing) = merged.iter_mut().find(|d| d.name() == definition.name()) else {
+            merged.push(definition);
+            continue;
+        };
+        if *existing == definition {
+            continue;
+        }
+
+        match on_conflict {
+            OnConflict::Error => {
+                let mut error = format!(
+                    "{} {:?} is defined differently in {} and {}:",
+                    T::KIND,
+                    definition.name(),
+                    existing.span(),
+                    definition.span()
+                );
+                for line in existing.diff(&definition) {
+                    error += &format!("\n  {line}");
+                }
+                errors.push(error);
+            }
+            OnConflict::First => {}
+            OnConflict::Last => *existing = definition,
+            OnConflict::Rename => unreachable!("conflicts are renamed before merging"),
+        }
+    }
+}
+
+/// Renames each of `next`'s groups and materials that conflicts with one already merged,
+/// after the file it's from, along with the group nodes in `next` that use it.
+fn rename_conflicts(merged: &Eyesight, next: &mut Eyesight) {
+    fn rename<T: Definition>(merged: &[T], next: &mut [T]) -> BTreeMap<String, String> {
+        let mut taken = merged
+            .iter()
+            .chain(&*next)
+            .map(|d| d.name().to_owned())
+            .collect::<BTreeSet<_>>();
+        let mut renamed = BTreeMap::new();
+
+        for definition in next {
+            let conflicts = merged
+                .iter()
+                .any(|d| d.name() == definition.name() && d != definition);
+            if !conflicts {
+                continue;
+            }
+
+            let stem = definition
+                .span()
+                .file
+                .path()
+                .and_then(|p| p.file_stem())
+                .map_or("renamed".into(), |s| s.to_string_lossy());
+            let base = format!("{} ({stem})", definition.name());
+            let new = (1..)
+                .map(|n| match n {
+                    1 => base.clone(),
+                    _ => format!("{base} {n}"),
+                })
+                .find(|name| !taken.contains(name))
+                .unwrap();
+
+            taken.insert(new.clone());
+            renamed.insert(definition.name().to_owned(), new.clone());
+            *definition.name_mut() = new;
+        }
+
+        renamed
+    }
+
+    // Using a renamed group makes a definition differ from the one already merged,
+    // so keep renaming until nothing new conflicts.
+    loop {
+        let groups = rename(&merged.groups, &mut next.groups);
+        if groups.is_empty() {
+            break;
+        }
+        for shader in next.all_shaders_mut() {
+            for node in &mut shader.nodes {
+                if let Node::Group(g) = node {
+                    if let Some(new) = groups.get(&g.group_name) {
+                        g.group_name = new.clone();
+                    }
+                }
+            }
+        }
+    }
+    rename(&merged.materials, &mut next.materials);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use eyesight_xml::span;
+
+    use super::*;
+
+    fn group(name: &str, nodes: &str) -> String {
+        format!(
+            r#"<group name="{name}"><shader><group_output name="out"/>{nodes}<connect from_node="v" from_socket="Value" to_node="out" to_socket="Value"/></shader></group>"#
+        )
+    }
+
+    fn material(name: &str, group: &str) -> String {
+        format!(
+            r#"<material name="{name}" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance"><shader><group name="call" group_name="{group}"/><connect from_node="call" from_socket="BSDF" to_node="output" to_socket="Surface"/></shader></material>"#
+        )
+    }
+
+    fn file(path: &str, version: &str, elements: &[String]) -> Eyesight {
+        let xml = format!("<eyesight{version}>\n{}\n</eyesight>", elements.join("\n"));
+        let mut eyesight = quick_xml::de::from_str::<Eyesight>(&xml).unwrap();
+        span::locate(&mut eyesight, &xml, Path::new(path));
+        eyesight
+    }
+
+    fn inputs() -> Vec<Eyesight> {
+        vec![
+            file(
+                "base.xml",
+                r#" version="1""#,
+                &[
+                    group("G", r#"<value name="v" value="1"/>"#),
+                    group("H", r#"<value name="v" value="1"/>"#),
+                    group(
+                        "K",
+                        r#"<value name="v" value="1"/><group name="call" group_name="G"/>"#,
+                    ),
+                    material("M", "G"),
+                ],
+            ),
+            file(
+                "pack.xml",
+                r#" version="2""#,
+                &[
+                    group(
+                        "G",
+                        r#"<value name="v" value="2"/><value name="w" value="0"/>"#,
+                    ),
+                    group("H", r#"<value name="v" value="1"/>"#),
+                    group(
+                        "K",
+                        r#"<value name="v" value="1"/><group name="call" group_name="G"/>"#,
+                    ),
+                    material("M", "G"),
+                    material("A", "G"),
+                ],
+            ),
+        ]
+    }
+
+    fn names<T: Named>(definitions: &[T]) -> Vec<&str> {
+        definitions.iter().map(|d| d.name()).collect()
+    }
+
+    fn value(group: &Group) -> f32 {
+        match &group.shader.nodes[1] {
+            Node::Value(v) => v.value,
+            node => panic!("unexpected {node:?}"),
+        }
+    }
+
+    #[test]
+    fn conflicts_are_reported_with_a_diff() {
+        assert_eq!(
+            merge(inputs(), OnConflict::Error).unwrap_err(),
+            "group \"G\" is defined differently in base.xml:2 and pack.xml:2:\n  \
+             ~ node v (value) at base.xml:2 and pack.xml:2\n  \
+             + node w (value) at pack.xml:2"
+        );
+    }
+
+    #[test]
+    fn first_and_last_pick_a_definition() {
+        let first = merge(inputs(), OnConflict::First).unwrap();
+        assert_eq!(first.version.as_deref(), Some("1"));
+        assert_eq!(names(&first.groups), ["G", "H", "K"]);
+        assert_eq!(names(&first.materials), ["A", "M"]);
+        assert_eq!(value(&first.groups[0]), 1.0);
+
+        let last = merge(inputs(), OnConflict::Last).unwrap();
+        assert_eq!(value(&last.groups[0]), 2.0);
+        assert_eq!(last.groups[0].shader.nodes.len(), 3);
+    }
+
+    #[test]
+    fn renamed_conflicts_keep_their_users() {
+        let merged = merge(inputs(), OnConflict::Rename).unwrap();
+        assert_eq!(
+            names(&merged.groups),
+            ["G", "G (pack)", "H", "K", "K (pack)"]
+        );
+        // K and M now differ from the first file's, as they use the renamed group.
+        assert_eq!(names(&merged.materials), ["A", "M", "M (pack)"]);
+
+        let group_names = merged
+            .materials
+            .iter()
+            .map(|m| match &m.shader.nodes[0] {
+                Node::Group(g) => &*g.group_name,
+                node => panic!("unexpected {node:?}"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(group_names, ["G (pack)", "G", "G (pack)"]);
+    }
+}
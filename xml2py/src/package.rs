@@ -0,0 +1,259 @@
+//! `xml2py package`: the converted groups and materials as an installable Blender add-on.
+//!
+//! The zip holds a single `studio_materials` package:
+//!
+//! - `__init__.py` with `bl_info`, an "Add Studio Material by Color" operator and its menu entries
+//! - `groups.py` and `materials.py`, generated with the `bpy` backend
+//! - `helpers.py` and `custom_nodes.py`, the modules the generated code imports
+//!
+//! `node_dsl` isn't included; the add-on doesn't need it.
+
+use std::collections::BTreeSet;
+use std::io::Write;
+use std::path::Path;
+
+use eyesight_xml::schema::Eyesight;
+use heck::ToTitleCase;
+
+use crate::blender::{BlenderVersion, SocketMismatch};
+use crate::codegen::{self, Backend};
+use crate::custom_groups;
+use crate::identifiers::FunctionNames;
+use crate::python::{Expr, Module, Stmt};
+
+const PACKAGE_NAME: &str = "studio_materials";
+
+/// The files of the add-on, as paths inside the zip and their contents.
+pub fn addon_files(
+    eyesight: &Eyesight,
+    groups_to_convert: &BTreeSet<&str>,
+    version: BlenderVersion,
+) -> Result<Vec<(String, String)>, Vec<SocketMismatch>> {
+    let groups = codegen::group_functions(eyesight, groups_to_convert, Backend::Bpy, version)?;
+
+    // Materials that use a group we aren't converting would fail when they're added.
+    let materials = eyesight
+        .materials
+        .iter()
+        .filter(|m| {
+            m.shader.nodes.iter().all(|node| match node {
+                eyesight_xml::nodes::Node::Group(g) => groups_to_convert.contains(&*g.group_name),
+                _ => true,
+            })
+        })
+        .collect::<Vec<_>>();
+    let function_names = FunctionNames::new(eyesight);
+    let material_functions = codegen::material_functions(&materials, &function_names, version)?;
+
+    let group_names = groups.iter().map(|f| f.name.clone()).collect::<Vec<_>>();
+
+    let mut groups_module = Module::default();
+    let mut imports = vec![
+        "import bpy".to_owned(),
+        "from .helpers import load_image".to_owned(),
+    ];
+    imports.extend(custom_groups::import_lines());
+    groups_module.body.push(Stmt::Raw(imports));
+    groups_module.body.extend(groups.into_iter().map(Stmt::Def));
+
+    let mut materials_module = Module::default();
+    let mut imports = vec!["import bpy".to_owned()];
+    imports.extend(custom_groups::import_lines());
+    if !group_names.is_empty() {
+        imports.push("from .groups import (".to_owned());
+        imports.extend(group_names.iter().map(|name| format!("    {name},")));
+        imports.push(")".to_owned());
+    }
+    materials_module.body.push(Stmt::Raw(imports));
+    let registry = materials
+        .iter()
+        .map(|m| {
+            let function_name = function_names.material(&m.name);
+            (Expr::str(&m.name), Expr::name(function_name))
+        })
+        .collect();
+    materials_module
+        .body
+        .extend(material_functions.into_iter().map(Stmt::Def));
+    materials_module.body.push(Stmt::Raw(vec![
+        "# Every material in the add-on, by Studio color name.".to_owned(),
+    ]));
+    materials_module
+        .body
+        .push(Stmt::Assign(Expr::name("MATERIALS"), Expr::Dict(registry)));
+
+    let mut init = Module::default();
+    init.body.push(Stmt::Assign(
+        Expr::name("bl_info"),
+        bl_info(eyesight.version.as_deref(), version),
+    ));
+    init.body.push(Stmt::Blank);
+    init.body
+        .push(raw(&["import bpy", "from .materials import MATERIALS"]));
+    init.body.push(Stmt::Raw(
+        include_str!("addon.py").lines().map(String::from).collect(),
+    ));
+
+    let helpers = format!("import bpy\nimport os.path\n{}", include_str!("helpers.py"));
+    let custom_nodes = format!("import bpy\n\n{}", include_str!("custom_nodes.py"));
+
+    Ok([
+        ("__init__.py", init.print()),
+        ("groups.py", groups_module.print()),
+        ("materials.py", materials_module.print()),
+        ("helpers.py", helpers),
+        ("custom_nodes.py", custom_nodes),
+    ]
+    .into_iter()
+    .map(|(file, contents)| (format!("{PACKAGE_NAME}/{file}"), contents))
+    .collect())
+}
+
+pub fn write_zip(path: &Path, files: &[(String, String)]) -> zip::result::ZipResult<()> {
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let options = zip::write::SimpleFileOptions::default();
+    for (name, contents) in files {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(contents.as_bytes())?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+/// The leading numbers of a Studio version such as `2.2.14_2`, as an add-on version.
+fn addon_version(studio_version: Option<&str>) -> (u32, u32, u32) {
+    let mut parts = studio_version
+        .unwrap_or_default()
+        .split(|c: char| !c.is_ascii_digit())
+        .map_while(|part| part.parse().ok());
+    (
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+        parts.next().unwrap_or(0),
+    )
+}
+
+fn bl_info(studio_version: Option<&str>, version: BlenderVersion) -> Expr {
+    let (major, minor, patch) = addon_version(studio_version);
+    let int_tuple =
+        |parts: &[u32]| Expr::Tuple(parts.iter().map(|&n| Expr::Int(n.into())).collect());
+    let blender = version
+        .to_string()
+        .split('.')
+        .map(|n| n.parse().unwrap())
+        .chain([0])
+        .collect::<Vec<u32>>();
+
+    let description = match studio_version {
+        Some(v) => format!("Materials from BrickLink Studio {v}"),
+        None => "Materials from BrickLink Studio".to_owned(),
+    };
+
+    let entries = [
+        ("name", Expr::str(PACKAGE_NAME.to_title_case())),
+        ("author", Expr::str("xml2py")),
+        ("version", int_tuple(&[major, minor, patch])),
+        ("blender", int_tuple(&blender)),
+        (
+            "location",
+            Expr::str("3D Viewport > Object > Add Studio Material by Color"),
+        ),
+        ("description", Expr::str(description)),
+        ("category", Expr::str("Material")),
+    ];
+
+    Expr::Dict(
+        entries
+            .into_iter()
+            .map(|(key, value)| (Expr::str(key), value))
+            .collect(),
+    )
+}
+
+fn raw(lines: &[&str]) -> Stmt {
+    Stmt::Raw(lines.iter().map(|&l| l.to_owned()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eyesight(version: &str) -> Eyesight {
+        let xml = format!(
+            r#"<eyesight{version}>
+                <material name="Solid Red" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance"><shader>
+                    <diffuse_bsdf name="d"/>
+                    <connect from_node="d" from_socket="BSDF" to_node="output" to_socket="Surface"/>
+                </shader></material>
+            </eyesight>"#
+        );
+        quick_xml::de::from_str(&xml).unwrap()
+    }
+
+    #[test]
+    fn addon_version_takes_the_leading_numbers() {
+        assert_eq!(addon_version(Some("2.2.14_2")), (2, 2, 14));
+        assert_eq!(addon_version(Some("2.3")), (2, 3, 0));
+        assert_eq!(addon_version(Some("beta")), (0, 0, 0));
+        assert_eq!(addon_version(None), (0, 0, 0));
+    }
+
+    #[test]
+    fn bl_info_names_both_versions() {
+        let print = |expr| {
+            Module {
+                body: vec![Stmt::Assign(Expr::name("bl_info"), expr)],
+            }
+            .print()
+        };
+        assert_eq!(
+            print(bl_info(Some("2.2.14_2"), BlenderVersion::V4_2)),
+            r#"bl_info = {
+    "name": "Studio Materials",
+    "author": "xml2py",
+    "version": (2, 2, 14),
+    "blender": (4, 2, 0),
+    "location": "3D Viewport > Object > Add Studio Material by Color",
+    "description": "Materials from BrickLink Studio 2.2.14_2",
+    "category": "Material",
+}
+"#
+        );
+        let unversioned = print(bl_info(None, BlenderVersion::V3_6));
+        assert!(unversioned.contains(r#""version": (0, 0, 0),"#));
+        assert!(unversioned.contains(r#""blender": (3, 6, 0),"#));
+        assert!(unversioned.contains(r#""description": "Materials from BrickLink Studio","#));
+    }
+
+    #[test]
+    fn files_are_in_the_package() {
+        let files =
+            addon_files(&eyesight(""), &BTreeSet::new(), BlenderVersion::default()).unwrap();
+        let names = files
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "studio_materials/__init__.py",
+                "studio_materials/groups.py",
+                "studio_materials/materials.py",
+                "studio_materials/helpers.py",
+                "studio_materials/custom_nodes.py",
+            ]
+        );
+    }
+
+    #[test]
+    fn materials_without_groups_import_none() {
+        let files =
+            addon_files(&eyesight(""), &BTreeSet::new(), BlenderVersion::default()).unwrap();
+        let (_, materials) = files
+            .iter()
+            .find(|(name, _)| name == "studio_materials/materials.py")
+            .unwrap();
+        assert!(!materials.contains("from .groups"));
+        assert!(materials.contains(r#"MATERIALS = {"Solid Red": solid_red_material}"#));
+    }
+}
@@ -0,0 +1,467 @@
+//! The transforms applied to the XML before anything else looks at it.
+//!
+//! Each one is a [`Pass`]. [`registry`] lists them in the order they run by default,
+//! and a [`Pipeline`] runs the enabled ones, in that order unless a pass says it has
+//! to come after another.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use eyesight_xml::nodes::Node;
+use eyesight_xml::schema::Eyesight;
+use eyesight_xml::Named;
+use heck::{ToPascalCase, ToSnakeCase, ToTitleCase};
+use serde_derive::Serialize;
+
+use crate::blender::BlenderVersion;
+use crate::cli::Options;
+use crate::config::{BeautifyNames, Case};
+use crate::patch::Patch;
+use crate::polyfill::Polyfills;
+use crate::principled;
+
+pub trait Pass {
+    /// How `--enable`, `--disable` and `xml2py.toml` refer to the pass.
+    fn name(&self) -> &str;
+    fn description(&self) -> &str;
+    /// Passes that have to run before this one, if they run at all.
+    fn after(&self) -> Vec<&str> {
+        vec![]
+    }
+    /// Whether the pass makes sense for the Blender version being targeted.
+    /// It's skipped otherwise, even if it's enabled.
+    fn applies_to(&self, _version: BlenderVersion) -> bool {
+        true
+    }
+    /// What has to be true of the XML for the pass to run.
+    fn preconditions(&self) -> Vec<Condition<'_>> {
+        vec![]
+    }
+    /// What is true of the XML once the pass has run.
+    fn postconditions(&self) -> Vec<Condition<'_>> {
+        vec![]
+    }
+    /// Transforms the XML. An error stops the pipeline.
+    fn apply(&self, eyesight: &mut Eyesight) -> Result<PassReport, String>;
+}
+
+pub struct Condition<'a> {
+    pub description: String,
+    pub holds: Box<dyn Fn(&Eyesight) -> bool + 'a>,
+}
+
+impl<'a> Condition<'a> {
+    pub fn new(description: impl Into<String>, holds: impl Fn(&Eyesight) -> bool + 'a) -> Self {
+        Self {
+            description: description.into(),
+            holds: Box::new(holds),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PassReport {
+    /// How many names, nodes or links the pass changed, added or replaced.
+    pub changes: usize,
+    /// Anything the pass did that whoever runs it should know about.
+    pub notes: Vec<String>,
+}
+
+/// What happened when a pass ran, for `--pass-report`.
+#[derive(Debug)]
+pub struct PassRun {
+    pub name: String,
+    pub report: PassReport,
+    pub time: Duration,
+}
+
+/// A pass, with whether it runs.
+type Entry = (Box<dyn Pass>, bool);
+
+/// Every pass, in the order they run by default, with whether it's enabled by default.
+/// Patches come after the built-in passes: the built-in ones, then those `xml2py.toml` lists.
+pub fn registry(options: &Options) -> Result<Vec<Entry>, String> {
+    let config = &options.passes;
+    let mut passes: Vec<Entry> = vec![
+        (
+            Box::new(config.beautify_names.clone()),
+            config.beautify_names.enabled,
+        ),
+        (Box::new(Polyfills), config.polyfills.enabled),
+    ];
+
+    let slope_roughness = Patch::parse(include_str!("patches/slope-roughness.toml"))
+        .map_err(|e| format!("slope-roughness.toml: {e}"))?;
+    passes.push((
+        Box::new(slope_roughness.targeting(options.blender_version)),
+        config.slope_roughness.enabled,
+    ));
+    for path in &config.patches {
+        let patch = Patch::load(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        passes.push((Box::new(patch.targeting(options.blender_version)), true));
+    }
+
+    // Patches are applied to the v1 Principled BSDF that Eyesight uses.
+    passes.push((Box::new(PrincipledV2), config.principled_v2.enabled));
+
+    Ok(passes)
+}
+
+pub struct Pipeline {
+    /// Every pass, in the order they run, with whether it runs.
+    passes: Vec<Entry>,
+    /// Where to write a debug dump of the XML as it is after each pass.
+    dump: Option<PathBuf>,
+}
+
+impl Pipeline {
+    pub fn new(options: &Options) -> Result<Self, String> {
+        let mut passes = registry(options)?;
+
+        for (i, (pass, _)) in passes.iter().enumerate() {
+            if passes[..i].iter().any(|(p, _)| p.name() == pass.name()) {
+                return Err(format!("there are two passes named {:?}", pass.name()));
+            }
+        }
+
+        for (names, enabled) in [(&options.enable, true), (&options.disable, false)] {
+            for name in names {
+                let (_, e) = passes
+                    .iter_mut()
+                    .find(|(pass, _)| pass.name() == name)
+                    .ok_or_else(|| format!("unknown pass {name:?}"))?;
+                *e = enabled;
+            }
+        }
+        for (pass, enabled) in &mut passes {
+            *enabled &= pass.applies_to(options.blender_version);
+        }
+
+        let mut ordered = vec![];
+        while !passes.is_empty() {
+            let is_pending = |name: &str| passes.iter().any(|(p, _)| p.name() == name);
+            let Some(i) = passes
+                .iter()
+                .position(|(pass, _)| !pass.after().iter().any(|a| is_pending(a)))
+            else {
+                let names = passes.iter().map(|(p, _)| p.name()).collect::<Vec<_>>();
+                return Err(format!("passes can't be ordered: {}", names.join(", ")));
+            };
+            ordered.push(passes.remove(i));
+        }
+
+        Ok(Self {
+            passes: ordered,
+            dump: options.dump_passes.clone(),
+        })
+    }
+
+    /// Every pass, in the order they run, with whether it runs.
+    pub fn passes(&self) -> impl Iterator<Item = (&dyn Pass, bool)> {
+        self.passes.iter().map(|(pass, runs)| (&**pass, *runs))
+    }
+
+    /// Runs every pass in order. A pass whose preconditions don't hold is an error;
+    /// one that doesn't leave its postconditions holding is a bug, and panics.
+    pub fn run(&self, eyesight: &mut Eyesight) -> Result<Vec<PassRun>, String> {
+        self.dump(eyesight, 0, "input")?;
+
+        let mut runs = vec![];
+        let passes = self
+            .passes()
+            .filter(|(_, runs)| *runs)
+            .map(|(pass, _)| pass);
+        for (i, pass) in passes.enumerate() {
+            for condition in pass.preconditions() {
+                if !(condition.holds)(eyesight) {
+                    return Err(format!(
+                        "{} can't run unless {}",
+                        pass.name(),
+                        condition.description
+                    ));
+                }
+            }
+
+            let start = Instant::now();
+            let report = pass
+                .apply(eyesight)
+                .map_err(|e| format!("{}: {e}", pass.name()))?;
+            let time = start.elapsed();
+
+            for condition in pass.postconditions() {
+                if !(condition.holds)(eyesight) {
+                    panic!(
+                        "{} should have made sure {}",
+                        pass.name(),
+                        condition.description
+                    );
+                }
+            }
+
+            self.dump(eyesight, i + 1, pass.name())?;
+            runs.push(PassRun {
+                name: pass.name().to_owned(),
+                report,
+                time,
+            });
+        }
+
+        Ok(runs)
+    }
+
+    fn dump(&self, eyesight: &Eyesight, index: usize, name: &str) -> Result<(), String> {
+        let Some(dir) = &self.dump else {
+            return Ok(());
+        };
+        let path = dir.join(format!("{index:02}-{name}.txt"));
+        std::fs::create_dir_all(dir)
+            .and_then(|_| std::fs::write(&path, format!("{eyesight:#?}\n")))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+/// A table of what each pass did and how long it took.
+pub fn report(runs: &[PassRun]) -> String {
+    let mut s = format!("{:<20} {:>8} {:>10}\n", "pass", "changes", "time");
+    for run in runs {
+        let millis = run.time.as_secs_f64() * 1000.0;
+        s += &format!(
+            "{:<20} {:>8} {:>8.2}ms\n",
+            run.name, run.report.changes, millis
+        );
+    }
+    s
+}
+
+impl Pass for BeautifyNames {
+    fn name(&self) -> &str {
+        "beautify-names"
+    }
+
+    fn description(&self) -> &str {
+        "tidy up group, node and material names"
+    }
+
+    fn apply(&self, eyesight: &mut Eyesight) -> Result<PassReport, String> {
+        let mut notes = vec![];
+
+        let groups = rename_all(
+            eyesight.groups.iter().map(|g| &*g.name),
+            |name| beautify_group_name(name, self),
+            self.group_case,
+            "group",
+            &mut notes,
+        );
+        let materials = rename_all(
+            eyesight.materials.iter().map(|m| &*m.name),
+            |name| beautify_material_name(name, self),
+            self.material_case,
+            "material",
+            &mut notes,
+        );
+        let nodes = eyesight
+            .groups
+            .iter()
+            .map(|group| {
+                let pascal_name = group.name.to_pascal_case();
+                let nodes = rename_all(
+                    group.shader.nodes.iter().map(|n| n.name()),
+                    |name| beautify_node_name(name, &group.name, &pascal_name, self),
+                    self.node_case,
+                    &format!("node in group {:?}", groups[&group.name]),
+                    &mut notes,
+                );
+                (group.name.clone(), nodes)
+            })
+            .collect();
+        let name_map = NameMap {
+            groups,
+            materials,
+            nodes,
+        };
+
+        let mut changes = 0;
+        let mut set = |name: &mut String, rename: &dyn Fn(&str) -> String| {
+            let new = rename(name);
+            if *name != new {
+                *name = new;
+                changes += 1;
+            }
+        };
+        let group_name = |name: &str| match name_map.groups.get(name) {
+            Some(new) => new.clone(),
+            // Custom groups and groups that aren't defined.
+            None => beautify_group_name(name, self),
+        };
+
+        for group in &mut eyesight.groups {
+            let pascal_name = group.name.to_pascal_case();
+            let nodes = &name_map.nodes[&group.name];
+            let node_name = |name: &str| match nodes.get(name) {
+                Some(new) => new.clone(),
+                None => beautify_node_name(name, &group.name, &pascal_name, self),
+            };
+
+            for node in &mut group.shader.nodes {
+                set(node.name_mut(), &node_name);
+                if let Node::Group(g) = node {
+                    set(&mut g.group_name, &group_name);
+                }
+            }
+
+            for link in &mut group.shader.links {
+                set(&mut link.from_node, &node_name);
+                set(&mut link.to_node, &node_name);
+            }
+
+            set(&mut group.name, &|name| name_map.groups[name].clone());
+        }
+
+        for material in &mut eyesight.materials {
+            for node in &mut material.shader.nodes {
+                if let Node::Group(g) = node {
+                    set(&mut g.group_name, &group_name);
+                }
+            }
+
+            set(&mut material.name, &|name| name_map.materials[name].clone());
+        }
+
+        if let Some(path) = &self.name_map {
+            let s = toml::to_string(&name_map).unwrap();
+            std::fs::write(path, s).map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+
+        Ok(PassReport { changes, notes })
+    }
+}
+
+/// The name each group, material and node had, and the one it was given.
+#[derive(Serialize, Debug)]
+struct NameMap {
+    groups: BTreeMap<String, String>,
+    materials: BTreeMap<String, String>,
+    /// By the group's original name.
+    nodes: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+/// Beautifies each name. A name that would end up the same as an earlier one is
+/// numbered instead, with a note saying so; `what` says what the names are of,
+/// and `case` is the case `beautify` leaves them in.
+fn rename_all<'a>(
+    names: impl Iterator<Item = &'a str>,
+    beautify: impl Fn(&str) -> String,
+    case: Case,
+    what: &str,
+    notes: &mut Vec<String>,
+) -> BTreeMap<String, String> {
+    let mut renamed = BTreeMap::new();
+    // Which name each new name was given to.
+    let mut owners = BTreeMap::<String, &str>::new();
+
+    for name in names {
+        if renamed.contains_key(name) {
+            continue;
+        }
+
+        let wanted = beautify(name);
+        let mut new = wanted.clone();
+        for n in 2.. {
+            if !owners.contains_key(&new) {
+                break;
+            }
+            new = case.numbered(&wanted, n);
+        }
+        if new != wanted {
+            notes.push(format!(
+                "{name:?} ({what}) would be called {wanted:?}, like {:?}, so it's called {new:?}",
+                owners[&wanted]
+            ));
+        }
+
+        owners.insert(new.clone(), name);
+        renamed.insert(name.to_owned(), new);
+    }
+
+    renamed
+}
+
+impl Case {
+    fn apply(self, name: &str) -> String {
+        match self {
+            Case::Keep => name.to_owned(),
+            Case::Snake => name.to_snake_case(),
+            Case::Title => name.to_title_case(),
+            Case::Pascal => name.to_pascal_case(),
+        }
+    }
+
+    /// The `n`th name that would otherwise be `name`.
+    fn numbered(self, name: &str, n: usize) -> String {
+        match self {
+            Case::Snake => format!("{name}_{n}"),
+            Case::Title | Case::Keep => format!("{name} {n}"),
+            Case::Pascal => format!("{name}{n}"),
+        }
+    }
+}
+
+fn beautify_node_name(
+    name: &str,
+    original_group_name: &str,
+    pascal_group_name: &str,
+    rules: &BeautifyNames,
+) -> String {
+    let mut fixed = name.to_owned();
+    for (from, to) in &rules.node_replacements {
+        fixed = fixed.replace(from, to);
+    }
+    let trimmed = fixed
+        .trim_start_matches(original_group_name)
+        .trim_start_matches(pascal_group_name);
+    rules.node_case.apply(trimmed)
+}
+
+fn beautify_group_name(name: &str, rules: &BeautifyNames) -> String {
+    let mut trimmed = name;
+    for suffix in &rules.group_suffixes {
+        trimmed = trimmed.trim_end_matches(&**suffix);
+    }
+    rules.group_case.apply(trimmed)
+}
+
+fn beautify_material_name(name: &str, rules: &BeautifyNames) -> String {
+    let mut name = rules.material_case.apply(name);
+    for (from, to) in &rules.material_replacements {
+        name = name.replace(from, to);
+    }
+    name
+}
+
+struct PrincipledV2;
+
+impl Pass for PrincipledV2 {
+    fn name(&self) -> &str {
+        "principled-v2"
+    }
+
+    fn description(&self) -> &str {
+        "convert Principled BSDF v1 inputs (Blender 4.0 and later only)"
+    }
+
+    fn applies_to(&self, version: BlenderVersion) -> bool {
+        version >= BlenderVersion::V4_0
+    }
+
+    fn apply(&self, eyesight: &mut Eyesight) -> Result<PassReport, String> {
+        let changes = eyesight
+            .all_shaders_mut()
+            .map(principled::convert_to_v2)
+            .sum();
+        Ok(PassReport {
+            changes,
+            ..Default::default()
+        })
+    }
+}
@@ -0,0 +1,754 @@
+//! Patch files: declarative edits to one group's or material's shader, applied as a pass.
+//!
+//! ```toml
+//! name = "slope-roughness"     # the pass's name, for --enable and --disable
+//! description = "make the Normal group's roughness depend on slope"
+//! group = "Normal"             # or material = "..."
+//! after = ["beautify-names"]   # the default, so names are as that pass leaves them
+//!
+//! [[edits]]
+//! add-node = { kind = "mix_value", name = "choose", attributes = { type = "mix" }, inputs = [
+//!     { name = "A", type = "float", value = 2.0 },
+//! ] }
+//!
+//! [[edits]]
+//! set-input = { node = "rough_surface", input = "Scale", type = "float", value = 5.0 }
+//!
+//! [[edits]]
+//! add-link = { from = "choose.0", to = "rough_surface.Scale" }
+//! ```
+//!
+//! The other edits are `replace-node` (same fields as `add-node`; links are kept),
+//! `delete-node = { node = "..." }` (its links go too) and `remove-link`.
+//!
+//! Nodes are written the way the XML writes them: `kind` is the element name, `attributes`
+//! its attributes and `inputs` and `outputs` its `<input>` and `<output>` elements.
+//! Sockets are `node.socket`, with the XML's socket name or an index. A `set-input`
+//! without a `type` takes the type of the value it replaces.
+//!
+//! Before anything is changed, every edit checks that it still applies: that the nodes
+//! and sockets it mentions exist, that it isn't adding something already there, and so on.
+//! When a Studio update moves the XML away from a patch, that's what gets reported.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use eyesight_xml::nodes::{
+    GroupReferenceInput, InputsMut, Node, NodeInput, NodeInputValue, SocketType, Vec3,
+};
+use eyesight_xml::schema::{Eyesight, Link, Shader};
+use eyesight_xml::Named;
+use quick_xml::escape::escape;
+use serde_derive::Deserialize;
+
+use crate::blender::BlenderVersion;
+use crate::groups::value_type;
+use crate::mapping;
+use crate::passes::{Condition, Pass, PassReport};
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Patch {
+    name: String,
+    #[serde(default)]
+    description: String,
+    group: Option<String>,
+    material: Option<String>,
+    #[serde(default = "default_after")]
+    after: Vec<String>,
+    edits: Vec<Edit>,
+    /// The Blender version whose sockets the edits are checked against.
+    #[serde(skip)]
+    version: BlenderVersion,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+enum Edit {
+    AddNode(NodeSpec),
+    /// Swaps a node for another with the same name. Its links are kept.
+    ReplaceNode(NodeSpec),
+    /// Removes a node and its links.
+    DeleteNode {
+        node: String,
+    },
+    SetInput {
+        node: String,
+        input: String,
+        #[serde(rename = "type")]
+        data_type: Option<SocketType>,
+        value: toml::Value,
+    },
+    AddLink {
+        from: String,
+        to: String,
+    },
+    RemoveLink {
+        from: String,
+        to: String,
+    },
+}
+
+/// A node, as the XML would have it.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+struct NodeSpec {
+    /// The element name, like `mix_value`, or `group` for a group node.
+    kind: String,
+    name: String,
+    #[serde(default)]
+    attributes: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    inputs: Vec<SocketSpec>,
+    #[serde(default)]
+    outputs: Vec<SocketSpec>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+struct SocketSpec {
+    name: String,
+    #[serde(rename = "type")]
+    data_type: String,
+    value: Option<toml::Value>,
+}
+
+fn default_after() -> Vec<String> {
+    vec!["beautify-names".into()]
+}
+
+impl Patch {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let s = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        Self::parse(&s)
+    }
+
+    /// Reads a patch and checks everything that doesn't depend on the XML it's applied to.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let patch = toml::from_str::<Self>(s).map_err(|e| e.to_string())?;
+
+        if patch.group.is_some() == patch.material.is_some() {
+            return Err("a patch needs either a group or a material".into());
+        }
+
+        for edit in &patch.edits {
+            match edit {
+                Edit::AddNode(spec) | Edit::ReplaceNode(spec) => {
+                    spec.to_node()?;
+                }
+                Edit::DeleteNode { .. } => {}
+                Edit::SetInput {
+                    data_type, value, ..
+                } => {
+                    if let Some(data_type) = data_type {
+                        input_value(*data_type, value)?;
+                    }
+                }
+                Edit::AddLink { from, to } | Edit::RemoveLink { from, to } => {
+                    socket(from)?;
+                    socket(to)?;
+                }
+            }
+        }
+
+        Ok(patch)
+    }
+
+    /// The patch, checking sockets against the given Blender version's nodes.
+    pub fn targeting(self, version: BlenderVersion) -> Self {
+        Self { version, ..self }
+    }
+
+    /// `group "Normal"` or `material "..."`, for messages.
+    fn target(&self) -> String {
+        match (&self.group, &self.material) {
+            (Some(group), _) => format!("group {group:?}"),
+            (None, Some(material)) => format!("material {material:?}"),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    fn shader<'e>(&self, eyesight: &'e Eyesight) -> Option<&'e Shader> {
+        match (&self.group, &self.material) {
+            (Some(name), _) => eyesight
+                .groups
+                .iter()
+                .find(|g| g.name == *name)
+                .map(|g| &g.shader),
+            (None, Some(name)) => eyesight
+                .materials
+                .iter()
+                .find(|m| m.name == *name)
+                .map(|m| &m.shader),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    fn shader_mut<'e>(&self, eyesight: &'e mut Eyesight) -> Option<&'e mut Shader> {
+        match (&self.group, &self.material) {
+            (Some(name), _) => eyesight
+                .groups
+                .iter_mut()
+                .find(|g| g.name == *name)
+                .map(|g| &mut g.shader),
+            (None, Some(name)) => eyesight
+                .materials
+                .iter_mut()
+                .find(|m| m.name == *name)
+                .map(|m| &mut m.shader),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    /// The nodes and links the edits change, as they are after each edit.
+    fn simulate(&self) -> Vec<Changes> {
+        let mut changes = Changes::default();
+        let mut after_each = vec![];
+        for edit in &self.edits {
+            after_each.push(changes.clone());
+            match edit {
+                Edit::AddNode(spec) | Edit::ReplaceNode(spec) => {
+                    let node = spec.to_node().unwrap();
+                    changes.nodes.insert(spec.name.clone(), Some(node));
+                }
+                Edit::DeleteNode { node } => {
+                    changes.nodes.insert(node.clone(), None);
+                }
+                Edit::SetInput { .. } => {}
+                Edit::AddLink { from, to } => {
+                    changes.links.insert((from.clone(), to.clone()), true);
+                }
+                Edit::RemoveLink { from, to } => {
+                    changes.links.insert((from.clone(), to.clone()), false);
+                }
+            }
+        }
+        after_each.push(changes);
+        after_each
+    }
+}
+
+/// What a patch has done so far, on top of the shader it started from.
+#[derive(Debug, Default, Clone)]
+struct Changes {
+    /// Nodes that were added or replaced, or `None` if they were deleted.
+    nodes: BTreeMap<String, Option<Node>>,
+    /// Links that were added (`true`) or removed (`false`), as `node.socket` pairs.
+    links: BTreeMap<(String, String), bool>,
+}
+
+impl Changes {
+    fn node(&self, shader: &Shader, name: &str) -> Option<Node> {
+        match self.nodes.get(name) {
+            Some(node) => node.clone(),
+            None => shader.nodes.iter().find(|n| n.name() == name).cloned(),
+        }
+    }
+
+    fn links(&self, shader: &Shader) -> BTreeSet<(String, String)> {
+        let mut links = shader
+            .links
+            .iter()
+            .map(|l| {
+                let from = format!("{}.{}", l.from_node, l.from_socket);
+                let to = format!("{}.{}", l.to_node, l.to_socket);
+                (from, to)
+            })
+            .filter(|link| !self.links.contains_key(link))
+            .collect::<BTreeSet<_>>();
+        for (link, added) in &self.links {
+            if *added {
+                links.insert(link.clone());
+            }
+        }
+        links
+    }
+}
+
+impl Pass for Patch {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn description(&self) -> &str {
+        &self.description
+    }
+
+    fn after(&self) -> Vec<&str> {
+        self.after.iter().map(|a| &**a).collect()
+    }
+
+    fn preconditions(&self) -> Vec<Condition<'_>> {
+        let target = self.target();
+        let mut conditions = vec![Condition::new(format!("there is a {target}"), |e| {
+            self.shader(e).is_some()
+        })];
+
+        // Each edit is checked against the shader as the edits before it leave it.
+        for (edit, changes) in self.edits.iter().zip(self.simulate()) {
+            let has_node = |name: String, wanted: bool| {
+                let changes = changes.clone();
+                move |e: &Eyesight| {
+                    let shader = self.shader(e).unwrap();
+                    changes.node(shader, &name).is_some() == wanted
+                }
+            };
+            let socket_exists = |s: &str, is_input: bool| {
+                let (node, socket) = socket(s).unwrap();
+                let (node, socket) = (node.to_owned(), socket.to_owned());
+                let changes = changes.clone();
+                move |e: &Eyesight| {
+                    let shader = self.shader(e).unwrap();
+                    changes
+                        .node(shader, &node)
+                        .is_some_and(|n| has_socket(&n, &socket, is_input, self.version))
+                }
+            };
+
+            match edit {
+                Edit::AddNode(spec) => conditions.push(Condition::new(
+                    format!("{target} has no node named {:?} yet", spec.name),
+                    has_node(spec.name.clone(), false),
+                )),
+                Edit::ReplaceNode(NodeSpec { name, .. }) | Edit::DeleteNode { node: name } => {
+                    conditions.push(Condition::new(
+                        format!("{target} has a node named {name:?}"),
+                        has_node(name.clone(), true),
+                    ))
+                }
+                Edit::SetInput {
+                    node,
+                    input,
+                    data_type,
+                    ..
+                } => {
+                    let description =
+                        format!("{target} has a node named {node:?} with an input {input:?}");
+                    let changes_before = changes.clone();
+                    let (name, socket) = (node.clone(), input.clone());
+                    conditions.push(Condition::new(description, move |e| {
+                        let shader = self.shader(e).unwrap();
+                        let Some(mut node) = changes_before.node(shader, &name) else {
+                            return false;
+                        };
+                        let settable =
+                            matches!(node, Node::Group(_)) || node.inputs_mut().is_some();
+                        settable && has_socket(&node, &socket, true, self.version)
+                    }));
+
+                    if data_type.is_none() {
+                        let description = format!(
+                            "{node:?} in {target} already has a value for {input:?}, \
+                             since the edit doesn't give a type"
+                        );
+                        let (node, input) = (node.clone(), input.clone());
+                        let changes = changes.clone();
+                        conditions.push(Condition::new(description, move |e| {
+                            let shader = self.shader(e).unwrap();
+                            changes
+                                .node(shader, &node)
+                                .is_some_and(|n| input_type(&n, &input).is_some())
+                        }));
+                    }
+                }
+                Edit::AddLink { from, to } => {
+                    conditions.push(Condition::new(
+                        format!("{target} has an output {from:?}"),
+                        socket_exists(from, false),
+                    ));
+                    conditions.push(Condition::new(
+                        format!("{target} has an input {to:?}"),
+                        socket_exists(to, true),
+                    ));
+                    let changes = changes.clone();
+                    let to = to.clone();
+                    conditions.push(Condition::new(
+                        format!("nothing in {target} is linked to {to:?} yet"),
+                        move |e| {
+                            let shader = self.shader(e).unwrap();
+                            !changes.links(shader).iter().any(|(_, t)| *t == to)
+                        },
+                    ));
+                }
+                Edit::RemoveLink { from, to } => {
+                    let changes = changes.clone();
+                    let link = (from.clone(), to.clone());
+                    conditions.push(Condition::new(
+                        format!("{target} has a link from {from:?} to {to:?}"),
+                        move |e| {
+                            let shader = self.shader(e).unwrap();
+                            changes.links(shader).contains(&link)
+                        },
+                    ));
+                }
+            }
+        }
+
+        conditions
+    }
+
+    fn postconditions(&self) -> Vec<Condition<'_>> {
+        let target = self.target();
+        let changes = self.simulate().pop().unwrap();
+        let mut conditions = vec![];
+
+        for (name, node) in changes.nodes {
+            let exists = node.is_some();
+            let description = if exists {
+                format!("{target} has a node named {name:?}")
+            } else {
+                format!("{target} has no node named {name:?}")
+            };
+            conditions.push(Condition::new(description, move |e| {
+                let shader = self.shader(e).unwrap();
+                shader.nodes.iter().any(|n| n.name() == name) == exists
+            }));
+        }
+
+        for ((from, to), exists) in changes.links {
+            let description = if exists {
+                format!("{target} has a link from {from:?} to {to:?}")
+            } else {
+                format!("{target} has no link from {from:?} to {to:?}")
+            };
+            let link = (from, to);
+            conditions.push(Condition::new(description, move |e| {
+                let shader = self.shader(e).unwrap();
+                Changes::default().links(shader).contains(&link) == exists
+            }));
+        }
+
+        conditions
+    }
+
+    fn apply(&self, eyesight: &mut Eyesight) -> Result<PassReport, String> {
+        let shader = self.shader_mut(eyesight).unwrap();
+
+        for edit in &self.edits {
+            match edit {
+                Edit::AddNode(spec) => shader.nodes.push(spec.to_node().unwrap()),
+                Edit::ReplaceNode(spec) => {
+                    let node = shader
+                        .nodes
+                        .iter_mut()
+                        .find(|n| n.name() == spec.name)
+                        .unwrap();
+                    *node = spec.to_node().unwrap();
+                }
+                Edit::DeleteNode { node } => {
+                    shader.nodes.retain(|n| n.name() != node);
+                    shader
+                        .links
+                        .retain(|l| l.from_node != *node && l.to_node != *node);
+                }
+                Edit::SetInput {
+                    node,
+                    input,
+                    data_type,
+                    value,
+                } => {
+                    let node = shader.nodes.iter_mut().find(|n| n.name() == node).unwrap();
+                    let data_type = data_type.or_else(|| input_type(node, input)).unwrap();
+                    let value = input_value(data_type, value).unwrap();
+                    set_input(node, input, data_type, value);
+                }
+                Edit::AddLink { from, to } => {
+                    let (from_node, from_socket) = socket(from).unwrap();
+                    let (to_node, to_socket) = socket(to).unwrap();
+                    shader
+                        .links
+                        .push(Link::new(from_node, from_socket, to_node, to_socket));
+                }
+                Edit::RemoveLink { from, to } => {
+                    let (from_node, from_socket) = socket(from).unwrap();
+                    let (to_node, to_socket) = socket(to).unwrap();
+                    shader
+                        .links
+                        .retain(|l| *l != Link::new(from_node, from_socket, to_node, to_socket));
+                }
+            }
+        }
+
+        Ok(PassReport {
+            changes: self.edits.len(),
+            ..Default::default()
+        })
+    }
+}
+
+impl NodeSpec {
+    /// Builds the node by reading it as XML, so it's checked like any other.
+    fn to_node(&self) -> Result<Node, String> {
+        let mut xml = format!("<{} name=\"{}\"", self.kind, escape(&self.name));
+        for (name, value) in &self.attributes {
+            write!(xml, " {name}=\"{}\"", escape(&attribute(value)?)).unwrap();
+        }
+        xml += ">";
+        for (element, sockets) in [("input", &self.inputs), ("output", &self.outputs)] {
+            for socket in sockets {
+                write!(
+                    xml,
+                    "<{element} name=\"{}\" type=\"{}\"",
+                    escape(&socket.name),
+                    escape(&socket.data_type)
+                )
+                .unwrap();
+                if let Some(value) = &socket.value {
+                    write!(xml, " value=\"{}\"", escape(&attribute(value)?)).unwrap();
+                }
+                xml += "/>";
+            }
+        }
+        write!(xml, "</{}>", self.kind).unwrap();
+
+        quick_xml::de::from_str::<Node>(&xml).map_err(|e| format!("node {:?}: {e}", self.name))
+    }
+}
+
+/// A TOML value the way an XML attribute would spell it. Arrays are space-separated.
+fn attribute(value: &toml::Value) -> Result<String, String> {
+    Ok(match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(n) => n.to_string(),
+        toml::Value::Float(x) => x.to_string(),
+        toml::Value::Boolean(b) => b.to_string(),
+        toml::Value::Array(items) => items
+            .iter()
+            .map(attribute)
+            .collect::<Result<Vec<_>, _>>()?
+            .join(" "),
+        _ => return Err(format!("{value} can't be an attribute")),
+    })
+}
+
+fn input_value(data_type: SocketType, value: &toml::Value) -> Result<NodeInputValue, String> {
+    let number = |value: &toml::Value| match value {
+        toml::Value::Integer(n) => Some(*n as f32),
+        toml::Value::Float(x) => Some(*x as f32),
+        _ => None,
+    };
+    let vec3 = |value: &toml::Value| match value.as_array()?.as_slice() {
+        [x, y, z] => Some(Vec3([number(x)?, number(y)?, number(z)?])),
+        _ => None,
+    };
+
+    let parsed = match data_type {
+        SocketType::Float => number(value).map(NodeInputValue::Float),
+        SocketType::Vector => vec3(value).map(NodeInputValue::Vector),
+        SocketType::Int => value
+            .as_integer()
+            .and_then(|n| u32::try_from(n).ok())
+            .map(NodeInputValue::Int),
+        SocketType::Color => vec3(value).map(NodeInputValue::Color),
+        SocketType::Boolean => value.as_bool().map(NodeInputValue::Boolean),
+        SocketType::Closure => None,
+    };
+    parsed.ok_or_else(|| format!("{value} isn't a {data_type:?} value"))
+}
+
+/// Splits `node.socket`.
+fn socket(s: &str) -> Result<(&str, &str), String> {
+    s.rsplit_once('.')
+        .ok_or_else(|| format!("{s:?} should be node.socket"))
+}
+
+/// Whether the node has the socket in the targeted Blender version, after aliasing.
+fn has_socket(node: &Node, socket: &str, is_input: bool, version: BlenderVersion) -> bool {
+    let mapping = mapping::get();
+    let node_type = mapping.node_type(node, version);
+    let alias = if is_input {
+        mapping.input_alias(version, node_type, socket)
+    } else {
+        mapping.output_alias(version, node_type, socket)
+    };
+    let (identifier, _) =
+        version.resolve_socket(node, node_type, alias.unwrap_or(socket), is_input);
+    version
+        .sockets(node_type)
+        .is_some_and(|sockets| sockets.has(&identifier, is_input))
+}
+
+/// The type of the value the node has for an input, if it has one.
+fn input_type(node: &Node, input: &str) -> Option<SocketType> {
+    match node {
+        Node::Group(g) => g
+            .inputs_
+            .iter()
+            .find(|i| i.name == input)
+            .map(|i| i.data_type),
+        _ => node
+            .clone()
+            .inputs_mut()?
+            .iter()
+            .find(|i| i.name == input)
+            .map(|i| value_type(&i.value)),
+    }
+}
+
+fn set_input(node: &mut Node, input: &str, data_type: SocketType, value: NodeInputValue) {
+    if let Node::Group(g) = node {
+        match g.inputs_.iter_mut().find(|i| i.name == input) {
+            Some(existing) => {
+                existing.data_type = data_type;
+                existing.value = Some(value);
+            }
+            None => g.inputs_.push(GroupReferenceInput {
+                name: input.into(),
+                data_type,
+                value: Some(value),
+            }),
+        }
+        return;
+    }
+
+    let inputs = node.inputs_mut().unwrap();
+    match inputs.iter_mut().find(|i| i.name == input) {
+        Some(existing) => existing.value = value,
+        None => inputs.push(NodeInput::new(input, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use eyesight_xml::nodes::INode;
+
+    use super::*;
+
+    const PATCH: &str = r#"
+name = "test"
+group = "G"
+
+[[edits]]
+add-node = { kind = "value", name = "w", attributes = { value = 2.0 } }
+
+[[edits]]
+set-input = { node = "d", input = "Roughness", value = 0.25 }
+
+[[edits]]
+add-link = { from = "w.Value", to = "d.Roughness" }
+
+[[edits]]
+remove-link = { from = "d.BSDF", to = "out.BSDF" }
+
+[[edits]]
+delete-node = { node = "v" }
+"#;
+
+    fn group(nodes: &str, links: &str) -> Eyesight {
+        let xml = format!(
+            r#"<eyesight><group name="G"><shader>
+                <group_output name="out"/>
+                <noise_texture name="n" tex_mapping.rotation="0 0 0" tex_mapping.scale="1 1 1" tex_mapping.translation="0 0 0" tex_mapping.type="point"/>
+                {nodes}
+                <connect from_node="n" from_socket="Fac" to_node="out" to_socket="Fac"/>
+                {links}
+            </shader></group></eyesight>"#
+        );
+        quick_xml::de::from_str(&xml).unwrap()
+    }
+
+    fn failing(conditions: Vec<Condition>, eyesight: &Eyesight) -> Vec<String> {
+        conditions
+            .into_iter()
+            .filter(|c| !(c.holds)(eyesight))
+            .map(|c| c.description)
+            .collect()
+    }
+
+    #[test]
+    fn parse_checks_what_it_can() {
+        let error = |s: &str| Patch::parse(s).unwrap_err();
+        assert_eq!(
+            error("name = \"x\"\nedits = []"),
+            "a patch needs either a group or a material"
+        );
+        assert_eq!(
+            error("name = \"x\"\ngroup = \"G\"\nmaterial = \"M\"\nedits = []"),
+            "a patch needs either a group or a material"
+        );
+        let edit = |edit: &str| error(&format!("name = \"x\"\ngroup = \"G\"\nedits = [{edit}]"));
+        assert_eq!(
+            edit(r#"{ add-link = { from = "w", to = "d.Roughness" } }"#),
+            "\"w\" should be node.socket"
+        );
+        assert_eq!(
+            edit(
+                r#"{ set-input = { node = "d", input = "Roughness", type = "float", value = "high" } }"#
+            ),
+            "\"high\" isn't a Float value"
+        );
+        assert!(
+            edit(r#"{ add-node = { kind = "nonsense", name = "x" } }"#).starts_with("node \"x\": ")
+        );
+    }
+
+    #[test]
+    fn edits_apply_in_order() {
+        let mut eyesight = group(
+            r#"<value name="v" value="1"/>
+               <diffuse_bsdf name="d"><input name="Roughness" type="float" value="0.5"/></diffuse_bsdf>"#,
+            r#"<connect from_node="d" from_socket="BSDF" to_node="out" to_socket="BSDF"/>"#,
+        );
+        let patch = Patch::parse(PATCH).unwrap();
+        assert!(failing(patch.preconditions(), &eyesight).is_empty());
+
+        patch.apply(&mut eyesight).unwrap();
+        assert!(failing(patch.postconditions(), &eyesight).is_empty());
+
+        let shader = &eyesight.groups[0].shader;
+        let names = shader.nodes.iter().map(|n| n.name()).collect::<Vec<_>>();
+        assert_eq!(names, ["out", "n", "d", "w"]);
+        assert_eq!(
+            shader.nodes[2].inputs()[0].value,
+            NodeInputValue::Float(0.25)
+        );
+        assert_eq!(
+            Changes::default().links(shader),
+            BTreeSet::from([
+                ("n.Fac".to_owned(), "out.Fac".to_owned()),
+                ("w.Value".to_owned(), "d.Roughness".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn stale_patches_say_what_moved() {
+        let eyesight = group(
+            r#"<value name="v" value="1"/>
+               <value name="w" value="1"/>
+               <diffuse_bsdf name="d"/>"#,
+            "",
+        );
+        let patch = Patch::parse(PATCH).unwrap();
+        assert_eq!(
+            failing(patch.preconditions(), &eyesight),
+            [
+                "group \"G\" has no node named \"w\" yet",
+                "\"d\" in group \"G\" already has a value for \"Roughness\", \
+                 since the edit doesn't give a type",
+                "group \"G\" has a link from \"d.BSDF\" to \"out.BSDF\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn sockets_are_checked_against_the_targeted_version() {
+        let eyesight = group(r#"<value name="w" value="1"/>"#, "");
+        let patch = Patch::parse(
+            r#"
+name = "lacunarity"
+group = "G"
+edits = [{ add-link = { from = "w.Value", to = "n.Lacunarity" } }]
+"#,
+        )
+        .unwrap();
+        assert!(failing(patch.preconditions(), &eyesight).is_empty());
+
+        let patch = patch.targeting(BlenderVersion::V3_6);
+        assert_eq!(
+            failing(patch.preconditions(), &eyesight),
+            ["group \"G\" has an input \"n.Lacunarity\""]
+        );
+    }
+}
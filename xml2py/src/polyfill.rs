@@ -0,0 +1,589 @@
+//! Lowerings for the Eyesight nodes that Blender has no equivalent for.
+//!
+//! Every such node has a [`Polyfill`] saying what it becomes, and which [`Conversion`]
+//! does it. Most are replaced by standard nodes in the polyfills pass. Those replacements
+//! are tested with a small numeric evaluator: the original node and its replacement
+//! are both evaluated, with the node's literal inputs and made-up values on the inputs
+//! that are linked, and have to agree. The rest are handled where they're converted,
+//! and are only documented here.
+//!
+//! Inputs that are neither linked nor set in the XML are taken to be zero on both sides.
+
+use std::collections::BTreeMap;
+
+use eyesight_xml::nodes::{
+    MixClosure, MixType, MixValue, MixVector, Node, NodeInput, SocketType, VectorOperation,
+};
+use eyesight_xml::schema::{Eyesight, Shader};
+use eyesight_xml::Named;
+
+use crate::passes::{Condition, Pass, PassReport};
+
+pub struct Polyfill {
+    /// The Eyesight node, as `Node::kind` spells it.
+    pub node_kind: &'static str,
+    /// What the node becomes, and why that does the same thing.
+    pub lowering: &'static str,
+    pub conversion: Conversion,
+}
+
+/// Where a node is lowered.
+pub enum Conversion {
+    /// The polyfills pass replaces it with standard nodes.
+    Replace(Replacement),
+    /// Code generation calls the hand-written group with this name; see `custom_groups`.
+    CustomGroup(&'static str),
+    /// Code generation makes it the standard node that the mapping names, set up
+    /// from the node's attributes.
+    Attributes,
+}
+
+pub struct Replacement {
+    /// Whether this node needs replacing; not every node of the kind does.
+    pub applies: fn(&Node) -> bool,
+    pub replace: fn(&Node) -> Node,
+    /// The inputs the Eyesight node reads, with their types. Links to any other input
+    /// make no difference, and are dropped.
+    pub inputs: &'static [(&'static str, SocketType)],
+    /// What the Eyesight node outputs. Only the tests evaluate nodes.
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub evaluate: fn(&Node, &Inputs) -> Value,
+}
+
+pub const POLYFILLS: &[Polyfill] = &[
+    Polyfill {
+        node_kind: "VectorMath",
+        lowering: "An average becomes a vector Mix node with a factor of one half. \
+                   Vector1 and Vector2 become its A and B inputs.",
+        conversion: Conversion::Replace(Replacement {
+            applies: |node| matches!(node, Node::VectorMath(v) if v.operation == VectorOperation::Average),
+            replace: |node| {
+                let Node::VectorMath(v) = node else {
+                    unreachable!()
+                };
+                let mut inputs = v.inputs.clone();
+                inputs.push(NodeInput::new("Factor", 0.5));
+                Node::MixVector(MixVector {
+                    name: v.name.clone(),
+                    inputs,
+                    span: v.span.clone(),
+                })
+            },
+            inputs: &[
+                ("Vector1", SocketType::Vector),
+                ("Vector2", SocketType::Vector),
+            ],
+            evaluate: |_, inputs| {
+                let [a, b] = [inputs.vector("Vector1"), inputs.vector("Vector2")];
+                Value::Vector(std::array::from_fn(|i| (a[i] + b[i]) / 2.0))
+            },
+        }),
+    },
+    Polyfill {
+        node_kind: "SwitchFloat",
+        lowering: "A float Mix node whose factor is 1 if the switch is enabled and 0 if not. \
+                   ValueDisable and ValueEnable become its A and B inputs.",
+        conversion: Conversion::Replace(Replacement {
+            applies: |_| true,
+            replace: |node| {
+                let Node::SwitchFloat(s) = node else {
+                    unreachable!()
+                };
+                let mut inputs = s.inputs.clone();
+                inputs.push(NodeInput::new("Factor", factor(s.enable)));
+                Node::MixValue(MixValue {
+                    name: s.name.clone(),
+                    mix_type: MixType::Mix,
+                    use_clamp: false,
+                    inputs,
+                    span: s.span.clone(),
+                })
+            },
+            inputs: &[
+                ("ValueDisable", SocketType::Float),
+                ("ValueEnable", SocketType::Float),
+            ],
+            evaluate: |node, inputs| {
+                let Node::SwitchFloat(s) = node else {
+                    unreachable!()
+                };
+                let socket = if s.enable {
+                    "ValueEnable"
+                } else {
+                    "ValueDisable"
+                };
+                Value::Float(inputs.float(socket))
+            },
+        }),
+    },
+    Polyfill {
+        node_kind: "SwitchClosure",
+        lowering: "A Mix Shader node whose factor is 1 if the switch is enabled and 0 if not, \
+                   so that it passes Shader2 or Shader1 through.",
+        conversion: Conversion::Replace(Replacement {
+            applies: |_| true,
+            replace: |node| {
+                let Node::SwitchClosure(s) = node else {
+                    unreachable!()
+                };
+                Node::MixClosure(MixClosure {
+                    name: s.name.clone(),
+                    inputs: vec![NodeInput::new("Fac", factor(s.enable))],
+                    span: s.span.clone(),
+                })
+            },
+            inputs: &[
+                ("Shader1", SocketType::Closure),
+                ("Shader2", SocketType::Closure),
+            ],
+            evaluate: |node, inputs| {
+                let Node::SwitchClosure(s) = node else {
+                    unreachable!()
+                };
+                let socket = if s.enable { "Shader2" } else { "Shader1" };
+                Value::Closure(inputs.closure(socket))
+            },
+        }),
+    },
+    Polyfill {
+        node_kind: "RoundingEdgeNormal",
+        lowering: "A Bevel node. Its Samples input is a property in Blender, so it's set as one, \
+                   and a disabled node is muted, which passes its Normal input through.",
+        conversion: Conversion::Attributes,
+    },
+    Polyfill {
+        node_kind: "UvDegradation",
+        lowering: "The hand-written UV Degradation group; see `custom_groups`.",
+        conversion: Conversion::CustomGroup("UV Degradation"),
+    },
+    Polyfill {
+        node_kind: "ProjectToAxisPlane",
+        lowering: "The hand-written Project To Axis Plane group; see `custom_groups`.",
+        conversion: Conversion::CustomGroup("Project To Axis Plane"),
+    },
+];
+
+/// The polyfill for a node, if it's one that Blender has no equivalent for.
+pub fn for_node(node: &Node) -> Option<&'static Polyfill> {
+    POLYFILLS.iter().find(|p| p.node_kind == node.kind())
+}
+
+/// The replacement for a node, if it needs replacing.
+fn replacement(node: &Node) -> Option<&'static Replacement> {
+    match &for_node(node)?.conversion {
+        Conversion::Replace(r) if (r.applies)(node) => Some(r),
+        _ => None,
+    }
+}
+
+/// Nodes that the polyfills pass would have replaced, as "owner / node". Nothing
+/// else converts them, so they're an error if the pass was skipped.
+pub fn unreplaced(eyesight: &Eyesight) -> Vec<String> {
+    let groups = eyesight.groups.iter().map(|g| (&g.name, &g.shader));
+    let materials = eyesight.materials.iter().map(|m| (&m.name, &m.shader));
+    groups
+        .chain(materials)
+        .flat_map(|(owner, shader)| {
+            shader
+                .nodes
+                .iter()
+                .filter(|n| replacement(n).is_some())
+                .map(move |n| format!("{owner} / {}", n.name()))
+        })
+        .collect()
+}
+
+fn factor(enable: bool) -> f32 {
+    if enable {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+/// What flows through a socket, as far as the evaluator is concerned.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Float(f32),
+    Vector([f32; 3]),
+    /// How much of each closure linked into the node makes it to the output,
+    /// by the socket it's linked to.
+    Closure(BTreeMap<String, f32>),
+}
+
+/// A node's input values, by socket name.
+#[derive(Debug, Default)]
+pub struct Inputs(BTreeMap<String, Value>);
+
+impl Inputs {
+    fn get(&self, socket: &str) -> Option<&Value> {
+        self.0.get(socket)
+    }
+
+    pub fn float(&self, socket: &str) -> f32 {
+        match self.get(socket) {
+            Some(Value::Float(f)) => *f,
+            None => 0.0,
+            Some(v) => panic!("expected a float for {socket}, got {v:?}"),
+        }
+    }
+
+    pub fn vector(&self, socket: &str) -> [f32; 3] {
+        match self.get(socket) {
+            Some(Value::Vector(v)) => *v,
+            Some(Value::Float(f)) => [*f; 3],
+            None => [0.0; 3],
+            Some(v) => panic!("expected a vector for {socket}, got {v:?}"),
+        }
+    }
+
+    pub fn closure(&self, socket: &str) -> BTreeMap<String, f32> {
+        match self.get(socket) {
+            Some(Value::Closure(c)) => c.clone(),
+            None => BTreeMap::new(),
+            Some(v) => panic!("expected a closure for {socket}, got {v:?}"),
+        }
+    }
+}
+
+/// Replaces every node that has a replacement, and drops links to inputs that the
+/// nodes don't read, with a note for each. `owner` is the shader's group or material.
+pub fn apply(shader: &mut Shader, owner: &str, notes: &mut Vec<String>) -> usize {
+    let mut changes = 0;
+    for i in 0..shader.nodes.len() {
+        let node = &shader.nodes[i];
+        let Some(replacement) = replacement(node) else {
+            continue;
+        };
+
+        let reads = |socket: &str| replacement.inputs.iter().any(|&(name, _)| name == socket);
+        shader.links.retain(|l| {
+            let unread = l.to_node == node.name() && !reads(&l.to_socket);
+            if unread {
+                notes.push(format!(
+                    "{owner} / {}: a {} doesn't read its {} input, so the link to it is dropped",
+                    node.name(),
+                    node.kind(),
+                    l.to_socket
+                ));
+            }
+            !unread
+        });
+
+        let replaced = (replacement.replace)(node);
+        shader.nodes[i] = replaced;
+        changes += 1;
+    }
+    changes
+}
+
+/// Replaces Eyesight-only nodes with standard ones.
+pub struct Polyfills;
+
+impl Pass for Polyfills {
+    fn name(&self) -> &str {
+        "polyfills"
+    }
+
+    fn description(&self) -> &str {
+        "replace Eyesight-only nodes with equivalent standard ones"
+    }
+
+    fn postconditions(&self) -> Vec<Condition<'_>> {
+        vec![Condition::new(
+            "no node needs a polyfill",
+            |e: &Eyesight| {
+                e.groups
+                    .iter()
+                    .map(|g| &g.shader)
+                    .chain(e.materials.iter().map(|m| &m.shader))
+                    .all(|shader| shader.nodes.iter().all(|n| replacement(n).is_none()))
+            },
+        )]
+    }
+
+    fn apply(&self, eyesight: &mut Eyesight) -> Result<PassReport, String> {
+        let mut notes = vec![];
+        let groups = eyesight.groups.iter_mut().map(|g| (&g.name, &mut g.shader));
+        let materials = eyesight
+            .materials
+            .iter_mut()
+            .map(|m| (&m.name, &mut m.shader));
+        let changes = groups
+            .chain(materials)
+            .map(|(owner, shader)| apply(shader, owner, &mut notes))
+            .sum();
+        Ok(PassReport { changes, notes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use eyesight_xml::nodes::{
+        INode, NodeInputValue, RoundingEdgeNormal, SwitchClosure, SwitchFloat, Vec3, VectorMath,
+    };
+    use eyesight_xml::schema::Link;
+
+    use super::*;
+    use crate::blender::BlenderVersion;
+    use crate::{custom_groups, mapping};
+
+    impl Value {
+        fn from_input(value: &NodeInputValue) -> Self {
+            match *value {
+                NodeInputValue::Float(f) => Self::Float(f),
+                NodeInputValue::Int(i) => Self::Float(i as f32),
+                NodeInputValue::Boolean(b) => Self::Float(factor(b)),
+                NodeInputValue::Vector(Vec3(v)) | NodeInputValue::Color(Vec3(v)) => Self::Vector(v),
+            }
+        }
+
+        /// A made-up value for a linked socket. `probe` picks one of a few.
+        fn probe(data_type: SocketType, socket: &str, probe: usize) -> Self {
+            const FLOATS: [f32; 3] = [0.25, -3.0, 0.75];
+            const VECTORS: [[f32; 3]; 3] = [[0.1, 0.2, 0.3], [-1.0, 4.0, 0.5], [2.0, 0.0, -0.25]];
+            match data_type {
+                SocketType::Float | SocketType::Int | SocketType::Boolean => {
+                    Self::Float(FLOATS[probe])
+                }
+                SocketType::Vector | SocketType::Color => Self::Vector(VECTORS[probe]),
+                SocketType::Closure => Self::Closure([(socket.to_owned(), 1.0)].into()),
+            }
+        }
+
+        fn approx_eq(&self, other: &Self) -> bool {
+            let close = |a: f32, b: f32| (a - b).abs() <= 1e-5 * a.abs().max(b.abs()).max(1.0);
+            match (self, other) {
+                (Self::Float(a), Self::Float(b)) => close(*a, *b),
+                (Self::Vector(a), Self::Vector(b)) => (0..3).all(|i| close(a[i], b[i])),
+                (Self::Closure(a), Self::Closure(b)) => {
+                    let weight =
+                        |c: &BTreeMap<String, f32>, k: &str| c.get(k).copied().unwrap_or(0.0);
+                    a.keys()
+                        .chain(b.keys())
+                        .all(|k| close(weight(a, k), weight(b, k)))
+                }
+                _ => false,
+            }
+        }
+    }
+
+    /// What a standard node outputs, given its inputs by Blender socket name.
+    /// Only covers the nodes that polyfills are replaced by.
+    fn evaluate_blender(node: &Node, inputs: &Inputs) -> Value {
+        let mix = |f: f32, a: f32, b: f32| a + (b - a) * f;
+        match node {
+            Node::MixValue(m) => {
+                let mut f = inputs.float("Factor");
+                if m.use_clamp {
+                    f = f.clamp(0.0, 1.0);
+                }
+                let result = mix(f, inputs.float("A"), inputs.float("B"));
+                Value::Float(if m.use_clamp {
+                    result.clamp(0.0, 1.0)
+                } else {
+                    result
+                })
+            }
+            // `clamp_factor` is left at Blender's default, which is on.
+            Node::MixVector(_) => {
+                let f = inputs.float("Factor").clamp(0.0, 1.0);
+                let [a, b] = [inputs.vector("A"), inputs.vector("B")];
+                Value::Vector(std::array::from_fn(|i| mix(f, a[i], b[i])))
+            }
+            Node::MixClosure(_) => {
+                let f = inputs.float("Fac").clamp(0.0, 1.0);
+                let mut closure = BTreeMap::<String, f32>::new();
+                for (socket, weight) in [("1", 1.0 - f), ("2", f)] {
+                    for (k, w) in inputs.closure(socket) {
+                        *closure.entry(k).or_default() += w * weight;
+                    }
+                }
+                Value::Closure(closure)
+            }
+            _ => panic!("can't evaluate a {} node", node.kind()),
+        }
+    }
+
+    /// Panics unless `replaced` outputs what `node` does. `linked` are the sockets of
+    /// `node` that something is linked to.
+    fn check(
+        replacement: &Replacement,
+        node: &Node,
+        replaced: &Node,
+        linked: &BTreeSet<&str>,
+        version: BlenderVersion,
+    ) {
+        let mapping = mapping::get();
+        let type_name = mapping.node_type(replaced, version);
+
+        for probe in 0..3 {
+            let linked_values = replacement
+                .inputs
+                .iter()
+                .filter(|(name, _)| linked.contains(name))
+                .map(|&(name, data_type)| (name, Value::probe(data_type, name, probe)))
+                .collect::<Vec<_>>();
+
+            let values = |node: &Node, alias: &dyn Fn(&str) -> String| {
+                let literal = node
+                    .inputs_override()
+                    .into_iter()
+                    .map(|i| (alias(&i.name), Value::from_input(&i.value)));
+                let linked = linked_values
+                    .iter()
+                    .map(|(name, value)| (alias(name), value.clone()));
+                Inputs(literal.chain(linked).collect())
+            };
+
+            let expected = (replacement.evaluate)(node, &values(node, &|s| s.to_owned()));
+            let actual = evaluate_blender(
+                replaced,
+                &values(replaced, &|s| {
+                    mapping
+                        .input_alias(version, type_name, s)
+                        .unwrap_or(s)
+                        .to_owned()
+                }),
+            );
+
+            if !expected.approx_eq(&actual) {
+                panic!(
+                    "{}: the {} polyfill gives {actual:?} where Eyesight gives {expected:?}",
+                    node.name(),
+                    node.kind()
+                );
+            }
+        }
+    }
+
+    const VERSIONS: [BlenderVersion; 3] = [
+        BlenderVersion::V3_6,
+        BlenderVersion::V4_0,
+        BlenderVersion::V4_2,
+    ];
+
+    /// Checks the replacement of `node` with every combination of its inputs linked.
+    fn check_replacement(node: Node) {
+        let replacement = replacement(&node).expect("the node should be replaced");
+        let replaced = (replacement.replace)(&node);
+        let sockets = replacement.inputs.iter().map(|&(name, _)| name);
+        for mask in 0..1 << replacement.inputs.len() {
+            let linked = sockets
+                .clone()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, name)| name)
+                .collect();
+            for version in VERSIONS {
+                check(replacement, &node, &replaced, &linked, version);
+            }
+        }
+    }
+
+    /// Nodes of a kind that a polyfill replaces or sets up, to check it with.
+    fn samples(kind: &str) -> Vec<Node> {
+        match kind {
+            "VectorMath" => vec![Node::VectorMath(VectorMath {
+                name: "avg".into(),
+                operation: VectorOperation::Average,
+                inputs: vec![NodeInput::new(
+                    "Vector1",
+                    NodeInputValue::Vector(Vec3([1.0, 0.0, -2.0])),
+                )],
+                span: Default::default(),
+            })],
+            "SwitchFloat" => [false, true]
+                .map(|enable| {
+                    Node::SwitchFloat(SwitchFloat {
+                        name: "switch".into(),
+                        enable,
+                        inputs: vec![NodeInput::new("ValueDisable", 0.2)],
+                        span: Default::default(),
+                    })
+                })
+                .into(),
+            "SwitchClosure" => [false, true]
+                .map(|enable| {
+                    Node::SwitchClosure(SwitchClosure {
+                        name: "switch".into(),
+                        enable,
+                        span: Default::default(),
+                    })
+                })
+                .into(),
+            "RoundingEdgeNormal" => vec![Node::RoundingEdgeNormal(RoundingEdgeNormal {
+                name: "bevel".into(),
+                enable: false,
+                inputs: vec![NodeInput::new("Samples", 8.0)],
+                span: Default::default(),
+            })],
+            _ => panic!("no {kind} nodes to check its polyfill with"),
+        }
+    }
+
+    #[test]
+    fn every_polyfill_holds() {
+        for polyfill in POLYFILLS {
+            match &polyfill.conversion {
+                Conversion::Replace(_) => {
+                    for node in samples(polyfill.node_kind) {
+                        check_replacement(node);
+                    }
+                }
+                Conversion::Attributes => {
+                    for node in samples(polyfill.node_kind) {
+                        assert!(replacement(&node).is_none());
+                        assert!(custom_groups::for_node(&node).is_none());
+                        for version in VERSIONS {
+                            let node_type = mapping::get().node_type(&node, version);
+                            assert!(version.sockets(node_type).is_some(), "{node_type}");
+                        }
+                    }
+                }
+                Conversion::CustomGroup(group) => {
+                    let custom = custom_groups::get(group).expect("the custom group should exist");
+                    assert_eq!(custom.node_kind, Some(polyfill.node_kind));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn only_averages_are_replaced() {
+        let add = Node::VectorMath(VectorMath {
+            name: "add".into(),
+            operation: VectorOperation::Add,
+            inputs: vec![],
+            span: Default::default(),
+        });
+        assert!(replacement(&add).is_none());
+    }
+
+    #[test]
+    fn links_to_unread_inputs_are_dropped() {
+        let mut shader = Shader {
+            nodes: vec![Node::VectorMath(VectorMath {
+                name: "avg".into(),
+                operation: VectorOperation::Average,
+                inputs: vec![],
+                span: Default::default(),
+            })],
+            links: vec![
+                Link::new("a", "Vector", "avg", "Vector1"),
+                Link::new("b", "Value", "avg", "Scale"),
+            ],
+        };
+        let mut notes = vec![];
+        assert_eq!(apply(&mut shader, "G", &mut notes), 1);
+        assert!(matches!(shader.nodes[0], Node::MixVector(_)));
+        assert_eq!(
+            shader.links,
+            vec![Link::new("a", "Vector", "avg", "Vector1")]
+        );
+        assert_eq!(notes.len(), 1);
+        assert!(notes[0].starts_with("G / avg:"), "{}", notes[0]);
+    }
+}
@@ -0,0 +1,665 @@
+//! Conversion of Eyesight's Principled BSDF parameters to Blender 4's Principled BSDF.
+//!
+//! Eyesight's parameters are those of the original Principled BSDF (Blender 3.x, "v1").
+//! Blender 4.0 replaced it with a new model ("v2"), and several parameters changed meaning,
+//! not just name. Each v2 value is computed from the v1 values as follows,
+//! where a missing v1 input takes its Blender 3.6 default:
+//!
+//! - `Specular IOR Level = Specular · 0.04 / F0(IOR)`, with `F0(n) = ((n − 1) / (n + 1))²`.
+//!   v1 reflected `0.08 · Specular` at normal incidence regardless of IOR;
+//!   v2 reflects `2 · F0(IOR) · Specular IOR Level`. `IOR` itself is carried over.
+//! - `Specular Tint = mix(white, tint(Base Color), Specular Tint)`, where
+//!   `tint(c) = c / lum(c)` is the hue of `c` at unit Rec. 709 luminance (white if `lum(c) = 0`).
+//!   v1's tint was a float blending towards that hue; v2's is the color itself.
+//! - `Sheen Weight = Sheen` and `Sheen Tint = mix(white, tint(Base Color), Sheen Tint)`.
+//!   `Sheen Roughness` is set to 0.5, the closest match for v1's fixed sheen lobe.
+//! - `Coat Weight = 0.25 · Clearcoat` and `Coat IOR = 1.5`:
+//!   v1 scaled its clearcoat lobe by a quarter internally and used a fixed F0 of 0.04.
+//!   `Clearcoat Roughness` and `Clearcoat Normal` become `Coat Roughness` and `Coat Normal`.
+//! - `Subsurface Weight = (Subsurface > 0)`, `Subsurface Scale = Subsurface` and
+//!   `Base Color = mix(Base Color, Subsurface Color, Subsurface)`:
+//!   v1's single parameter both blended towards `Subsurface Color` and scaled the radius.
+//! - `Transmission Weight = Transmission`. v2 has no separate transmission roughness, so
+//!   `Roughness = mix(Roughness, Transmission Roughness, Transmission)` approximates it.
+//! - `Emission Color = Emission`. `Emission Strength` defaults to 1 as it did in v1
+//!   (v2 defaults to 0).
+//!
+//! Constant inputs are folded into new constants. When an input is linked,
+//! the same formula is built out of math nodes placed in front of the BSDF.
+
+use std::collections::BTreeMap;
+
+use crate::blender::BlenderVersion;
+use crate::mapping;
+use eyesight_xml::nodes::{
+    Math, MathOperation, Mix, MixOperation, MixType, MixValue, Node, NodeInput, NodeInputValue,
+    Vec3, VectorMath, VectorOperation,
+};
+use eyesight_xml::schema::{Link, Shader};
+use eyesight_xml::span::Span;
+use eyesight_xml::{Named, Spanned};
+
+const PYTHON_TYPE: &str = "ShaderNodeBsdfPrincipled";
+
+const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// Rewrites every Principled BSDF in the shader to use v2 parameters,
+/// and returns how many there were.
+pub fn convert_to_v2(shader: &mut Shader) -> usize {
+    let bsdfs = shader
+        .nodes
+        .iter()
+        .filter(|n| matches!(n, Node::PrincipledBsdf(_)))
+        .map(|n| n.name().to_owned())
+        .collect::<Vec<_>>();
+
+    let count = bsdfs.len();
+    for bsdf in bsdfs {
+        Converter::new(shader, bsdf).convert();
+    }
+    count
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Float(f32),
+    Color([f32; 3]),
+    Vector([f32; 3]),
+    /// An output socket, as `(node, socket)`.
+    Socket(String, String),
+}
+
+impl From<NodeInputValue> for Operand {
+    fn from(value: NodeInputValue) -> Self {
+        match value {
+            NodeInputValue::Float(n) => Self::Float(n),
+            NodeInputValue::Int(n) => Self::Float(n as f32),
+            NodeInputValue::Boolean(b) => Self::Float(b.into()),
+            NodeInputValue::Vector(Vec3(v)) => Self::Vector(v),
+            NodeInputValue::Color(Vec3(v)) => Self::Color(v),
+        }
+    }
+}
+
+impl Operand {
+    /// The value as a constant color, if it is one.
+    fn rgb(&self) -> Option<[f32; 3]> {
+        match self {
+            Self::Float(n) => Some([*n; 3]),
+            Self::Color(c) | Self::Vector(c) => Some(*c),
+            Self::Socket(..) => None,
+        }
+    }
+}
+
+struct Converter<'a> {
+    shader: &'a mut Shader,
+    bsdf: String,
+    /// Where the BSDF was defined, which is where the nodes added for it are from.
+    span: Span,
+    /// The BSDF's v1 inputs, keyed by their Blender 3.6 names.
+    v1: BTreeMap<String, Operand>,
+    /// The BSDF's v2 inputs, keyed by their Blender 4 names.
+    v2: BTreeMap<String, Operand>,
+}
+
+impl<'a> Converter<'a> {
+    /// Takes all inputs and incoming links away from the BSDF.
+    fn new(shader: &'a mut Shader, bsdf: String) -> Self {
+        let v1_name = |name: &str| {
+            mapping::get()
+                .input_alias(BlenderVersion::V3_6, PYTHON_TYPE, name)
+                .unwrap_or(name)
+                .to_owned()
+        };
+
+        let mut v1 = BTreeMap::new();
+
+        let node = shader.nodes.iter_mut().find(|n| n.name() == bsdf).unwrap();
+        let Node::PrincipledBsdf(node) = node else {
+            unreachable!()
+        };
+        let span = node.span.clone();
+        for input in std::mem::take(&mut node.inputs) {
+            v1.insert(v1_name(&input.name), input.value.into());
+        }
+
+        shader.links.retain(|link| {
+            if link.to_node != bsdf {
+                return true;
+            }
+            let operand = Operand::Socket(link.from_node.clone(), link.from_socket.clone());
+            v1.insert(v1_name(&link.to_socket), operand);
+            false
+        });
+
+        Self {
+            shader,
+            bsdf,
+            span,
+            v1,
+            v2: BTreeMap::new(),
+        }
+    }
+
+    fn take(&mut self, name: &str) -> Option<Operand> {
+        self.v1.remove(name)
+    }
+
+    fn take_or(&mut self, name: &str, default: Operand) -> Operand {
+        self.take(name).unwrap_or(default)
+    }
+
+    fn set(&mut self, name: &str, value: Operand) {
+        self.v2.insert(name.to_owned(), value);
+    }
+
+    fn convert(mut self) {
+        let base_color = self.take_or("Base Color", Operand::Color([0.8, 0.8, 0.8]));
+        let ior = self.take_or("IOR", Operand::Float(1.45));
+
+        let specular = self.take_or("Specular", Operand::Float(0.5));
+        let f0 = self.fresnel_f0(ior.clone(), "f0");
+        let scale = self.math(
+            MathOperation::Divide,
+            Operand::Float(0.04),
+            f0,
+            "specular_scale",
+        );
+        let level = self.math(MathOperation::Multiply, specular, scale, "specular_level");
+        self.set("Specular IOR Level", level);
+        self.set("IOR", ior);
+
+        if let Some(tint) = self.take("Specular Tint") {
+            let hue = self.tint(base_color.clone(), "specular");
+            let tint = self.mix_color(tint, Operand::Color(WHITE), hue, "specular_tint");
+            self.set("Specular Tint", tint);
+        }
+
+        if let Some(sheen) = self.take("Sheen") {
+            let tint = self.take_or("Sheen Tint", Operand::Float(0.5));
+            let hue = self.tint(base_color.clone(), "sheen");
+            let tint = self.mix_color(tint, Operand::Color(WHITE), hue, "sheen_tint");
+            self.set("Sheen Weight", sheen);
+            self.set("Sheen Tint", tint);
+            self.set("Sheen Roughness", Operand::Float(0.5));
+        }
+        self.take("Sheen Tint");
+
+        if let Some(clearcoat) = self.take("Clearcoat") {
+            let weight = self.math(
+                MathOperation::Multiply,
+                clearcoat,
+                Operand::Float(0.25),
+                "coat",
+            );
+            self.set("Coat Weight", weight);
+            self.set("Coat IOR", Operand::Float(1.5));
+        }
+        if let Some(roughness) = self.take("Clearcoat Roughness") {
+            self.set("Coat Roughness", roughness);
+        }
+        if let Some(normal) = self.take("Clearcoat Normal") {
+            self.set("Coat Normal", normal);
+        }
+
+        let mut base_color = base_color;
+        if let Some(subsurface) = self.take("Subsurface") {
+            let weight = self.math(
+                MathOperation::GreaterThan,
+                subsurface.clone(),
+                Operand::Float(0.0),
+                "subsurface_weight",
+            );
+            self.set("Subsurface Weight", weight);
+            self.set("Subsurface Scale", subsurface.clone());
+            if let Some(color) = self.take("Subsurface Color") {
+                base_color = self.mix_color(subsurface, base_color, color, "subsurface_color");
+            }
+        }
+        self.take("Subsurface Color");
+        self.set("Base Color", base_color);
+
+        let mut roughness = self.take("Roughness");
+        if let Some(transmission) = self.take("Transmission") {
+            if let Some(transmission_roughness) = self.take("Transmission Roughness") {
+                roughness = Some(self.mix_float(
+                    transmission.clone(),
+                    roughness.unwrap_or(Operand::Float(0.5)),
+                    transmission_roughness,
+                    "roughness",
+                ));
+            }
+            self.set("Transmission Weight", transmission);
+        }
+        self.take("Transmission Roughness");
+        if let Some(roughness) = roughness {
+            self.set("Roughness", roughness);
+        }
+
+        if let Some(emission) = self.take("Emission") {
+            let strength = self.take_or("Emission Strength", Operand::Float(1.0));
+            self.set("Emission Color", emission);
+            self.set("Emission Strength", strength);
+        } else if self.v1.contains_key("Emission Strength") {
+            // v1's emission color defaults to black, but v2's is white.
+            self.set("Emission Color", Operand::Color([0.0, 0.0, 0.0]));
+        }
+
+        // Everything else (Metallic, Alpha, Normal, Anisotropic, ...) is unchanged.
+        let rest = std::mem::take(&mut self.v1);
+        self.v2.extend(rest);
+
+        self.finish();
+    }
+
+    /// Puts the v2 inputs back on the BSDF, as literal inputs or links.
+    fn finish(self) {
+        let mut inputs = vec![];
+        for (name, operand) in self.v2 {
+            let value = match operand {
+                Operand::Float(n) => NodeInputValue::Float(n),
+                Operand::Color(v) | Operand::Vector(v) if name == "Subsurface Radius" => {
+                    NodeInputValue::Vector(Vec3(v))
+                }
+                Operand::Color(v) | Operand::Vector(v) => NodeInputValue::Color(Vec3(v)),
+                Operand::Socket(node, socket) => {
+                    let link = Link::new(&node, &socket, &self.bsdf, &name);
+                    self.shader.links.push(link);
+                    continue;
+                }
+            };
+            inputs.push(NodeInput::new(&name, value));
+        }
+
+        let node = self.shader.nodes.iter_mut().find(|n| n.name() == self.bsdf);
+        let Some(Node::PrincipledBsdf(node)) = node else {
+            unreachable!()
+        };
+        node.inputs = inputs;
+    }
+
+    /// Adds a node whose inputs are `operands`, linking the sockets and setting the constants.
+    fn add_node(&mut self, node: Node, operands: Vec<(&str, Operand)>) -> String {
+        let mut node = node;
+        let name = self.fresh_name(node.name());
+        *node.name_mut() = name.clone();
+        *node.span_mut() = self.span.clone();
+
+        let mut literals = vec![];
+        for (socket, operand) in operands {
+            match operand {
+                Operand::Float(n) => literals.push(NodeInput::new(socket, n)),
+                Operand::Color(v) => {
+                    literals.push(NodeInput::new(socket, NodeInputValue::Color(Vec3(v))))
+                }
+                Operand::Vector(v) => {
+                    literals.push(NodeInput::new(socket, NodeInputValue::Vector(Vec3(v))))
+                }
+                Operand::Socket(from, from_socket) => {
+                    let link = Link::new(&from, &from_socket, &name, socket);
+                    self.shader.links.push(link);
+                }
+            }
+        }
+
+        match &mut node {
+            Node::Math(n) => n.inputs = literals,
+            Node::MixValue(n) => n.inputs = literals,
+            Node::Mix(n) => n.inputs = literals,
+            Node::VectorMath(n) => n.inputs = literals,
+            _ => unreachable!("{} can't be inserted", node.kind()),
+        }
+
+        self.shader.nodes.push(node);
+        name
+    }
+
+    fn fresh_name(&self, label: &str) -> String {
+        let base = format!("{}_{label}", self.bsdf);
+        let taken = |name: &str| self.shader.nodes.iter().any(|n| n.name() == name);
+        if !taken(&base) {
+            return base;
+        }
+        (2..)
+            .map(|n| format!("{base}_{n}"))
+            .find(|name| !taken(name))
+            .unwrap()
+    }
+
+    fn math(&mut self, operation: MathOperation, a: Operand, b: Operand, label: &str) -> Operand {
+        if let (Operand::Float(a), Operand::Float(b)) = (&a, &b) {
+            return Operand::Float(fold(operation, *a, *b));
+        }
+        let node = Node::Math(Math {
+            name: label.into(),
+            operation,
+            use_clamp: false,
+            inputs: vec![],
+            span: Span::default(),
+        });
+        let name = self.add_node(node, vec![("Value1", a), ("Value2", b)]);
+        Operand::Socket(name, "Value".into())
+    }
+
+    fn mix_float(&mut self, factor: Operand, a: Operand, b: Operand, label: &str) -> Operand {
+        if let (Operand::Float(f), Operand::Float(a), Operand::Float(b)) = (&factor, &a, &b) {
+            return Operand::Float(a + (b - a) * f);
+        }
+        let node = Node::MixValue(MixValue {
+            name: label.into(),
+            mix_type: MixType::Mix,
+            use_clamp: false,
+            inputs: vec![],
+            span: Span::default(),
+        });
+        let name = self.add_node(node, vec![("Fac", factor), ("Value1", a), ("Value2", b)]);
+        Operand::Socket(name, "Value".into())
+    }
+
+    fn mix_color(&mut self, factor: Operand, a: Operand, b: Operand, label: &str) -> Operand {
+        if let (Operand::Float(f), Some(a), Some(b)) = (&factor, a.rgb(), b.rgb()) {
+            return Operand::Color(std::array::from_fn(|i| a[i] + (b[i] - a[i]) * f));
+        }
+        let node = Node::Mix(Mix {
+            name: label.into(),
+            operation: MixOperation::Mix,
+            use_clamp: false,
+            inputs: vec![],
+            span: Span::default(),
+        });
+        let name = self.add_node(node, vec![("Fac", factor), ("Color1", a), ("Color2", b)]);
+        Operand::Socket(name, "Color".into())
+    }
+
+    /// `((n − 1) / (n + 1))²`
+    fn fresnel_f0(&mut self, ior: Operand, label: &str) -> Operand {
+        let one = || Operand::Float(1.0);
+        let name = |suffix: &str| format!("{label}_{suffix}");
+        let below = self.math(MathOperation::Subtract, ior.clone(), one(), &name("below"));
+        let above = self.math(MathOperation::Add, ior, one(), &name("above"));
+        let ratio = self.math(MathOperation::Divide, below, above, &name("ratio"));
+        self.math(MathOperation::Multiply, ratio.clone(), ratio, label)
+    }
+
+    /// `c / lum(c)`, or white for black.
+    fn tint(&mut self, color: Operand, label: &str) -> Operand {
+        let name = |suffix: &str| format!("{label}_{suffix}");
+        match color {
+            Operand::Float(n) => self.tint(Operand::Color([n; 3]), label),
+            Operand::Color(c) | Operand::Vector(c) => {
+                let lum = dot(c, LUMINANCE);
+                if lum > 0.0 {
+                    Operand::Color(c.map(|x| x / lum))
+                } else {
+                    Operand::Color(WHITE)
+                }
+            }
+            socket @ Operand::Socket(..) => {
+                let dot_product = Node::VectorMath(VectorMath {
+                    name: name("luminance"),
+                    operation: VectorOperation::DotProduct,
+                    inputs: vec![],
+                    span: Span::default(),
+                });
+                let lum = self.add_node(
+                    dot_product,
+                    vec![
+                        ("Vector1", socket.clone()),
+                        ("Vector2", Operand::Vector(LUMINANCE)),
+                    ],
+                );
+                let lum = Operand::Socket(lum, "Value".into());
+                let lum = self.math(
+                    MathOperation::Maximum,
+                    lum,
+                    Operand::Float(1e-6),
+                    &name("luminance_nonzero"),
+                );
+                let inverse = self.math(
+                    MathOperation::Divide,
+                    Operand::Float(1.0),
+                    lum,
+                    &name("luminance_inverse"),
+                );
+
+                let scale = Node::VectorMath(VectorMath {
+                    name: name("hue"),
+                    operation: VectorOperation::Scale,
+                    inputs: vec![],
+                    span: Span::default(),
+                });
+                let hue = self.add_node(scale, vec![("Vector1", socket), ("Scale", inverse)]);
+                Operand::Socket(hue, "Vector".into())
+            }
+        }
+    }
+}
+
+fn fold(operation: MathOperation, a: f32, b: f32) -> f32 {
+    match operation {
+        MathOperation::Add => a + b,
+        MathOperation::Subtract => a - b,
+        MathOperation::Multiply => a * b,
+        MathOperation::Divide if b == 0.0 => 0.0,
+        MathOperation::Divide => a / b,
+        MathOperation::Floor => a.floor(),
+        MathOperation::Minimum => a.min(b),
+        MathOperation::Maximum => a.max(b),
+        MathOperation::LessThan => (a < b).into(),
+        MathOperation::GreaterThan => (a > b).into(),
+        MathOperation::Power => a.powf(b),
+    }
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| a * b).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use eyesight_xml::nodes::{BsdfDistribution, PrincipledBsdf};
+
+    use super::*;
+
+    /// A shader with just a Principled BSDF called "bsdf", converted, with `links`
+    /// into it.
+    fn convert(inputs: Vec<NodeInput>, links: Vec<Link>) -> Shader {
+        let mut shader = Shader {
+            nodes: vec![Node::PrincipledBsdf(PrincipledBsdf {
+                name: "bsdf".into(),
+                distribution: BsdfDistribution::Ggx,
+                subsurface_method: None,
+                inputs,
+                span: Span::default(),
+            })],
+            links,
+        };
+        assert_eq!(convert_to_v2(&mut shader), 1);
+        shader
+    }
+
+    fn input(shader: &Shader, name: &str) -> NodeInputValue {
+        let Node::PrincipledBsdf(bsdf) = &shader.nodes[0] else {
+            unreachable!()
+        };
+        let input = bsdf.inputs.iter().find(|i| i.name == name);
+        input.unwrap_or_else(|| panic!("no {name} input")).value
+    }
+
+    fn float(shader: &Shader, name: &str) -> f32 {
+        match input(shader, name) {
+            NodeInputValue::Float(n) => n,
+            value => panic!("{name} is {value:?}"),
+        }
+    }
+
+    fn color(shader: &Shader, name: &str) -> [f32; 3] {
+        match input(shader, name) {
+            NodeInputValue::Color(Vec3(c)) => c,
+            value => panic!("{name} is {value:?}"),
+        }
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        let close = a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5);
+        assert!(close, "{a:?} != {b:?}");
+    }
+
+    fn color_input(name: &str, c: [f32; 3]) -> NodeInput {
+        NodeInput::new(name, NodeInputValue::Color(Vec3(c)))
+    }
+
+    #[test]
+    fn specular_becomes_specular_ior_level() {
+        // An IOR of 1.5 has an F0 of 0.04, which is what v1 assumed.
+        let shader = convert(
+            vec![NodeInput::new("Specular", 0.3), NodeInput::new("IOR", 1.5)],
+            vec![],
+        );
+        assert_close(&[float(&shader, "Specular IOR Level")], &[0.3]);
+        assert_close(&[float(&shader, "IOR")], &[1.5]);
+
+        // The default IOR of 1.45 reflects less, so the level makes up for it.
+        let shader = convert(vec![NodeInput::new("Specular", 0.5)], vec![]);
+        let f0 = (0.45f32 / 2.45).powi(2);
+        assert_close(&[float(&shader, "Specular IOR Level")], &[0.5 * 0.04 / f0]);
+        assert_eq!(shader.nodes.len(), 1);
+    }
+
+    #[test]
+    fn specular_tint_blends_towards_the_base_hue() {
+        let base = [0.5, 0.25, 0.25];
+        let shader = convert(
+            vec![
+                color_input("BaseColor", base),
+                NodeInput::new("SpecularTint", 0.5),
+            ],
+            vec![],
+        );
+        let lum = 0.5 * 0.2126 + 0.25 * 0.7152 + 0.25 * 0.0722;
+        let expected = base.map(|c| 1.0 + (c / lum - 1.0) * 0.5);
+        assert_close(&color(&shader, "Specular Tint"), &expected);
+        assert_close(&color(&shader, "Base Color"), &base);
+    }
+
+    #[test]
+    fn coat_weight_is_a_quarter_of_clearcoat() {
+        let shader = convert(
+            vec![
+                NodeInput::new("Clearcoat", 0.8),
+                NodeInput::new("ClearcoatRoughness", 0.1),
+            ],
+            vec![],
+        );
+        assert_close(&[float(&shader, "Coat Weight")], &[0.2]);
+        assert_close(&[float(&shader, "Coat IOR")], &[1.5]);
+        assert_close(&[float(&shader, "Coat Roughness")], &[0.1]);
+    }
+
+    #[test]
+    fn subsurface_is_split_into_color_and_radius() {
+        let radius = [1.0, 0.2, 0.1];
+        let shader = convert(
+            vec![
+                color_input("BaseColor", [0.0, 0.0, 1.0]),
+                NodeInput::new("Subsurface", 0.25),
+                color_input("SubsurfaceColor", [1.0, 0.0, 0.0]),
+                NodeInput::new("SubsurfaceRadius", NodeInputValue::Vector(Vec3(radius))),
+            ],
+            vec![],
+        );
+        assert_close(&[float(&shader, "Subsurface Weight")], &[1.0]);
+        assert_close(&[float(&shader, "Subsurface Scale")], &[0.25]);
+        assert_close(&color(&shader, "Base Color"), &[0.25, 0.0, 0.75]);
+        assert_eq!(
+            input(&shader, "Subsurface Radius"),
+            NodeInputValue::Vector(Vec3(radius))
+        );
+    }
+
+    #[test]
+    fn transmission_roughness_is_mixed_into_roughness() {
+        let shader = convert(
+            vec![
+                NodeInput::new("Roughness", 0.2),
+                NodeInput::new("Transmission", 0.5),
+                NodeInput::new("TransmissionRoughness", 0.6),
+            ],
+            vec![],
+        );
+        assert_close(&[float(&shader, "Roughness")], &[0.4]);
+        assert_close(&[float(&shader, "Transmission Weight")], &[0.5]);
+    }
+
+    #[test]
+    fn emission_strength_alone_stays_dark() {
+        let shader = convert(vec![NodeInput::new("EmissionStrength", 2.0)], vec![]);
+        assert_close(&color(&shader, "Emission Color"), &[0.0, 0.0, 0.0]);
+        assert_close(&[float(&shader, "Emission Strength")], &[2.0]);
+
+        let red = [1.0, 0.0, 0.0];
+        let shader = convert(vec![color_input("Emission", red)], vec![]);
+        assert_close(&color(&shader, "Emission Color"), &red);
+        assert_close(&[float(&shader, "Emission Strength")], &[1.0]);
+    }
+
+    #[test]
+    fn linked_inputs_get_nodes_in_front_of_the_bsdf() {
+        let shader = convert(
+            vec![NodeInput::new("SpecularTint", 1.0)],
+            vec![
+                Link::new("tex", "Fac", "bsdf", "Specular"),
+                Link::new("tex", "Color", "bsdf", "BaseColor"),
+            ],
+        );
+
+        // Specular is scaled by a math node fed by the texture.
+        let level = shader
+            .nodes
+            .iter()
+            .find(|n| n.name() == "bsdf_specular_level")
+            .unwrap();
+        let Node::Math(math) = level else {
+            panic!("{level:?}")
+        };
+        assert_eq!(math.operation, MathOperation::Multiply);
+        let f0 = (0.45f32 / 2.45).powi(2);
+        let NodeInputValue::Float(scale) = math.inputs[0].value else {
+            panic!("{:?}", math.inputs)
+        };
+        assert_eq!(math.inputs[0].name, "Value2");
+        assert_close(&[scale], &[0.04 / f0]);
+
+        let has_link = |from: &str, from_socket: &str, to: &str, to_socket: &str| {
+            shader
+                .links
+                .contains(&Link::new(from, from_socket, to, to_socket))
+        };
+        assert!(has_link("tex", "Fac", "bsdf_specular_level", "Value1"));
+        assert!(has_link(
+            "bsdf_specular_level",
+            "Value",
+            "bsdf",
+            "Specular IOR Level"
+        ));
+
+        // The tint needs the linked base color's hue, worked out by vector math.
+        assert!(has_link(
+            "tex",
+            "Color",
+            "bsdf_specular_luminance",
+            "Vector1"
+        ));
+        assert!(has_link("tex", "Color", "bsdf", "Base Color"));
+        assert!(has_link(
+            "bsdf_specular_tint",
+            "Color",
+            "bsdf",
+            "Specular Tint"
+        ));
+        assert!(!shader.links.iter().any(|l| l.to_node == "bsdf"
+            && ["Specular", "BaseColor", "SpecularTint"].contains(&&*l.to_socket)));
+    }
+}
@@ -0,0 +1,411 @@
+//! A small typed model of the Python we generate, and a deterministic printer for it.
+//!
+//! The printer follows the parts of black's style that matter for our output:
+//! four-space indentation, an 88 column limit, two blank lines around top-level functions,
+//! and brackets that are split one element per line (with a trailing comma) only when
+//! the contents don't fit on a single indented line.
+
+use std::fmt::Write;
+
+const LINE_WIDTH: usize = 88;
+const INDENT: &str = "    ";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// An identifier or dotted path, emitted verbatim.
+    Name(String),
+    /// An atom that is already valid Python, such as a number or an enum string.
+    Raw(String),
+    Str(String),
+    Int(i64),
+    Attribute(Box<Expr>, String),
+    Subscript(Box<Expr>, Box<Expr>),
+    Call(Box<Expr>, Vec<Arg>),
+    Tuple(Vec<Expr>),
+    Dict(Vec<(Expr, Expr)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Positional(Expr),
+    Keyword(String, Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Expr(Expr),
+    Assign(Expr, Expr),
+    Return(Option<Expr>),
+    If(Expr, Vec<Stmt>),
+    Def(FunctionDef),
+    /// Verbatim source lines, indented to the current block.
+    Raw(Vec<String>),
+    Blank,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionDef {
+    pub name: String,
+    /// Parameters, already including any annotations.
+    pub params: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Module {
+    pub body: Vec<Stmt>,
+}
+
+impl Expr {
+    pub fn name(s: impl Into<String>) -> Self {
+        Self::Name(s.into())
+    }
+
+    pub fn raw(s: impl Into<String>) -> Self {
+        Self::Raw(s.into())
+    }
+
+    pub fn str(s: impl Into<String>) -> Self {
+        Self::Str(s.into())
+    }
+
+    pub fn attr(self, name: &str) -> Self {
+        Self::Attribute(Box::new(self), name.into())
+    }
+
+    pub fn index(self, key: Expr) -> Self {
+        Self::Subscript(Box::new(self), Box::new(key))
+    }
+
+    pub fn call(self, args: Vec<Arg>) -> Self {
+        Self::Call(Box::new(self), args)
+    }
+
+    fn flat(&self) -> String {
+        match self {
+            Self::Name(s) | Self::Raw(s) => s.clone(),
+            Self::Str(s) => string_literal(s),
+            Self::Int(n) => n.to_string(),
+            Self::Attribute(value, name) => format!("{}.{name}", value.flat()),
+            Self::Subscript(value, key) => format!("{}[{}]", value.flat(), key.flat()),
+            _ => {
+                let (open, items, close) = self.bracketed().unwrap();
+                let mut s = open;
+                s += &items.iter().map(Item::flat).collect::<Vec<_>>().join(", ");
+                if matches!(self, Self::Tuple(v) if v.len() == 1) {
+                    s += ",";
+                }
+                s + close
+            }
+        }
+    }
+
+    /// Splits an expression into its opening bracket (including anything before it),
+    /// its elements, and its closing bracket.
+    fn bracketed(&self) -> Option<(String, Vec<Item<'_>>, &'static str)> {
+        Some(match self {
+            Self::Call(func, args) => {
+                let items = args
+                    .iter()
+                    .map(|arg| match arg {
+                        Arg::Positional(e) => Item::new("", e),
+                        Arg::Keyword(k, e) => Item::new(format!("{k}="), e),
+                    })
+                    .collect();
+                (func.flat() + "(", items, ")")
+            }
+            Self::Tuple(v) => (
+                "(".into(),
+                v.iter().map(|e| Item::new("", e)).collect(),
+                ")",
+            ),
+            Self::Dict(v) => {
+                let items = v
+                    .iter()
+                    .map(|(k, e)| Item::new(format!("{}: ", k.flat()), e))
+                    .collect();
+                ("{".into(), items, "}")
+            }
+            _ => return None,
+        })
+    }
+
+    /// Lays the expression out as one or more lines at the given indentation level,
+    /// with `prefix` and `suffix` attached to its first and last lines.
+    fn layout(&self, prefix: &str, suffix: &str, level: usize, out: &mut Vec<String>) {
+        let indent = INDENT.repeat(level);
+        let flat = format!("{indent}{prefix}{}{suffix}", self.flat());
+        let Some((open, items, close)) = self.bracketed() else {
+            out.push(flat);
+            return;
+        };
+        if fits(&flat) || items.is_empty() {
+            out.push(flat);
+            return;
+        }
+
+        out.push(format!("{indent}{prefix}{open}"));
+
+        let inner_indent = INDENT.repeat(level + 1);
+        let joined = items.iter().map(Item::flat).collect::<Vec<_>>().join(", ");
+        let needs_comma = matches!(self, Self::Tuple(v) if v.len() == 1);
+        let inner = format!(
+            "{inner_indent}{joined}{}",
+            if needs_comma { "," } else { "" }
+        );
+
+        if fits(&inner) {
+            out.push(inner);
+        } else {
+            for item in items {
+                item.value.layout(&item.prefix, ",", level + 1, out);
+            }
+        }
+
+        out.push(format!("{indent}{close}{suffix}"));
+    }
+}
+
+struct Item<'a> {
+    prefix: String,
+    value: &'a Expr,
+}
+
+impl<'a> Item<'a> {
+    fn new(prefix: impl Into<String>, value: &'a Expr) -> Self {
+        Self {
+            prefix: prefix.into(),
+            value,
+        }
+    }
+
+    fn flat(&self) -> String {
+        format!("{}{}", self.prefix, self.value.flat())
+    }
+}
+
+impl Arg {
+    pub fn kw(name: &str, value: Expr) -> Self {
+        Self::Keyword(name.into(), value)
+    }
+}
+
+fn fits(line: &str) -> bool {
+    line.chars().count() <= LINE_WIDTH
+}
+
+/// Quotes a string as a double-quoted Python literal.
+pub fn string_literal(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\x{:02x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Stmt {
+    fn layout(&self, level: usize, out: &mut Vec<String>) {
+        let indent = INDENT.repeat(level);
+        match self {
+            Self::Expr(e) => e.layout("", "", level, out),
+            Self::Assign(target, value) => {
+                value.layout(&format!("{} = ", target.flat()), "", level, out)
+            }
+            Self::Return(None) => out.push(format!("{indent}return")),
+            Self::Return(Some(e)) => e.layout("return ", "", level, out),
+            Self::If(cond, body) => {
+                cond.layout("if ", ":", level, out);
+                layout_block(body, level + 1, out);
+            }
+            Self::Def(def) => {
+                let signature = Expr::name(&def.name).call(
+                    def.params
+                        .iter()
+                        .map(|p| Arg::Positional(Expr::name(p)))
+                        .collect(),
+                );
+                signature.layout("def ", ":", level, out);
+                layout_block(&def.body, level + 1, out);
+            }
+            Self::Raw(lines) => {
+                for line in lines {
+                    if line.is_empty() {
+                        out.push(String::new());
+                    } else {
+                        out.push(format!("{indent}{line}"));
+                    }
+                }
+            }
+            Self::Blank => out.push(String::new()),
+        }
+    }
+}
+
+fn layout_block(body: &[Stmt], level: usize, out: &mut Vec<String>) {
+    let start = out.len();
+    for stmt in body {
+        let is_blank = matches!(stmt, Stmt::Blank);
+        let after_blank = out.len() == start || out.last().is_some_and(|l| l.is_empty());
+        if is_blank && after_blank {
+            continue;
+        }
+        stmt.layout(level, out);
+    }
+    while out.len() > start && out.last().is_some_and(|l| l.is_empty()) {
+        out.pop();
+    }
+    if out.len() == start {
+        out.push(format!("{}pass", INDENT.repeat(level)));
+    }
+}
+
+impl Module {
+    pub fn print(&self) -> String {
+        let mut lines = Vec::<String>::new();
+        let mut after_def = false;
+
+        for stmt in &self.body {
+            let is_def = matches!(stmt, Stmt::Def(_));
+            if is_def || after_def {
+                while lines.last().is_some_and(|l| l.is_empty()) {
+                    lines.pop();
+                }
+                if !lines.is_empty() {
+                    lines.extend([String::new(), String::new()]);
+                }
+            }
+            stmt.layout(0, &mut lines);
+            after_def = is_def;
+        }
+
+        while lines.last().is_some_and(|l| l.is_empty()) {
+            lines.pop();
+        }
+
+        let mut s = lines.join("\n");
+        s.push('\n');
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn print(body: Vec<Stmt>) -> String {
+        Module { body }.print()
+    }
+
+    #[test]
+    fn string_literals_are_escaped() {
+        assert_eq!(string_literal("plain"), r#""plain""#);
+        assert_eq!(string_literal("a \"b\" \\ c"), r#""a \"b\" \\ c""#);
+        assert_eq!(string_literal("a\nb\tc\u{1}"), r#""a\nb\tc\x01""#);
+    }
+
+    #[test]
+    fn short_expressions_stay_on_one_line() {
+        let node = Expr::name("nodes").attr("new").call(vec![
+            Arg::Positional(Expr::str("ShaderNodeMix")),
+            Arg::kw("location", Expr::Tuple(vec![Expr::Int(0), Expr::Int(-20)])),
+        ]);
+        let single = Expr::Tuple(vec![Expr::raw("1.0")]);
+        let socket = Expr::name("mix").attr("inputs").index(Expr::Int(4));
+        assert_eq!(
+            print(vec![
+                Stmt::Assign(Expr::name("mix"), node),
+                Stmt::Assign(Expr::name("t"), single),
+                Stmt::Expr(socket),
+            ]),
+            "mix = nodes.new(\"ShaderNodeMix\", location=(0, -20))\nt = (1.0,)\nmix.inputs[4]\n"
+        );
+    }
+
+    #[test]
+    fn long_brackets_are_split() {
+        let args = (0..6)
+            .map(|_| Arg::Positional(Expr::str("aaaaaaaaaa")))
+            .collect();
+        let dict = Expr::Dict(
+            (0..3)
+                .map(|i| {
+                    (
+                        Expr::str(format!("socket_{i:02}_with_a_long_name")),
+                        Expr::Int(i),
+                    )
+                })
+                .collect(),
+        );
+        assert_eq!(
+            print(vec![
+                Stmt::Assign(Expr::name("x"), Expr::name("f").call(args)),
+                Stmt::Assign(Expr::name("y"), dict),
+            ]),
+            r#"x = f(
+    "aaaaaaaaaa", "aaaaaaaaaa", "aaaaaaaaaa", "aaaaaaaaaa", "aaaaaaaaaa", "aaaaaaaaaa"
+)
+y = {
+    "socket_00_with_a_long_name": 0,
+    "socket_01_with_a_long_name": 1,
+    "socket_02_with_a_long_name": 2,
+}
+"#
+        );
+    }
+
+    #[test]
+    fn functions_are_separated_and_never_empty() {
+        let f = FunctionDef {
+            name: "f".into(),
+            params: vec!["a".into(), "b: int".into()],
+            body: vec![
+                Stmt::Blank,
+                Stmt::If(Expr::name("a"), vec![Stmt::Return(Some(Expr::name("b")))]),
+                Stmt::Blank,
+                Stmt::Blank,
+                Stmt::Return(None),
+                Stmt::Blank,
+            ],
+        };
+        let g = FunctionDef {
+            name: "g".into(),
+            params: vec![],
+            body: vec![],
+        };
+        assert_eq!(
+            print(vec![
+                Stmt::Raw(vec!["import bpy".into()]),
+                Stmt::Blank,
+                Stmt::Def(f),
+                Stmt::Def(g),
+                Stmt::Expr(Expr::name("g").call(vec![])),
+            ]),
+            "import bpy
+
+
+def f(a, b: int):
+    if a:
+        return b
+
+    return
+
+
+def g():
+    pass
+
+
+g()
+"
+        );
+    }
+}
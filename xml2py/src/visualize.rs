@@ -0,0 +1,483 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use heck::ToSnakeCase;
+
+use crate::blender::BlenderVersion;
+use crate::codegen::topographic_sort;
+use crate::mapping;
+use eyesight_xml::nodes::{INode, Node};
+use eyesight_xml::schema::{Eyesight, Link, Shader};
+use eyesight_xml::Named;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphOptions {
+    pub format: GraphFormat,
+    /// Show the Blender socket names from the alias tables next to the Eyesight ones.
+    pub show_aliases: bool,
+    /// Inline referenced groups as nested clusters instead of linking to them.
+    pub expand_groups: bool,
+    /// Which release's alias tables `show_aliases` uses.
+    pub blender_version: BlenderVersion,
+}
+
+/// Renders the shader of the material or group called `name`.
+/// Returns `None` if there is no such material or group.
+pub fn render_graph(eyesight: &Eyesight, name: &str, options: &GraphOptions) -> Option<String> {
+    let (kind, shader) = if let Some(m) = eyesight.materials.iter().find(|m| m.name == name) {
+        ("Material", &m.shader)
+    } else {
+        let g = eyesight.groups.iter().find(|g| g.name == name)?;
+        ("Group", &g.shader)
+    };
+
+    let mut builder = Builder {
+        eyesight,
+        options,
+        next_id: 0,
+        stack: vec![name],
+    };
+    let root = builder.cluster(&format!("{kind}: {name}"), shader);
+
+    Some(match options.format {
+        GraphFormat::Dot => to_dot(name, &root),
+        GraphFormat::Mermaid => to_mermaid(&root),
+    })
+}
+
+struct Cluster {
+    id: String,
+    label: String,
+    tiers: Vec<Vec<DiagramNode>>,
+    edges: Vec<DiagramEdge>,
+    children: Vec<Cluster>,
+    /// Node name to diagram id, for the nodes drawn directly in this cluster.
+    ids: BTreeMap<String, String>,
+}
+
+struct DiagramNode {
+    id: String,
+    lines: Vec<String>,
+    /// Name of the referenced group, for group references that were not expanded.
+    link: Option<String>,
+}
+
+struct DiagramEdge {
+    from: String,
+    to: String,
+    label: String,
+}
+
+/// Where the edges touching a node should actually attach.
+/// Expanded group references are replaced by their inner input and output nodes.
+struct Endpoints {
+    inbound: String,
+    outbound: String,
+}
+
+struct Builder<'a> {
+    eyesight: &'a Eyesight,
+    options: &'a GraphOptions,
+    next_id: usize,
+    /// Groups currently being expanded, to avoid infinite recursion.
+    stack: Vec<&'a str>,
+}
+
+impl<'a> Builder<'a> {
+    fn fresh_id(&mut self) -> String {
+        self.next_id += 1;
+        format!("n{}", self.next_id)
+    }
+
+    fn cluster(&mut self, label: &str, shader: &'a Shader) -> Cluster {
+        let mut cluster = Cluster {
+            id: self.fresh_id(),
+            label: label.to_owned(),
+            tiers: vec![],
+            edges: vec![],
+            children: vec![],
+            ids: BTreeMap::new(),
+        };
+
+        let mut endpoints = BTreeMap::<&str, Endpoints>::new();
+
+        for tier in topographic_sort(&shader.nodes, &shader.links) {
+            let mut diagram_tier = vec![];
+            for node in tier {
+                if let Some((child, ends)) = self.expand(node) {
+                    endpoints.insert(node.name(), ends);
+                    cluster.children.push(child);
+                    continue;
+                }
+
+                let id = self.fresh_id();
+                cluster.ids.insert(node.name().to_owned(), id.clone());
+                endpoints.insert(
+                    node.name(),
+                    Endpoints {
+                        inbound: id.clone(),
+                        outbound: id.clone(),
+                    },
+                );
+                diagram_tier.push(DiagramNode {
+                    id,
+                    lines: self.node_lines(node, &shader.links),
+                    link: match node {
+                        Node::Group(g) => Some(g.group_name.clone()),
+                        _ => None,
+                    },
+                });
+            }
+            if !diagram_tier.is_empty() {
+                cluster.tiers.push(diagram_tier);
+            }
+        }
+
+        for link in &shader.links {
+            let (Some(from), Some(to)) = (
+                endpoints.get(&*link.from_node),
+                endpoints.get(&*link.to_node),
+            ) else {
+                continue;
+            };
+            cluster.edges.push(DiagramEdge {
+                from: from.outbound.clone(),
+                to: to.inbound.clone(),
+                label: self.edge_label(shader, link),
+            });
+        }
+
+        cluster
+    }
+
+    fn expand(&mut self, node: &'a Node) -> Option<(Cluster, Endpoints)> {
+        let Node::Group(reference) = node else {
+            return None;
+        };
+        if !self.options.expand_groups || self.stack.contains(&&*reference.group_name) {
+            return None;
+        }
+        let group = self
+            .eyesight
+            .groups
+            .iter()
+            .find(|g| g.name == reference.group_name)?;
+
+        self.stack.push(&group.name);
+        let label = format!("{} ({})", reference.name, group.name);
+        let child = self.cluster(&label, &group.shader);
+        self.stack.pop();
+
+        let find = |pred: fn(&Node) -> bool| {
+            let inner = group.shader.nodes.iter().find(|n| pred(n))?;
+            child.ids.get(inner.name()).cloned()
+        };
+        let inbound = find(|n| matches!(n, Node::GroupInput(_)));
+        let outbound = find(|n| matches!(n, Node::GroupOutput(_)));
+
+        // Fall back to the cluster's first node so that edges still have somewhere to go.
+        let fallback = child.tiers.first()?.first()?.id.clone();
+        let ends = Endpoints {
+            inbound: inbound.unwrap_or_else(|| fallback.clone()),
+            outbound: outbound.unwrap_or(fallback),
+        };
+        Some((child, ends))
+    }
+
+    fn node_lines(&self, node: &Node, links: &[Link]) -> Vec<String> {
+        let mut kind = node.kind().to_owned();
+        if self.options.show_aliases {
+            let node_type = mapping::get().node_type(node, self.options.blender_version);
+            kind += &format!(" ({node_type})");
+        }
+        let mut lines = vec![node.name().to_owned(), kind];
+
+        if let Node::Group(g) = node {
+            lines.push(format!("-> {}", g.group_name));
+        }
+
+        let is_linked = |socket: &str| {
+            links
+                .iter()
+                .any(|l| l.to_node == node.name() && l.to_socket == socket)
+        };
+
+        let literals = match node {
+            Node::Group(g) => g
+                .inputs_
+                .iter()
+                .filter_map(|i| Some((i.name.clone(), i.value?.to_string())))
+                .collect::<Vec<_>>(),
+            _ => node
+                .inputs_override()
+                .into_iter()
+                .map(|i| (i.name, i.value.to_string()))
+                .collect(),
+        };
+
+        for (socket, value) in literals {
+            if !is_linked(&socket) {
+                let socket = self.socket_name(node, &socket, true);
+                lines.push(format!("{socket} = {value}"));
+            }
+        }
+
+        lines
+    }
+
+    fn edge_label(&self, shader: &Shader, link: &Link) -> String {
+        let find = |name: &str| shader.nodes.iter().find(|n| n.name() == name);
+        let from = match find(&link.from_node) {
+            Some(n) => self.socket_name(n, &link.from_socket, false),
+            None => link.from_socket.clone(),
+        };
+        let to = match find(&link.to_node) {
+            Some(n) => self.socket_name(n, &link.to_socket, true),
+            None => link.to_socket.clone(),
+        };
+        format!("{from} → {to}")
+    }
+
+    fn socket_name(&self, node: &Node, socket: &str, is_input: bool) -> String {
+        if !self.options.show_aliases {
+            return socket.to_owned();
+        }
+        let mapping = mapping::get();
+        let version = self.options.blender_version;
+        let node_type = mapping.node_type(node, version);
+        let alias = if is_input {
+            mapping.input_alias(version, node_type, socket)
+        } else {
+            mapping.output_alias(version, node_type, socket)
+        };
+        match alias {
+            Some(alias) => format!("{socket} ({alias})"),
+            None => socket.to_owned(),
+        }
+    }
+}
+
+fn link_target(group_name: &str) -> String {
+    format!("{}.svg", group_name.to_snake_case())
+}
+
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn to_dot(name: &str, root: &Cluster) -> String {
+    let mut out = String::new();
+    writeln!(out, "digraph \"{}\" {{", dot_escape(name)).unwrap();
+    writeln!(out, "    rankdir=LR;").unwrap();
+    writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+    writeln!(out, "    edge [fontname=\"monospace\", fontsize=10];").unwrap();
+    let mut edges = vec![];
+    dot_cluster(&mut out, root, 1, &mut edges);
+    // Edges go at the top level: inside a cluster they would pull their endpoints into it.
+    for edge in edges {
+        let label = dot_escape(&edge.label);
+        writeln!(out, "    {} -> {} [label=\"{label}\"];", edge.from, edge.to).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+fn dot_cluster<'a>(
+    out: &mut String,
+    cluster: &'a Cluster,
+    depth: usize,
+    edges: &mut Vec<&'a DiagramEdge>,
+) {
+    let indent = "    ".repeat(depth);
+    writeln!(out, "{indent}subgraph cluster_{} {{", cluster.id).unwrap();
+    writeln!(out, "{indent}    label=\"{}\";", dot_escape(&cluster.label)).unwrap();
+
+    for tier in &cluster.tiers {
+        writeln!(out, "{indent}    {{").unwrap();
+        writeln!(out, "{indent}        rank=same;").unwrap();
+        for node in tier {
+            let label = node
+                .lines
+                .iter()
+                .map(|l| dot_escape(l))
+                .collect::<Vec<_>>()
+                .join("\\n");
+            write!(out, "{indent}        {} [label=\"{label}\"", node.id).unwrap();
+            if let Some(group_name) = &node.link {
+                let target = link_target(group_name);
+                write!(out, ", URL=\"{target}\", style=rounded").unwrap();
+            }
+            writeln!(out, "];").unwrap();
+        }
+        writeln!(out, "{indent}    }}").unwrap();
+    }
+
+    for child in &cluster.children {
+        dot_cluster(out, child, depth + 1, edges);
+    }
+
+    edges.extend(&cluster.edges);
+
+    writeln!(out, "{indent}}}").unwrap();
+}
+
+fn mermaid_escape(s: &str) -> String {
+    s.replace('"', "#quot;")
+        .replace('<', "#lt;")
+        .replace('>', "#gt;")
+}
+
+fn to_mermaid(root: &Cluster) -> String {
+    let mut out = String::new();
+    writeln!(out, "flowchart LR").unwrap();
+    let mut clicks = vec![];
+    mermaid_cluster(&mut out, root, 1, &mut clicks);
+    for (id, group_name) in clicks {
+        let target = link_target(&group_name);
+        writeln!(
+            out,
+            "    click {id} href \"{target}\" \"{}\"",
+            mermaid_escape(&group_name)
+        )
+        .unwrap();
+    }
+    out
+}
+
+fn mermaid_cluster(
+    out: &mut String,
+    cluster: &Cluster,
+    depth: usize,
+    clicks: &mut Vec<(String, String)>,
+) {
+    let indent = "    ".repeat(depth);
+    let label = mermaid_escape(&cluster.label);
+    writeln!(out, "{indent}subgraph {} [\"{label}\"]", cluster.id).unwrap();
+
+    for node in cluster.tiers.iter().flatten() {
+        let label = node
+            .lines
+            .iter()
+            .map(|l| mermaid_escape(l))
+            .collect::<Vec<_>>()
+            .join("<br/>");
+        if let Some(group_name) = &node.link {
+            writeln!(out, "{indent}    {}([\"{label}\"])", node.id).unwrap();
+            clicks.push((node.id.clone(), group_name.clone()));
+        } else {
+            writeln!(out, "{indent}    {}[\"{label}\"]", node.id).unwrap();
+        }
+    }
+
+    for child in &cluster.children {
+        mermaid_cluster(out, child, depth + 1, clicks);
+    }
+
+    writeln!(out, "{indent}end").unwrap();
+
+    for edge in &cluster.edges {
+        let label = mermaid_escape(&edge.label);
+        writeln!(out, "{indent}{} -->|\"{label}\"| {}", edge.from, edge.to).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eyesight() -> Eyesight {
+        let xml = r#"<eyesight>
+            <material name="M" displacement_method="bump" heterogeneous_volume="false" use_local_tuning="false" use_mis="true" use_transparent_shadow="true" volume_interpolation_method="linear" volume_sampling_method="multiple_importance"><shader>
+                <group name="call" group_name="Inner"><input name="X" type="float" value="0.5"/></group>
+                <diffuse_bsdf name="d"><input name="Roughness" type="float" value="0.25"/></diffuse_bsdf>
+                <connect from_node="call" from_socket="Fac" to_node="d" to_socket="Color"/>
+                <connect from_node="d" from_socket="BSDF" to_node="output" to_socket="Surface"/>
+            </shader></material>
+            <group name="Inner"><shader>
+                <group_input name="in"/>
+                <group_output name="out"/>
+                <group name="again" group_name="Inner"/>
+                <connect from_node="in" from_socket="X" to_node="out" to_socket="Fac"/>
+            </shader></group>
+        </eyesight>"#;
+        quick_xml::de::from_str(xml).unwrap()
+    }
+
+    fn render(name: &str, format: GraphFormat, expand_groups: bool) -> Option<String> {
+        let options = GraphOptions {
+            format,
+            show_aliases: false,
+            expand_groups,
+            blender_version: BlenderVersion::default(),
+        };
+        render_graph(&eyesight(), name, &options)
+    }
+
+    #[test]
+    fn unknown_names_have_no_graph() {
+        assert_eq!(render("Missing", GraphFormat::Dot, false), None);
+    }
+
+    #[test]
+    fn dot_links_to_referenced_groups() {
+        assert_eq!(
+            render("M", GraphFormat::Dot, false).unwrap(),
+            r#"digraph "M" {
+    rankdir=LR;
+    node [shape=box, fontname="monospace"];
+    edge [fontname="monospace", fontsize=10];
+    subgraph cluster_n1 {
+        label="Material: M";
+        {
+            rank=same;
+            n2 [label="call\nGroup\n-> Inner\nX = 0.5", URL="inner.svg", style=rounded];
+        }
+        {
+            rank=same;
+            n3 [label="d\nDiffuseBsdf\nRoughness = 0.25"];
+        }
+    }
+    n2 -> n3 [label="Fac → Color"];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn mermaid_expands_groups_once() {
+        assert_eq!(
+            render("M", GraphFormat::Mermaid, true).unwrap(),
+            r#"flowchart LR
+    subgraph n1 ["Material: M"]
+        n6["d<br/>DiffuseBsdf<br/>Roughness = 0.25"]
+        subgraph n2 ["call (Inner)"]
+            n3["in<br/>GroupInput"]
+            n4(["again<br/>Group<br/>-#gt; Inner"])
+            n5["out<br/>GroupOutput"]
+        end
+        n3 -->|"X → Fac"| n5
+    end
+    n5 -->|"Fac → Color"| n6
+    click n4 href "inner.svg" "Inner"
+"#
+        );
+    }
+
+    #[test]
+    fn aliases_show_blender_names() {
+        let options = GraphOptions {
+            format: GraphFormat::Dot,
+            show_aliases: true,
+            expand_groups: false,
+            blender_version: BlenderVersion::default(),
+        };
+        let dot = render_graph(&eyesight(), "M", &options).unwrap();
+        assert!(dot.contains(r#"n3 [label="d\nDiffuseBsdf (ShaderNodeBsdfDiffuse)\n"#));
+    }
+}